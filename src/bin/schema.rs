@@ -2,7 +2,7 @@ use std::{env, path::Path};
 
 use cosmwasm_schema::{export_schema_with_title, schema_for, write_api};
 
-use selene_markets::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, SeleneCw20Msg};
+use selene_markets::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SeleneCw20Msg};
 
 fn main() {
     let schema = schema_for!(SeleneCw20Msg);
@@ -11,6 +11,7 @@ fn main() {
         instantiate: InstantiateMsg,
         execute: ExecuteMsg,
         query: QueryMsg,
+        migrate: MigrateMsg,
     };
 
     let mut out_dir = env::current_dir().unwrap();
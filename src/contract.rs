@@ -2,38 +2,75 @@
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
 
-use crate::contract_admin_execute::route_admin_execute;
+use crate::contract_admin_execute::{
+    add_market, check_market_params, check_order_limits, route_admin_execute,
+};
 use crate::contract_execute::route_execute;
 
 use crate::contract_query::route_query;
 use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 
 use crate::error::ContractError;
-use crate::state::{ADMIN, MARKET_ID_TRACKER};
+use crate::migrations;
+use crate::state::{ADMIN, DEFAULT_MARKET_PARAMS, FEE_COLLECTOR, MARKET_ID_TRACKER, ORDER_LIMITS};
 
 use cw2::set_contract_version;
 
 // version info for migration info
-const CONTRACT_NAME: &str = "AzoyaLabs:ContractTemplate";
-const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const CONTRACT_NAME: &str = "AzoyaLabs:SeleneMarkets";
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    _msg: InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    ADMIN.save(deps.storage, &info.sender)?;
+
+    let admin = match msg.admin {
+        None => info.sender,
+        Some(admin) => deps.api.addr_validate(&admin)?,
+    };
+    ADMIN.save(deps.storage, &admin)?;
     MARKET_ID_TRACKER.save(deps.storage, &0)?;
 
-    return Ok(Response::new());
+    if let Some(fee_collector) = msg.fee_collector {
+        let fee_collector = deps.api.addr_validate(&fee_collector)?;
+        FEE_COLLECTOR.save(deps.storage, &fee_collector)?;
+    }
+
+    if let Some(params) = msg.default_market_params {
+        check_market_params(deps.as_ref(), &params)?;
+        DEFAULT_MARKET_PARAMS.save(deps.storage, &params)?;
+    }
+
+    if let Some(order_limits) = msg.order_limits {
+        check_order_limits(&order_limits)?;
+        ORDER_LIMITS.save(deps.storage, &order_limits)?;
+    }
+
+    // markets are validated the same way as when added by the admin, any invalid one fails instantiation
+    let mut response = Response::new();
+    for market in msg.markets.unwrap_or_default() {
+        let res = add_market(
+            deps.branch(),
+            market.base_currency,
+            market.quote_currency,
+            market.base_decimals,
+            market.quote_decimals,
+            market.allow_inverse.unwrap_or(false),
+        )?;
+        response = response.add_attributes(res.attributes);
+    }
+
+    return Ok(response);
 }
 
-#[entry_point]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    Ok(Response::default())
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    return migrations::migrate(deps, env, msg, CONTRACT_NAME, CONTRACT_VERSION);
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
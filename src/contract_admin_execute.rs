@@ -1,41 +1,295 @@
-use cosmwasm_std::{DepsMut, Env, MessageInfo, Response};
+use cosmwasm_std::{
+    Addr, Decimal, Deps, DepsMut, Empty, Env, MessageInfo, Order, Response, StdResult, Timestamp,
+    Uint128,
+};
+use cw20::{Cw20QueryMsg, TokenInfoResponse};
 
 use crate::{
+    market_logic::{fee_discounts, liquidity_remover},
     msg::AdminExecuteMsg,
-    state::{ADMIN, MARKET_ID_TRACKER, MARKET_INFO},
-    structs::{CurrencyInfo, MarketInfo},
+    state::{
+        ADMIN, BLOCKLIST, DEFAULT_MARKET_PARAMS, FEE_COLLECTOR, FEE_DISCOUNT_CONFIG,
+        FEE_TIER_CONFIG, LISTING_FEE, MARKET_ALLOWLIST, MARKET_ID_TRACKER, MARKET_INFO,
+        MARKET_PAIRS, ORDER_LIMITS, PENDING_ADMIN, PROTOCOL_FEES, REFERRAL_SHARE_BPS, ROLE_MEMBERS,
+    },
+    structs::{
+        CurrencyInfo, FeeDiscount, FeeDiscountConfig, FeeTier, FeeTierConfig, ListingFee,
+        MarketInfo, MarketParams, MarketStatus, OrderLimits, PendingAdmin, Role, VolumeScope,
+    },
+    utils::{create_funds_message, BPS_DENOMINATOR},
     ContractError,
 };
 
+/// Prices are stored with 18 decimals, more precise currencies could not be scaled for display
+const MAX_CURRENCY_DECIMALS: u8 = 18;
+
+/// Fees can't be more than 10%
+const MAX_FEE_BPS: u16 = 1_000;
+
+/// Referrers can't get more than half of the taker fees
+const MAX_REFERRAL_SHARE_BPS: u16 = 5_000;
+
+/// Fee tokens can't be kept unbonding for more than 90 days
+const MAX_UNBONDING_SECONDS: u64 = 90 * 86_400;
+
+/// Number of orders refunded by a call on a delisted market
+const DEFAULT_REFUND_LIMIT: u32 = 30;
+const MAX_REFUND_LIMIT: u32 = 100;
+
+/// Volumes are kept for a year at most
+const MAX_VOLUME_WINDOW_DAYS: u64 = 365;
+
 pub fn route_admin_execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: AdminExecuteMsg,
 ) -> Result<Response, ContractError> {
-    // validate caller is admin
-    if !match ADMIN.load(deps.storage) {
-        Ok(admin) => admin == info.sender,
-        Err(_) => false,
-    } {
-        return Err(ContractError::Unauthorized {});
-    }
+    check_permission(deps.as_ref(), &info.sender, &msg)?;
 
     match msg {
-        AdminExecuteMsg::UpdateAdmin { new_admin } => update_admin(deps, new_admin),
+        AdminExecuteMsg::ProposeAdmin {
+            new_admin,
+            expires_at,
+        } => propose_admin(deps, env, new_admin, expires_at),
+        AdminExecuteMsg::CancelAdminProposal {} => cancel_admin_proposal(deps),
+        AdminExecuteMsg::RenounceAdmin {} => renounce_admin(deps),
+        AdminExecuteMsg::GrantRole { role, address } => grant_role(deps, role, address),
+        AdminExecuteMsg::RevokeRole { role, address } => revoke_role(deps, role, address),
         AdminExecuteMsg::AddMarket {
             base_currency,
             quote_currency,
-        } => add_market(deps, base_currency, quote_currency),
+            base_decimals,
+            quote_decimals,
+            allow_inverse,
+        } => add_market(
+            deps,
+            base_currency,
+            quote_currency,
+            base_decimals,
+            quote_decimals,
+            allow_inverse.unwrap_or(false),
+        ),
+        AdminExecuteMsg::UpdateMarketFees {
+            market_id,
+            maker_fee_bps,
+            taker_fee_bps,
+        } => update_market_fees(deps, market_id, maker_fee_bps, taker_fee_bps),
+        AdminExecuteMsg::UpdateMarketStatus { market_id, status } => {
+            update_market_status(deps, market_id, status)
+        }
+        AdminExecuteMsg::UpdateMarketPermissioned {
+            market_id,
+            permissioned,
+        } => update_market_permissioned(deps, market_id, permissioned),
+        AdminExecuteMsg::UpdateMarketAllowlist {
+            market_id,
+            add,
+            remove,
+        } => update_market_allowlist(deps, market_id, add, remove),
+        AdminExecuteMsg::UpdateBlocklist { add, remove } => update_blocklist(deps, add, remove),
+        AdminExecuteMsg::CancelBlocklistedOrders { address, limit } => {
+            cancel_blocklisted_orders(deps, env, address, limit)
+        }
+        AdminExecuteMsg::RefundDelistedOrders { market_id, limit } => {
+            refund_delisted_orders(deps, env, market_id, limit)
+        }
+        AdminExecuteMsg::WithdrawFees {
+            currency,
+            recipient,
+        } => withdraw_fees(deps, currency, recipient),
+        AdminExecuteMsg::UpdateFeeTiers {
+            window_days,
+            scope,
+            tiers,
+        } => update_fee_tiers(deps, window_days, scope, tiers),
+        AdminExecuteMsg::UpdateReferralShare { share_bps } => {
+            update_referral_share(deps, share_bps)
+        }
+        AdminExecuteMsg::UpdateFeeDiscounts {
+            token,
+            unbonding_seconds,
+            discounts,
+        } => update_fee_discounts(deps, token, unbonding_seconds, discounts),
+        AdminExecuteMsg::UpdateListingFee { listing_fee } => update_listing_fee(deps, listing_fee),
+        AdminExecuteMsg::UpdateFeeCollector { fee_collector } => {
+            update_fee_collector(deps, fee_collector)
+        }
+        AdminExecuteMsg::UpdateDefaultMarketParams { params } => {
+            update_default_market_params(deps, params)
+        }
+        AdminExecuteMsg::UpdateMarketSizes {
+            market_id,
+            tick_size,
+            lot_size,
+        } => update_market_sizes(deps, market_id, tick_size, lot_size),
+        AdminExecuteMsg::UpdateMarketDecimals {
+            market_id,
+            base_decimals,
+            quote_decimals,
+        } => update_market_decimals(deps, market_id, base_decimals, quote_decimals),
+        AdminExecuteMsg::UpdateOrderLimits { order_limits } => {
+            update_order_limits(deps, order_limits)
+        }
     }
 }
 
-fn update_admin(deps: DepsMut, new_admin: String) -> Result<Response, ContractError> {
+/// The admin can do everything, other addresses need a role allowing the message
+fn check_permission(deps: Deps, sender: &Addr, msg: &AdminExecuteMsg) -> Result<(), ContractError> {
+    match ADMIN.may_load(deps.storage)? {
+        Some(admin) if admin == *sender => return Ok(()),
+        Some(_) => {}
+        // once the admin is renounced, anyone can send the protocol fees to the fee collector
+        None => {
+            if let AdminExecuteMsg::WithdrawFees {
+                recipient: None, ..
+            } = msg
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    let allowed_roles = match msg {
+        // only the admin itself can be transferred or renounced
+        AdminExecuteMsg::ProposeAdmin { .. }
+        | AdminExecuteMsg::CancelAdminProposal {}
+        | AdminExecuteMsg::RenounceAdmin {} => vec![],
+        AdminExecuteMsg::GrantRole { .. }
+        | AdminExecuteMsg::RevokeRole { .. }
+        | AdminExecuteMsg::UpdateDefaultMarketParams { .. } => vec![Role::SuperAdmin],
+        AdminExecuteMsg::AddMarket { .. }
+        | AdminExecuteMsg::UpdateListingFee { .. }
+        | AdminExecuteMsg::UpdateMarketPermissioned { .. }
+        | AdminExecuteMsg::UpdateMarketAllowlist { .. }
+        | AdminExecuteMsg::UpdateBlocklist { .. }
+        | AdminExecuteMsg::CancelBlocklistedOrders { .. }
+        | AdminExecuteMsg::UpdateMarketSizes { .. }
+        | AdminExecuteMsg::UpdateMarketDecimals { .. }
+        | AdminExecuteMsg::UpdateOrderLimits { .. }
+        | AdminExecuteMsg::RefundDelistedOrders { .. } => {
+            vec![Role::SuperAdmin, Role::MarketLister]
+        }
+        AdminExecuteMsg::UpdateMarketStatus { status, .. } => match status {
+            MarketStatus::CancelOnly | MarketStatus::Paused => {
+                vec![Role::SuperAdmin, Role::MarketLister, Role::Guardian]
+            }
+            MarketStatus::Active | MarketStatus::Delisted => {
+                vec![Role::SuperAdmin, Role::MarketLister]
+            }
+        },
+        AdminExecuteMsg::UpdateMarketFees { .. }
+        | AdminExecuteMsg::WithdrawFees { .. }
+        | AdminExecuteMsg::UpdateFeeTiers { .. }
+        | AdminExecuteMsg::UpdateReferralShare { .. }
+        | AdminExecuteMsg::UpdateFeeDiscounts { .. }
+        | AdminExecuteMsg::UpdateFeeCollector { .. } => vec![Role::SuperAdmin, Role::FeeManager],
+    };
+
+    for role in allowed_roles {
+        if ROLE_MEMBERS.has(deps.storage, (role.as_str().to_string(), sender.clone())) {
+            return Ok(());
+        }
+    }
+
+    return Err(ContractError::Unauthorized {});
+}
+
+fn grant_role(deps: DepsMut, role: Role, address: String) -> Result<Response, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+
+    ROLE_MEMBERS.save(
+        deps.storage,
+        (role.as_str().to_string(), address),
+        &Empty {},
+    )?;
+
+    return Ok(Response::new());
+}
+
+fn revoke_role(deps: DepsMut, role: Role, address: String) -> Result<Response, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    let key = (role.as_str().to_string(), address);
+
+    if !ROLE_MEMBERS.has(deps.storage, key.clone()) {
+        return Err(ContractError::RoleNotGranted {});
+    }
+    ROLE_MEMBERS.remove(deps.storage, key);
+
+    return Ok(Response::new());
+}
+
+fn propose_admin(
+    deps: DepsMut,
+    env: Env,
+    new_admin: String,
+    expires_at: Option<Timestamp>,
+) -> Result<Response, ContractError> {
     let new_admin = deps.api.addr_validate(&new_admin)?;
 
-    ADMIN.update(deps.storage, |_| -> Result<_, ContractError> {
-        return Ok(new_admin);
-    })?;
+    if let Some(expires_at) = expires_at {
+        if expires_at <= env.block.time {
+            return Err(ContractError::InvalidExpiry {});
+        }
+    }
+
+    PENDING_ADMIN.save(
+        deps.storage,
+        &PendingAdmin {
+            address: new_admin,
+            expires_at: expires_at,
+        },
+    )?;
+
+    return Ok(Response::new());
+}
+
+fn cancel_admin_proposal(deps: DepsMut) -> Result<Response, ContractError> {
+    if PENDING_ADMIN.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::NoPendingAdmin {});
+    }
+
+    PENDING_ADMIN.remove(deps.storage);
+
+    return Ok(Response::new());
+}
+
+fn renounce_admin(deps: DepsMut) -> Result<Response, ContractError> {
+    // protocol fees can only be withdrawn to the fee collector afterwards
+    if FEE_COLLECTOR.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::NoFeeCollector {});
+    }
+
+    ADMIN.remove(deps.storage);
+    PENDING_ADMIN.remove(deps.storage);
+
+    // roles would keep admin powers otherwise
+    let members: Vec<(String, Addr)> = ROLE_MEMBERS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+    for member in members {
+        ROLE_MEMBERS.remove(deps.storage, member);
+    }
+
+    return Ok(Response::new());
+}
+
+/// The proposed admin takes over, this is not an admin message as the sender is not admin yet
+pub fn accept_admin(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let pending_admin = match PENDING_ADMIN.may_load(deps.storage)? {
+        Some(pending_admin) if pending_admin.address == info.sender => pending_admin,
+        Some(_) => return Err(ContractError::Unauthorized {}),
+        None => return Err(ContractError::NoPendingAdmin {}),
+    };
+
+    if let Some(expires_at) = pending_admin.expires_at {
+        if expires_at <= env.block.time {
+            return Err(ContractError::AdminProposalExpired {});
+        }
+    }
+
+    ADMIN.save(deps.storage, &pending_admin.address)?;
+    PENDING_ADMIN.remove(deps.storage);
 
     return Ok(Response::new());
 }
@@ -44,8 +298,37 @@ pub fn add_market(
     deps: DepsMut,
     base_currency: CurrencyInfo,
     quote_currency: CurrencyInfo,
+    base_decimals: Option<u8>,
+    quote_decimals: Option<u8>,
+    allow_inverse: bool,
 ) -> Result<Response, ContractError> {
+    let base_currency = validate_currency(deps.as_ref(), base_currency)?;
+    let quote_currency = validate_currency(deps.as_ref(), quote_currency)?;
+    if base_currency.get_denom() == quote_currency.get_denom() {
+        return Err(ContractError::SameCurrencyMarket {});
+    }
+
+    // a pair can only be listed once, and its inverse only if explicitly allowed
+    let pair_key = (base_currency.get_denom(), quote_currency.get_denom());
+    if let Some(market_id) = MARKET_PAIRS.may_load(deps.storage, pair_key.clone())? {
+        return Err(ContractError::MarketAlreadyExists { id: market_id });
+    }
+    if !allow_inverse {
+        let inverse_key = (quote_currency.get_denom(), base_currency.get_denom());
+        if let Some(market_id) = MARKET_PAIRS.may_load(deps.storage, inverse_key)? {
+            return Err(ContractError::InverseMarketExists { id: market_id });
+        }
+    }
+
+    let base_decimals = get_currency_decimals(deps.as_ref(), &base_currency, base_decimals)?;
+    let quote_decimals = get_currency_decimals(deps.as_ref(), &quote_currency, quote_decimals)?;
+
+    let params = DEFAULT_MARKET_PARAMS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+
     let curr_id = MARKET_ID_TRACKER.load(deps.storage).unwrap_or_default();
+    MARKET_PAIRS.save(deps.storage, pair_key, &curr_id)?;
     MARKET_INFO.save(
         deps.storage,
         curr_id,
@@ -53,6 +336,14 @@ pub fn add_market(
             market_id: curr_id,
             base_currency: base_currency,
             quote_currency: quote_currency,
+            base_decimals: base_decimals,
+            quote_decimals: quote_decimals,
+            maker_fee_bps: params.maker_fee_bps,
+            taker_fee_bps: params.taker_fee_bps,
+            status: MarketStatus::Active,
+            permissioned: false,
+            tick_size: params.tick_size,
+            lot_size: params.lot_size,
             top_level_bid: None,
             top_level_ask: None,
         },
@@ -60,14 +351,561 @@ pub fn add_market(
 
     MARKET_ID_TRACKER.save(deps.storage, &(curr_id + 1))?;
 
+    return Ok(Response::new().add_attribute("market_id", curr_id.to_string()));
+}
+
+fn update_market_fees(
+    deps: DepsMut,
+    market_id: u64,
+    maker_fee_bps: i16,
+    taker_fee_bps: u16,
+) -> Result<Response, ContractError> {
+    check_market_fees(deps.as_ref(), maker_fee_bps, taker_fee_bps)?;
+
+    let mut market_info = match MARKET_INFO.load(deps.storage, market_id) {
+        Err(_) => return Err(ContractError::UnknownMarketId { id: market_id }),
+        Ok(market_info) => market_info,
+    };
+
+    market_info.maker_fee_bps = maker_fee_bps;
+    market_info.taker_fee_bps = taker_fee_bps;
+    MARKET_INFO.save(deps.storage, market_id, &market_info)?;
+
+    return Ok(Response::new());
+}
+
+fn check_market_fees(
+    deps: Deps,
+    maker_fee_bps: i16,
+    taker_fee_bps: u16,
+) -> Result<(), ContractError> {
+    if maker_fee_bps > MAX_FEE_BPS as i16 || taker_fee_bps > MAX_FEE_BPS {
+        return Err(ContractError::InvalidFee {
+            max_bps: MAX_FEE_BPS,
+        });
+    }
+
+    let fee_tier_config = FEE_TIER_CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    return check_rebates_covered(
+        maker_fee_bps,
+        taker_fee_bps,
+        &fee_tier_config.tiers,
+        fee_discounts::get_max_discount_bps(deps.storage)?,
+    );
+}
+
+/// Sizes must be positive when set, and fees follow the same rules as the fees of a market
+pub fn check_market_params(deps: Deps, params: &MarketParams) -> Result<(), ContractError> {
+    check_market_sizes(params.tick_size, params.lot_size)?;
+    return check_market_fees(deps, params.maker_fee_bps, params.taker_fee_bps);
+}
+
+fn check_market_sizes(
+    tick_size: Option<Decimal>,
+    lot_size: Option<Uint128>,
+) -> Result<(), ContractError> {
+    if tick_size == Some(Decimal::zero()) || lot_size == Some(Uint128::zero()) {
+        return Err(ContractError::InvalidMarketParams {});
+    }
+
+    return Ok(());
+}
+
+fn update_default_market_params(
+    deps: DepsMut,
+    params: MarketParams,
+) -> Result<Response, ContractError> {
+    check_market_params(deps.as_ref(), &params)?;
+    DEFAULT_MARKET_PARAMS.save(deps.storage, &params)?;
+
+    return Ok(Response::new());
+}
+
+fn update_market_sizes(
+    deps: DepsMut,
+    market_id: u64,
+    tick_size: Option<Decimal>,
+    lot_size: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    check_market_sizes(tick_size, lot_size)?;
+
+    let mut market_info = match MARKET_INFO.load(deps.storage, market_id) {
+        Err(_) => return Err(ContractError::UnknownMarketId { id: market_id }),
+        Ok(market_info) => market_info,
+    };
+
+    // resting orders keep their price and quantity, the sizes apply to new orders
+    market_info.tick_size = tick_size;
+    market_info.lot_size = lot_size;
+    MARKET_INFO.save(deps.storage, market_id, &market_info)?;
+
+    return Ok(Response::new());
+}
+
+fn update_fee_collector(
+    deps: DepsMut,
+    fee_collector: Option<String>,
+) -> Result<Response, ContractError> {
+    match fee_collector {
+        None => FEE_COLLECTOR.remove(deps.storage),
+        Some(fee_collector) => {
+            let fee_collector = deps.api.addr_validate(&fee_collector)?;
+            FEE_COLLECTOR.save(deps.storage, &fee_collector)?;
+        }
+    }
+
+    return Ok(Response::new());
+}
+
+fn update_market_status(
+    deps: DepsMut,
+    market_id: u64,
+    status: MarketStatus,
+) -> Result<Response, ContractError> {
+    let mut market_info = match MARKET_INFO.load(deps.storage, market_id) {
+        Err(_) => return Err(ContractError::UnknownMarketId { id: market_id }),
+        Ok(market_info) => market_info,
+    };
+
+    if market_info.status == MarketStatus::Delisted {
+        return Err(ContractError::MarketDelisted {});
+    }
+
+    // a delisted pair can be listed again
+    if status == MarketStatus::Delisted {
+        let pair_key = (
+            market_info.base_currency.get_denom(),
+            market_info.quote_currency.get_denom(),
+        );
+        if MARKET_PAIRS.may_load(deps.storage, pair_key.clone())? == Some(market_id) {
+            MARKET_PAIRS.remove(deps.storage, pair_key);
+        }
+    }
+
+    market_info.status = status;
+    MARKET_INFO.save(deps.storage, market_id, &market_info)?;
+
     return Ok(Response::new());
 }
 
+/// Decimals only change how prices and quantities are read, resting orders are left as they are
+fn update_market_decimals(
+    deps: DepsMut,
+    market_id: u64,
+    base_decimals: Option<u8>,
+    quote_decimals: Option<u8>,
+) -> Result<Response, ContractError> {
+    let mut market_info = match MARKET_INFO.load(deps.storage, market_id) {
+        Err(_) => return Err(ContractError::UnknownMarketId { id: market_id }),
+        Ok(market_info) => market_info,
+    };
+
+    market_info.base_decimals =
+        get_currency_decimals(deps.as_ref(), &market_info.base_currency, base_decimals)?;
+    market_info.quote_decimals =
+        get_currency_decimals(deps.as_ref(), &market_info.quote_currency, quote_decimals)?;
+    MARKET_INFO.save(deps.storage, market_id, &market_info)?;
+
+    return Ok(Response::new());
+}
+
+fn update_market_permissioned(
+    deps: DepsMut,
+    market_id: u64,
+    permissioned: bool,
+) -> Result<Response, ContractError> {
+    let mut market_info = match MARKET_INFO.load(deps.storage, market_id) {
+        Err(_) => return Err(ContractError::UnknownMarketId { id: market_id }),
+        Ok(market_info) => market_info,
+    };
+
+    market_info.permissioned = permissioned;
+    MARKET_INFO.save(deps.storage, market_id, &market_info)?;
+
+    return Ok(Response::new());
+}
+
+fn update_market_allowlist(
+    deps: DepsMut,
+    market_id: u64,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    if !MARKET_INFO.has(deps.storage, market_id) {
+        return Err(ContractError::UnknownMarketId { id: market_id });
+    }
+
+    for address in add {
+        let address = deps.api.addr_validate(&address)?;
+        MARKET_ALLOWLIST.save(deps.storage, (market_id, address), &Empty {})?;
+    }
+    for address in remove {
+        let address = deps.api.addr_validate(&address)?;
+        MARKET_ALLOWLIST.remove(deps.storage, (market_id, address));
+    }
+
+    return Ok(Response::new());
+}
+
+fn update_blocklist(
+    deps: DepsMut,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    for address in add {
+        let address = deps.api.addr_validate(&address)?;
+        BLOCKLIST.save(deps.storage, address, &Empty {})?;
+    }
+    for address in remove {
+        let address = deps.api.addr_validate(&address)?;
+        BLOCKLIST.remove(deps.storage, address);
+    }
+
+    return Ok(Response::new());
+}
+
+/// Resting orders of a blocklisted address are cancelled in batches, so they stop being filled
+/// what the orders locked is credited to its internal balance, which it can still withdraw
+fn cancel_blocklisted_orders(
+    deps: DepsMut,
+    env: Env,
+    address: String,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let address = deps.api.addr_validate(&address)?;
+    if !BLOCKLIST.has(deps.storage, address.clone()) {
+        return Err(ContractError::NotBlocklisted { address: address });
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_REFUND_LIMIT).min(MAX_REFUND_LIMIT);
+    let cancelled =
+        liquidity_remover::cancel_user_orders(deps.storage, &address, limit, env.block.time)?;
+    let has_open_orders = liquidity_remover::has_open_orders(deps.storage, &address)?;
+
+    return Ok(Response::new()
+        .add_attribute("cancelled_orders", cancelled.to_string())
+        .add_attribute("has_open_orders", has_open_orders.to_string()));
+}
+
+fn update_listing_fee(
+    deps: DepsMut,
+    listing_fee: Option<ListingFee>,
+) -> Result<Response, ContractError> {
+    match listing_fee {
+        None => LISTING_FEE.remove(deps.storage),
+        Some(listing_fee) => {
+            if listing_fee.amount.is_zero() {
+                return Err(ContractError::ZeroAmount {});
+            }
+
+            let listing_fee = ListingFee {
+                currency: validate_currency(deps.as_ref(), listing_fee.currency)?,
+                amount: listing_fee.amount,
+            };
+            LISTING_FEE.save(deps.storage, &listing_fee)?;
+        }
+    }
+
+    return Ok(Response::new());
+}
+
+/// Caps must allow at least one order when set
+pub fn check_order_limits(order_limits: &OrderLimits) -> Result<(), ContractError> {
+    if order_limits.max_orders_per_user == Some(0) || order_limits.max_orders_per_level == Some(0) {
+        return Err(ContractError::InvalidOrderLimits {});
+    }
+
+    return Ok(());
+}
+
+/// Orders already open above the new caps stay in the book, the caps apply to new orders
+fn update_order_limits(
+    deps: DepsMut,
+    order_limits: OrderLimits,
+) -> Result<Response, ContractError> {
+    check_order_limits(&order_limits)?;
+    ORDER_LIMITS.save(deps.storage, &order_limits)?;
+
+    return Ok(Response::new());
+}
+
+/// Resting orders of a delisted market are refunded in batches, as a whole book can't be refunded in one transaction
+fn refund_delisted_orders(
+    deps: DepsMut,
+    env: Env,
+    market_id: u64,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let market_info = match MARKET_INFO.load(deps.storage, market_id) {
+        Err(_) => return Err(ContractError::UnknownMarketId { id: market_id }),
+        Ok(market_info) => market_info,
+    };
+
+    if market_info.status != MarketStatus::Delisted {
+        return Err(ContractError::MarketNotDelisted { id: market_id });
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_REFUND_LIMIT).min(MAX_REFUND_LIMIT);
+    let refunded =
+        liquidity_remover::refund_orders(deps.storage, market_id, limit, env.block.time)?;
+    let book_empty = liquidity_remover::is_book_empty(deps.storage, market_id)?;
+
+    return Ok(Response::new()
+        .add_attribute("refunded_orders", refunded.to_string())
+        .add_attribute("book_empty", book_empty.to_string()));
+}
+
+/// Replace the fee tier table, an empty table gives everyone the fees of the markets
+fn update_fee_tiers(
+    deps: DepsMut,
+    window_days: u64,
+    scope: VolumeScope,
+    tiers: Vec<FeeTier>,
+) -> Result<Response, ContractError> {
+    if window_days == 0 || window_days > MAX_VOLUME_WINDOW_DAYS {
+        return Err(ContractError::InvalidVolumeWindow {
+            max_days: MAX_VOLUME_WINDOW_DAYS,
+        });
+    }
+
+    if tiers
+        .iter()
+        .any(|tier| tier.maker_fee_bps > MAX_FEE_BPS as i16 || tier.taker_fee_bps > MAX_FEE_BPS)
+    {
+        return Err(ContractError::InvalidFee {
+            max_bps: MAX_FEE_BPS,
+        });
+    }
+
+    if tiers
+        .windows(2)
+        .any(|pair| pair[0].min_volume >= pair[1].min_volume)
+    {
+        return Err(ContractError::InvalidFeeTiers {});
+    }
+
+    let max_discount_bps = fee_discounts::get_max_discount_bps(deps.storage)?;
+    for elem in MARKET_INFO.range(deps.storage, None, None, Order::Ascending) {
+        let (_, market_info) = elem?;
+        check_rebates_covered(
+            market_info.maker_fee_bps,
+            market_info.taker_fee_bps,
+            &tiers,
+            max_discount_bps,
+        )?;
+    }
+    // new markets get the default fees
+    let default_params = DEFAULT_MARKET_PARAMS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    check_rebates_covered(
+        default_params.maker_fee_bps,
+        default_params.taker_fee_bps,
+        &tiers,
+        max_discount_bps,
+    )?;
+
+    FEE_TIER_CONFIG.save(
+        deps.storage,
+        &FeeTierConfig {
+            window_days: window_days,
+            scope: scope,
+            tiers: tiers,
+        },
+    )?;
+
+    return Ok(Response::new());
+}
+
+fn update_referral_share(deps: DepsMut, share_bps: u16) -> Result<Response, ContractError> {
+    if share_bps > MAX_REFERRAL_SHARE_BPS {
+        return Err(ContractError::InvalidReferralShare {
+            max_bps: MAX_REFERRAL_SHARE_BPS,
+        });
+    }
+
+    REFERRAL_SHARE_BPS.save(deps.storage, &share_bps)?;
+
+    return Ok(Response::new());
+}
+
+/// The fee token is set once, as users keep their tokens locked in it
+/// while unbonding period and discounts can be updated
+fn update_fee_discounts(
+    deps: DepsMut,
+    token: String,
+    unbonding_seconds: u64,
+    discounts: Vec<FeeDiscount>,
+) -> Result<Response, ContractError> {
+    let token = deps.api.addr_validate(&token)?;
+    if let Some(config) = FEE_DISCOUNT_CONFIG.may_load(deps.storage)? {
+        if config.token != token {
+            return Err(ContractError::FeeTokenAlreadySet {});
+        }
+    }
+
+    if unbonding_seconds > MAX_UNBONDING_SECONDS {
+        return Err(ContractError::InvalidUnbondingPeriod {
+            max_seconds: MAX_UNBONDING_SECONDS,
+        });
+    }
+
+    if discounts
+        .iter()
+        .any(|discount| discount.discount_bps > BPS_DENOMINATOR)
+        || discounts
+            .windows(2)
+            .any(|pair| pair[0].min_locked >= pair[1].min_locked)
+    {
+        return Err(ContractError::InvalidFeeDiscounts {});
+    }
+
+    let fee_tier_config = FEE_TIER_CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    let max_discount_bps = discounts
+        .iter()
+        .map(|discount| discount.discount_bps)
+        .max()
+        .unwrap_or_default();
+    for elem in MARKET_INFO.range(deps.storage, None, None, Order::Ascending) {
+        let (_, market_info) = elem?;
+        check_rebates_covered(
+            market_info.maker_fee_bps,
+            market_info.taker_fee_bps,
+            &fee_tier_config.tiers,
+            max_discount_bps,
+        )?;
+    }
+    // new markets get the default fees
+    let default_params = DEFAULT_MARKET_PARAMS
+        .may_load(deps.storage)?
+        .unwrap_or_default();
+    check_rebates_covered(
+        default_params.maker_fee_bps,
+        default_params.taker_fee_bps,
+        &fee_tier_config.tiers,
+        max_discount_bps,
+    )?;
+
+    FEE_DISCOUNT_CONFIG.save(
+        deps.storage,
+        &FeeDiscountConfig {
+            token: token,
+            unbonding_seconds: unbonding_seconds,
+            discounts: discounts,
+        },
+    )?;
+
+    return Ok(Response::new());
+}
+
+/// Fees of a user are the lowest of the market and of its tier, and takers can get a discount on top
+/// so the lowest taker fee must cover the highest rebate, whoever the maker and the taker are
+fn check_rebates_covered(
+    market_maker_fee_bps: i16,
+    market_taker_fee_bps: u16,
+    tiers: &[FeeTier],
+    max_discount_bps: u16,
+) -> Result<(), ContractError> {
+    let lowest_maker_fee_bps = tiers
+        .iter()
+        .map(|tier| tier.maker_fee_bps)
+        .fold(market_maker_fee_bps, i16::min);
+    let lowest_taker_fee_bps = tiers
+        .iter()
+        .map(|tier| tier.taker_fee_bps)
+        .fold(market_taker_fee_bps, u16::min);
+    let lowest_taker_fee_bps =
+        fee_discounts::apply_discount(lowest_taker_fee_bps, max_discount_bps);
+
+    if i32::from(lowest_taker_fee_bps) + i32::from(lowest_maker_fee_bps) < 0 {
+        return Err(ContractError::RebateNotCovered {});
+    }
+
+    return Ok(());
+}
+
+fn withdraw_fees(
+    deps: DepsMut,
+    currency: CurrencyInfo,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => FEE_COLLECTOR
+            .may_load(deps.storage)?
+            .ok_or(ContractError::NoFeeCollector {})?,
+    };
+
+    let fees = PROTOCOL_FEES
+        .may_load(deps.storage, currency.get_key())?
+        .unwrap_or_default();
+    if fees.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    PROTOCOL_FEES.remove(deps.storage, currency.get_key());
+
+    return Ok(Response::new().add_message(create_funds_message(fees, currency, recipient)));
+}
+
+/// Native denoms can't be empty and cw20 addresses must be valid
+/// cw20 tokens are then probed when reading their decimals
+fn validate_currency(deps: Deps, currency: CurrencyInfo) -> Result<CurrencyInfo, ContractError> {
+    return match currency {
+        CurrencyInfo::Native { denom } => {
+            if denom.trim().is_empty() {
+                return Err(ContractError::InvalidCurrency {});
+            }
+
+            Ok(CurrencyInfo::Native { denom: denom })
+        }
+        CurrencyInfo::Cw20 { address } => {
+            let address = deps
+                .api
+                .addr_validate(&address)
+                .map_err(|_| ContractError::InvalidCurrency {})?;
+
+            Ok(CurrencyInfo::Cw20 {
+                address: address.to_string(),
+            })
+        }
+    };
+}
+
+/// Decimals of a cw20 are read from the token, while they must be provided for a native currency
+pub fn get_currency_decimals(
+    deps: Deps,
+    currency: &CurrencyInfo,
+    decimals: Option<u8>,
+) -> Result<u8, ContractError> {
+    let decimals = match currency {
+        CurrencyInfo::Native { .. } => match decimals {
+            None => return Err(ContractError::MissingDecimals {}),
+            Some(decimals) => decimals,
+        },
+        CurrencyInfo::Cw20 { address } => {
+            // fails if the address is not a cw20 token
+            let token_info: TokenInfoResponse = deps
+                .querier
+                .query_wasm_smart(address, &Cw20QueryMsg::TokenInfo {})
+                .map_err(|_| ContractError::InvalidCurrency {})?;
+
+            token_info.decimals
+        }
+    };
+
+    if decimals > MAX_CURRENCY_DECIMALS {
+        return Err(ContractError::InvalidDecimals {});
+    }
+
+    return Ok(decimals);
+}
+
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::testing::mock_dependencies;
 
-    use crate::structs::CurrencyInfo;
+    use crate::{structs::CurrencyInfo, ContractError};
 
     use super::add_market;
 
@@ -83,6 +921,123 @@ mod tests {
             CurrencyInfo::Native {
                 denom: "heur".into(),
             },
+            Some(6),
+            Some(6),
+            false,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn admin_add_market_native_needs_decimals() {
+        let mut deps = mock_dependencies();
+
+        let err = add_market(
+            deps.as_mut(),
+            CurrencyInfo::Native {
+                denom: "husd".into(),
+            },
+            CurrencyInfo::Native {
+                denom: "heur".into(),
+            },
+            Some(6),
+            None,
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::MissingDecimals {});
+
+        let err = add_market(
+            deps.as_mut(),
+            CurrencyInfo::Native {
+                denom: "husd".into(),
+            },
+            CurrencyInfo::Native {
+                denom: "heur".into(),
+            },
+            Some(6),
+            Some(19),
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidDecimals {});
+    }
+
+    #[test]
+    fn admin_add_market_rejects_invalid_pairs() {
+        let mut deps = mock_dependencies();
+        let husd = CurrencyInfo::Native {
+            denom: "husd".into(),
+        };
+        let heur = CurrencyInfo::Native {
+            denom: "heur".into(),
+        };
+
+        let err = add_market(
+            deps.as_mut(),
+            husd.clone(),
+            husd.clone(),
+            Some(6),
+            Some(6),
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::SameCurrencyMarket {});
+
+        // not a cw20 token
+        let err = add_market(
+            deps.as_mut(),
+            CurrencyInfo::Cw20 {
+                address: "notatoken".into(),
+            },
+            husd.clone(),
+            None,
+            Some(6),
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidCurrency {});
+
+        add_market(
+            deps.as_mut(),
+            heur.clone(),
+            husd.clone(),
+            Some(6),
+            Some(6),
+            false,
+        )
+        .unwrap();
+
+        let err = add_market(
+            deps.as_mut(),
+            heur.clone(),
+            husd.clone(),
+            Some(6),
+            Some(6),
+            true,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::MarketAlreadyExists { id: 0 });
+
+        let err = add_market(
+            deps.as_mut(),
+            husd.clone(),
+            heur.clone(),
+            Some(6),
+            Some(6),
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InverseMarketExists { id: 0 });
+
+        // the inverse pair can be listed on purpose
+        add_market(
+            deps.as_mut(),
+            husd.clone(),
+            heur.clone(),
+            Some(6),
+            Some(6),
+            true,
         )
         .unwrap();
     }
@@ -1,19 +1,28 @@
 use cosmwasm_std::{
-    from_binary, Addr, CosmosMsg, Decimal, DepsMut, Env, MessageInfo, Response, Uint128,
+    from_binary, Addr, CosmosMsg, Decimal, DepsMut, Env, MessageInfo, Response, StdError,
+    StdResult, Storage, Uint128,
 };
 
 use crate::{
+    contract_admin_execute::{accept_admin, add_market},
     market_logic::{liquidity_consumer, liquidity_provider, liquidity_remover},
     msg::{ExecuteMsg, SeleneCw20Msg},
-    state::{LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO, USER_ORDERS},
-    structs::{CurrencyStatus, MarketInfo, OrderSide, UserOrderRecord},
-    utils::{check_only_one_fund, create_funds_message, create_id_level_no_status},
+    state::{
+        user_orders, FEE_DISCOUNT_CONFIG, FEE_TOKEN_LOCKS, FEE_TOKEN_UNBONDINGS, LEVELS_DATA,
+        LEVEL_ORDERS, LISTING_FEE, MARKET_INFO, REFERRAL_REWARDS, REFERRERS,
+    },
+    state_utils,
+    structs::{
+        CurrencyInfo, CurrencyStatus, FundingSource, MarketInfo, OrderSide, OrderStatus,
+        Settlement, Unbonding,
+    },
+    utils::{check_only_one_fund, compute_escrow, create_funds_message, create_id_level_no_status},
     ContractError,
 };
 
 pub fn route_execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
@@ -24,6 +33,7 @@ pub fn route_execute(
             match selene_msg {
                 SeleneCw20Msg::LimitOrder { market_id, price } => execute_limit_order_cw20(
                     deps,
+                    env,
                     sender,
                     info.sender.to_string(),
                     receive_msg.amount,
@@ -32,23 +42,83 @@ pub fn route_execute(
                 ),
                 SeleneCw20Msg::MarketOrder { market_id } => execute_market_order_cw20(
                     deps,
+                    env,
                     sender,
                     info.sender.to_string(),
                     receive_msg.amount,
                     market_id,
                 ),
+                SeleneCw20Msg::Deposit {} => {
+                    execute_deposit_cw20(deps, sender, info.sender.to_string(), receive_msg.amount)
+                }
+                SeleneCw20Msg::LockFeeToken {} => {
+                    execute_lock_fee_token(deps, sender, info.sender, receive_msg.amount)
+                }
+                SeleneCw20Msg::CreateMarket {
+                    base_currency,
+                    quote_currency,
+                    base_decimals,
+                    quote_decimals,
+                } => execute_create_market(
+                    deps,
+                    CurrencyInfo::Cw20 {
+                        address: info.sender.to_string(),
+                    },
+                    receive_msg.amount,
+                    base_currency,
+                    quote_currency,
+                    base_decimals,
+                    quote_decimals,
+                ),
             }
         }
         ExecuteMsg::LimitOrder {
             market_id,
             price,
             //order_side,
-        } => execute_limit_order(deps, info, market_id, price),
+        } => execute_limit_order(deps, env, info, market_id, price),
         ExecuteMsg::RemoveLimitOrder { market_id, price } => {
-            execute_remove_limit_order(deps, info, market_id, price)
+            execute_remove_limit_order(deps, env, info, market_id, price)
         }
 
-        ExecuteMsg::MarketOrder { market_id } => execute_market_order(deps, info, market_id),
+        ExecuteMsg::MarketOrder { market_id } => execute_market_order(deps, env, info, market_id),
+
+        ExecuteMsg::Deposit {} => execute_deposit(deps, info),
+        ExecuteMsg::Withdraw { currency, amount } => execute_withdraw(deps, info, currency, amount),
+        ExecuteMsg::PlaceOrderFromBalance {
+            market_id,
+            currency,
+            amount,
+            price,
+        } => execute_place_order_from_balance(deps, env, info, market_id, currency, amount, price),
+        ExecuteMsg::RegisterReferrer { referrer } => {
+            execute_register_referrer(deps, info, referrer)
+        }
+        ExecuteMsg::ClaimReferralRewards { currency } => {
+            execute_claim_referral_rewards(deps, info, currency)
+        }
+        ExecuteMsg::AcceptAdmin {} => accept_admin(deps, env, info),
+        ExecuteMsg::CreateMarket {
+            base_currency,
+            quote_currency,
+            base_decimals,
+            quote_decimals,
+        } => {
+            let payment = check_only_one_fund(&info)?;
+            execute_create_market(
+                deps,
+                CurrencyInfo::Native {
+                    denom: payment.denom,
+                },
+                payment.amount,
+                base_currency,
+                quote_currency,
+                base_decimals,
+                quote_decimals,
+            )
+        }
+        ExecuteMsg::UnlockFeeToken { amount } => execute_unlock_fee_token(deps, env, info, amount),
+        ExecuteMsg::ClaimUnlockedFeeToken {} => execute_claim_unlocked_fee_token(deps, env, info),
 
         // shouldn't happen here
         ExecuteMsg::Admin(_) => return Err(ContractError::Never {}),
@@ -57,620 +127,586 @@ pub fn route_execute(
 
 fn execute_remove_limit_order(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     market_id: u64,
     order_price: Decimal,
 ) -> Result<Response, ContractError> {
     let market_info = MARKET_INFO.load(deps.storage, market_id)?;
-    let currency_info =
-        market_info.get_currency_status_from_price(deps.as_ref().storage, order_price)?;
+    market_info.check_can_cancel_orders()?;
 
     // check if order exists
-    let mut user_orders = USER_ORDERS.load(deps.storage, info.sender.clone())?;
-    let target_order_id = user_orders
-        .iter()
-        .position(|order| order.market_id == market_id && order.price == order_price);
+    let target_order = state_utils::load_user_market_orders(deps.storage, &info.sender, market_id)?
+        .into_iter()
+        .find(|order| order.price == order_price);
 
-    let transfer_msg = match target_order_id {
+    match target_order {
         None => return Err(ContractError::OrderDoesNotExist {}),
-        Some(order_id) => {
-            let order_data = user_orders[order_id].clone();
-
-            // remove order from user list of orders
-            user_orders.swap_remove(order_id);
-            USER_ORDERS.save(deps.storage, info.sender.clone(), &user_orders)?;
-
-            // and remove from book
+        Some(order_data) => {
+            // remove from book
             liquidity_remover::remove_order(
                 deps.storage,
-                info.clone(),
+                order_data.order_id,
                 market_id,
                 order_price,
-                order_data.quantity,
-                order_data.order_side.to_owned(),
             )?;
 
-            // determine whether this is a base currency or a quote currency
-            let currency_status = market_info
-                .get_currency_status(&currency_info.get_denom())
-                .unwrap();
+            // refund exactly what the order still locks, in the currency it was funded with
+            // refunds are credited to the internal balance of the user
+            let currency_info =
+                market_info.get_currency_info_from_side(order_data.order_side.clone());
+            state_utils::credit_balance(
+                deps.storage,
+                &info.sender,
+                &currency_info,
+                order_data.locked,
+            )?;
 
-            let order_quantity = match currency_status {
-                CurrencyStatus::BaseCurrency => order_data.quantity,
-                CurrencyStatus::QuoteCurrency => {
-                    order_data.quantity.checked_mul_floor(order_price).unwrap()
-                }
-            };
-            create_funds_message(order_quantity, currency_info, info.sender)
+            // and close the order
+            state_utils::close_order(
+                deps.storage,
+                order_data,
+                OrderStatus::Cancelled,
+                env.block.time,
+            )?;
         }
     };
 
-    return Ok(Response::new().add_message(transfer_msg));
+    return Ok(Response::new());
 }
 
 fn execute_limit_order_cw20(
     deps: DepsMut,
+    env: Env,
     sender: Addr,
     currency: String,
     order_quantity: Uint128,
     market_id: u64,
     order_price: Decimal,
 ) -> Result<Response, ContractError> {
-    // load market info
-    let market_info = match MARKET_INFO.load(deps.storage, market_id) {
-        Err(_) => return Err(ContractError::UnknownMarketId { id: market_id }),
-        Ok(market_info) => market_info,
-    };
-
-    // determine whether this is a base currency or a quote currency
-    let currency_status = market_info.get_currency_status(&currency)?;
-
-    let order_quantity = match currency_status {
-        CurrencyStatus::BaseCurrency => order_quantity,
-        CurrencyStatus::QuoteCurrency => order_quantity.checked_div_floor(order_price).unwrap(),
-    };
-
-    let order_side = MarketInfo::get_order_side_from_currency_status(currency_status.clone());
-
-    // check if the user already has an order at this price
-    if let Ok(user_orders) = USER_ORDERS.load(deps.storage, sender.clone()) {
-        match user_orders.into_iter().find(|order| {
-            order.market_id == market_id
-                && order.price == order_price
-                && order.order_side == order_side
-        }) {
-            None => (),
-            Some(_) => {
-                // update user orders
-                USER_ORDERS.update(
-                    deps.storage,
-                    sender.clone(),
-                    |orders| -> Result<_, ContractError> {
-                        let orders = orders
-                            .unwrap()
-                            .into_iter()
-                            .map(|mut order| {
-                                if order.market_id == market_id && order.price == order_price {
-                                    order.quantity += order_quantity;
-                                }
-
-                                order
-                            })
-                            .collect();
-
-                        return Ok(orders);
-                    },
-                )?;
-
-                // update level orders
-                let level_id = create_id_level_no_status(&market_info, order_price);
-                LEVEL_ORDERS.update(
-                    deps.storage,
-                    level_id,
-                    |level_orders| -> Result<_, ContractError> {
-                        let level_orders = level_orders
-                            .unwrap()
-                            .into_iter()
-                            .map(|mut order| {
-                                if order.user == sender {
-                                    order.amount += order_quantity;
-                                }
-
-                                order
-                            })
-                            .collect();
-
-                        return Ok(level_orders);
-                    },
-                )?;
-
-                return Ok(Response::new());
-            }
-        }
-    }
-
-    let mut out_msgs: Vec<CosmosMsg> = vec![];
-
-    // then determine if it's a buy or a sell depending on currency_status
-    // and if taker or maker
-    match currency_status {
-        CurrencyStatus::BaseCurrency => {
-            // if we receive BaseCurrency, then it's a sell order
-            // so check if it's taker or maker
-            match market_info.top_level_bid {
-                // no bids registered, so it's a limit maker order
-                None => {
-                    // no bids registered, so it's a limit maker order
-                    USER_ORDERS.update(
-                        deps.storage,
-                        sender.clone(),
-                        |orders| -> Result<_, ContractError> {
-                            let mut orders = orders.unwrap_or_default();
-                            orders.push(UserOrderRecord {
-                                order_side: OrderSide::Sell,
-                                price: order_price,
-                                market_id: market_id,
-                                quantity: order_quantity,
-                            });
-
-                            return Ok(orders);
-                        },
-                    )?;
-
-                    liquidity_provider::process_limit_maker(
-                        deps.storage,
-                        sender,
-                        market_id,
-                        order_price,
-                        order_quantity,
-                        OrderSide::Sell,
-                    )?;
-                }
-                // bids registered, compare to price
-                Some(val_id_top_level_bid) => {
-                    let top_bid_level_data =
-                        LEVELS_DATA.load(deps.storage, val_id_top_level_bid)?;
-                    if top_bid_level_data.price < order_price {
-                        // this is a limit maker
-                        USER_ORDERS.update(
-                            deps.storage,
-                            sender.clone(),
-                            |orders| -> Result<_, ContractError> {
-                                let mut orders = orders.unwrap_or_default();
-                                orders.push(UserOrderRecord {
-                                    order_side: OrderSide::Sell,
-                                    price: order_price,
-                                    market_id: market_id,
-                                    quantity: order_quantity,
-                                });
-
-                                return Ok(orders);
-                            },
-                        )?;
-
-                        liquidity_provider::process_limit_maker(
-                            deps.storage,
-                            sender,
-                            market_id,
-                            order_price,
-                            order_quantity,
-                            OrderSide::Sell,
-                        )?;
-                    } else {
-                        //return Err(ContractError::Unimplemented {});
-
-                        // limit taker
-                        out_msgs = liquidity_consumer::process_liquidity_taker(
-                            deps,
-                            sender,
-                            market_id,
-                            Some(order_price),
-                            order_quantity,
-                            OrderSide::Sell,
-                        )?;
-                    }
-                }
-            }
-        }
-        // if we receive QuoteCurrency, then it's a buy order
-        // so check if it's taker or maker
-        CurrencyStatus::QuoteCurrency => {
-            match market_info.top_level_ask {
-                None => {
-                    // no asks registered, so it's a limit maker order
-                    USER_ORDERS.update(
-                        deps.storage,
-                        sender.clone(),
-                        |orders| -> Result<_, ContractError> {
-                            let mut orders = orders.unwrap_or_default();
-                            orders.push(UserOrderRecord {
-                                order_side: OrderSide::Buy,
-                                price: order_price,
-                                market_id: market_id,
-                                quantity: order_quantity,
-                            });
-
-                            return Ok(orders);
-                        },
-                    )?;
-
-                    liquidity_provider::process_limit_maker(
-                        deps.storage,
-                        sender,
-                        market_id,
-                        order_price,
-                        order_quantity,
-                        OrderSide::Buy,
-                    )?;
-                }
-                // asks registered, compare to price
-                Some(val_id_top_level_ask) => {
-                    let top_ask_level_data =
-                        LEVELS_DATA.load(deps.storage, val_id_top_level_ask)?;
-                    if top_ask_level_data.price > order_price {
-                        // this is a limit maker
-                        USER_ORDERS.update(
-                            deps.storage,
-                            sender.clone(),
-                            |orders| -> Result<_, ContractError> {
-                                let mut orders = orders.unwrap_or_default();
-                                orders.push(UserOrderRecord {
-                                    order_side: OrderSide::Buy,
-                                    price: order_price,
-                                    market_id: market_id,
-                                    quantity: order_quantity,
-                                });
-
-                                return Ok(orders);
-                            },
-                        )?;
-
-                        liquidity_provider::process_limit_maker(
-                            deps.storage,
-                            sender,
-                            market_id,
-                            order_price,
-                            order_quantity,
-                            OrderSide::Buy,
-                        )?;
-                    } else {
-                        //return Err(ContractError::Unimplemented {});
-
-                        // limit taker
-                        out_msgs = liquidity_consumer::process_liquidity_taker(
-                            deps,
-                            sender,
-                            market_id,
-                            Some(order_price),
-                            order_quantity,
-                            OrderSide::Buy,
-                        )?;
-                    }
-                }
-            }
-        }
-    }
-
-    return Ok(Response::new().add_messages(out_msgs));
+    return process_limit_order(
+        deps,
+        env,
+        sender,
+        currency,
+        order_quantity,
+        market_id,
+        order_price,
+        FundingSource::Transfer,
+    );
 }
 
 fn execute_limit_order(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     market_id: u64,
     order_price: Decimal,
 ) -> Result<Response, ContractError> {
     // validate funds
     let order_value = check_only_one_fund(&info)?;
-    let order_quantity = order_value.amount;
 
+    return process_limit_order(
+        deps,
+        env,
+        info.sender,
+        order_value.denom,
+        order_value.amount,
+        market_id,
+        order_price,
+        FundingSource::Transfer,
+    );
+}
+
+/// Shared logic of limit orders, whatever the currency type and the origin of the funds
+#[allow(clippy::too_many_arguments)]
+fn process_limit_order(
+    deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    currency: String,
+    order_quantity: Uint128,
+    market_id: u64,
+    order_price: Decimal,
+    funding: FundingSource,
+) -> Result<Response, ContractError> {
     // load market info
     let market_info = match MARKET_INFO.load(deps.storage, market_id) {
         Err(_) => return Err(ContractError::UnknownMarketId { id: market_id }),
         Ok(market_info) => market_info,
     };
+    market_info.check_can_place_orders()?;
+    state_utils::check_can_trade(deps.storage, &market_info, &sender)?;
+    if order_price.is_zero() {
+        return Err(ContractError::ZeroPrice {});
+    }
+    market_info.check_tick(order_price)?;
 
     // determine whether this is a base currency or a quote currency
-    let currency_status = market_info.get_currency_status(&order_value.denom)?;
+    let currency_status = market_info.get_currency_status(&currency)?;
 
-    let order_quantity = match currency_status {
+    // quantity of base currency for the order, in whole lots
+    let base_quantity = market_info.round_to_lot(match currency_status {
         CurrencyStatus::BaseCurrency => order_quantity,
-        CurrencyStatus::QuoteCurrency => order_quantity.checked_div_floor(order_price).unwrap(),
-    };
+        CurrencyStatus::QuoteCurrency => order_quantity
+            .checked_div_floor(order_price)
+            .map_err(|err| StdError::generic_err(err.to_string()))?,
+    });
+
+    if base_quantity.is_zero() {
+        return Err(ContractError::OrderTooSmall {});
+    }
 
-    let order_side = MarketInfo::get_order_side_from_currency_status(currency_status.clone());
+    let order_side = MarketInfo::get_order_side_from_currency_status(currency_status);
+    let currency_info = market_info.get_currency_info_from_side(order_side.clone());
 
     // check if the user already has an order at this price
-    if let Ok(user_orders) = USER_ORDERS.load(deps.storage, info.sender.clone()) {
-        match user_orders.into_iter().find(|order| {
-            order.market_id == market_id
-                && order.price == order_price
-                && order.order_side == order_side
-        }) {
-            None => (),
-            Some(_) => {
-                // update user orders
-                USER_ORDERS.update(
-                    deps.storage,
-                    info.sender.clone(),
-                    |orders| -> Result<_, ContractError> {
-                        let orders = orders
-                            .unwrap()
-                            .into_iter()
-                            .map(|mut order| {
-                                if order.market_id == market_id && order.price == order_price {
-                                    order.quantity += order_quantity;
-                                }
-
-                                order
-                            })
-                            .collect();
-
-                        return Ok(orders);
-                    },
-                )?;
-
-                // update level orders
-                let level_id = create_id_level_no_status(&market_info, order_price);
-                LEVEL_ORDERS.update(
-                    deps.storage,
-                    level_id,
-                    |level_orders| -> Result<_, ContractError> {
-                        let level_orders = level_orders
-                            .unwrap()
-                            .into_iter()
-                            .map(|mut order| {
-                                if order.user == info.sender {
-                                    order.amount += order_quantity;
-                                }
-
-                                order
-                            })
-                            .collect();
-
-                        return Ok(level_orders);
-                    },
-                )?;
+    let existing_order = state_utils::load_user_market_orders(deps.storage, &sender, market_id)?
+        .into_iter()
+        .find(|order| order.price == order_price && order.order_side == order_side);
+    if let Some(mut order) = existing_order {
+        // the added quantity locks its own escrow, what is received above it is refunded
+        let added_escrow = compute_escrow(base_quantity, order_price, &order_side);
+
+        // update user order
+        order.quantity += base_quantity;
+        order.original_quantity += base_quantity;
+        order.locked += added_escrow;
+        user_orders().save(deps.storage, order.order_id, &order)?;
+
+        // update level orders
+        let level_id = create_id_level_no_status(&market_info, order_price);
+        LEVEL_ORDERS.update(
+            deps.storage,
+            level_id,
+            |level_orders| -> Result<_, ContractError> {
+                let level_orders = level_orders
+                    .unwrap()
+                    .into_iter()
+                    .map(|mut level_order| {
+                        if level_order.order_id == order.order_id {
+                            level_order.amount += base_quantity;
+                        }
 
-                return Ok(Response::new());
-            }
-        }
+                        level_order
+                    })
+                    .collect();
+
+                return Ok(level_orders);
+            },
+        )?;
+
+        let msgs = refund_dust(
+            deps.storage,
+            &sender,
+            &currency_info,
+            order_quantity - added_escrow,
+            &funding,
+        )?;
+
+        return Ok(Response::new()
+            .add_messages(msgs)
+            .add_attribute("order_id", order.order_id.to_string()));
     }
 
-    let mut out_msgs: Vec<CosmosMsg> = vec![];
-
-    // then determine if it's a buy or a sell depending on currency_status
-    // and if taker or maker
-    match currency_status {
-        CurrencyStatus::BaseCurrency => {
-            // if we receive BaseCurrency, then it's a sell order
-            // so check if it's taker or maker
-            match market_info.top_level_bid {
-                // no bids registered, so it's a limit maker order
-                None => {
-                    // no bids registered, so it's a limit maker order
-                    USER_ORDERS.update(
-                        deps.storage,
-                        info.sender.clone(),
-                        |orders| -> Result<_, ContractError> {
-                            let mut orders = orders.unwrap_or_default();
-                            orders.push(UserOrderRecord {
-                                order_side: OrderSide::Sell,
-                                price: order_price,
-                                market_id: market_id,
-                                quantity: order_quantity,
-                            });
-
-                            return Ok(orders);
-                        },
-                    )?;
-
-                    liquidity_provider::process_limit_maker(
-                        deps.storage,
-                        info.sender,
-                        market_id,
-                        order_price,
-                        order_quantity,
-                        OrderSide::Sell,
-                    )?;
-
-                    /*
-                    match market_info.top_level_ask {
-                        None => {
-                            // no asks also, so maker
-                            liquidity_provider::process_limit_maker(deps, info, market_id, order_price, order_quantity, OrderSide::Sell)?;
-                        },
-                        Some(val_id_top_level_ask) => {
-                            // there are asks, need to compare prices
-                            let top_ask_level_data = LEVELS_DATA.load(deps.storage, val_id_top_level_ask)?;
-
-                        }
-                    }
-                    */
-                }
-                // bids registered, compare to price
-                Some(val_id_top_level_bid) => {
-                    let top_bid_level_data =
-                        LEVELS_DATA.load(deps.storage, val_id_top_level_bid)?;
-                    if top_bid_level_data.price < order_price {
-                        // this is a limit maker
-                        USER_ORDERS.update(
-                            deps.storage,
-                            info.sender.clone(),
-                            |orders| -> Result<_, ContractError> {
-                                let mut orders = orders.unwrap_or_default();
-                                orders.push(UserOrderRecord {
-                                    order_side: OrderSide::Sell,
-                                    price: order_price,
-                                    market_id: market_id,
-                                    quantity: order_quantity,
-                                });
-
-                                return Ok(orders);
-                            },
-                        )?;
-
-                        liquidity_provider::process_limit_maker(
-                            deps.storage,
-                            info.sender,
-                            market_id,
-                            order_price,
-                            order_quantity,
-                            OrderSide::Sell,
-                        )?;
-                    } else {
-                        //return Err(ContractError::Unimplemented {});
-
-                        // limit taker
-                        out_msgs = liquidity_consumer::process_liquidity_taker(
-                            deps,
-                            info.sender,
-                            market_id,
-                            Some(order_price),
-                            order_quantity,
-                            OrderSide::Sell,
-                        )?;
-                    }
-                }
+    // then determine if it's a taker or a maker by comparing to the top of the other side of the book
+    // if we receive BaseCurrency it's a sell order, so compare to bids
+    // if we receive QuoteCurrency it's a buy order, so compare to asks
+    let is_taker = match order_side {
+        OrderSide::Sell => match market_info.top_level_bid {
+            None => false,
+            Some(val_id_top_level_bid) => {
+                LEVELS_DATA.load(deps.storage, val_id_top_level_bid)?.price >= order_price
             }
-        }
-        // if we receive QuoteCurrency, then it's a buy order
-        // so check if it's taker or maker
-        CurrencyStatus::QuoteCurrency => {
-            match market_info.top_level_ask {
-                None => {
-                    // no bids registered, so it's a limit maker order
-                    USER_ORDERS.update(
-                        deps.storage,
-                        info.sender.clone(),
-                        |orders| -> Result<_, ContractError> {
-                            let mut orders = orders.unwrap_or_default();
-                            orders.push(UserOrderRecord {
-                                order_side: OrderSide::Buy,
-                                price: order_price,
-                                market_id: market_id,
-                                quantity: order_quantity,
-                            });
-
-                            return Ok(orders);
-                        },
-                    )?;
-
-                    liquidity_provider::process_limit_maker(
-                        deps.storage,
-                        info.sender,
-                        market_id,
-                        order_price,
-                        order_quantity,
-                        OrderSide::Buy,
-                    )?;
-                }
-                // bids registered, compare to price
-                Some(val_id_top_level_ask) => {
-                    let top_ask_level_data =
-                        LEVELS_DATA.load(deps.storage, val_id_top_level_ask)?;
-                    if top_ask_level_data.price > order_price {
-                        // this is a limit maker
-                        USER_ORDERS.update(
-                            deps.storage,
-                            info.sender.clone(),
-                            |orders| -> Result<_, ContractError> {
-                                let mut orders = orders.unwrap_or_default();
-                                orders.push(UserOrderRecord {
-                                    order_side: OrderSide::Buy,
-                                    price: order_price,
-                                    market_id: market_id,
-                                    quantity: order_quantity,
-                                });
-
-                                return Ok(orders);
-                            },
-                        )?;
-
-                        liquidity_provider::process_limit_maker(
-                            deps.storage,
-                            info.sender,
-                            market_id,
-                            order_price,
-                            order_quantity,
-                            OrderSide::Buy,
-                        )?;
-                    } else {
-                        //return Err(ContractError::Unimplemented {});
-
-                        // limit taker
-                        out_msgs = liquidity_consumer::process_liquidity_taker(
-                            deps,
-                            info.sender,
-                            market_id,
-                            Some(order_price),
-                            order_quantity,
-                            OrderSide::Buy,
-                        )?;
-                    }
-                }
+        },
+        OrderSide::Buy => match market_info.top_level_ask {
+            None => false,
+            Some(val_id_top_level_ask) => {
+                LEVELS_DATA.load(deps.storage, val_id_top_level_ask)?.price <= order_price
             }
-        }
+        },
+    };
+
+    if !is_taker {
+        // this is a limit maker
+        let order_id = liquidity_provider::add_maker_order(
+            deps.storage,
+            sender.clone(),
+            market_id,
+            order_price,
+            base_quantity,
+            order_side.clone(),
+            env.block.time,
+        )?;
+
+        let msgs = refund_dust(
+            deps.storage,
+            &sender,
+            &currency_info,
+            order_quantity - compute_escrow(base_quantity, order_price, &order_side),
+            &funding,
+        )?;
+
+        return Ok(Response::new()
+            .add_messages(msgs)
+            .add_attribute("order_id", order_id.to_string()));
     }
 
-    return Ok(Response::new().add_messages(out_msgs));
+    // limit taker, which takes the amount received and refunds what it can't use itself
+    return liquidity_consumer::process_liquidity_taker(
+        deps,
+        &env,
+        sender,
+        market_id,
+        Some(order_price),
+        order_quantity,
+        order_side,
+        funding,
+    );
+}
+
+/// Send back the part of the funds of an order which could not be locked because of rounding
+fn refund_dust(
+    storage: &mut dyn Storage,
+    sender: &Addr,
+    currency_info: &CurrencyInfo,
+    dust: Uint128,
+    funding: &FundingSource,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut settlement = Settlement::new();
+    settlement.add_for_funding(funding, sender, currency_info, dust);
+
+    return settlement.settle(storage);
 }
 
 fn execute_market_order(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     market_id: u64,
 ) -> Result<Response, ContractError> {
     // validate funds
     let order_value = check_only_one_fund(&info)?;
-    let order_quantity = order_value.amount;
 
-    let market_info = MARKET_INFO.load(deps.storage, market_id)?;
-    let order_side = market_info.get_order_side_from_currency(&order_value.denom)?;
-
-    //return Err(ContractError::Unimplemented {});
-
-    let msgs = liquidity_consumer::process_liquidity_taker(
+    return process_market_order(
         deps,
+        env,
         info.sender,
+        order_value.denom,
+        order_value.amount,
         market_id,
-        None,
-        order_quantity,
-        order_side,
-    )?;
-
-    return Ok(Response::new().add_messages(msgs));
+        FundingSource::Transfer,
+    );
 }
 
 fn execute_market_order_cw20(
     deps: DepsMut,
+    env: Env,
+    sender: Addr,
+    currency: String,
+    order_quantity: Uint128,
+    market_id: u64,
+) -> Result<Response, ContractError> {
+    return process_market_order(
+        deps,
+        env,
+        sender,
+        currency,
+        order_quantity,
+        market_id,
+        FundingSource::Transfer,
+    );
+}
+
+/// Shared logic of market orders, whatever the currency type and the origin of the funds
+fn process_market_order(
+    deps: DepsMut,
+    env: Env,
     sender: Addr,
     currency: String,
     order_quantity: Uint128,
     market_id: u64,
+    funding: FundingSource,
 ) -> Result<Response, ContractError> {
     let market_info = MARKET_INFO.load(deps.storage, market_id)?;
+    market_info.check_can_place_orders()?;
+    state_utils::check_can_trade(deps.storage, &market_info, &sender)?;
     let order_side = market_info.get_order_side_from_currency(&currency)?;
 
-    //return Err(ContractError::Unimplemented {});
-    // determine whether this is a base currency or a quote currency
-    let currency_status = market_info.get_currency_status(&currency)?;
-
-    /*
-    let order_quantity = match currency_status {
-        CurrencyStatus::BaseCurrency => order_quantity,
-        CurrencyStatus::QuoteCurrency => order_quantity.checked_div_floor(order_price).unwrap()
-    };
-    */
-
-    let msgs = liquidity_consumer::process_liquidity_taker(
+    return liquidity_consumer::process_liquidity_taker(
         deps,
+        &env,
         sender,
         market_id,
         None,
         order_quantity,
         order_side,
+        funding,
+    );
+}
+
+fn execute_deposit(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    if info.funds.is_empty() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    for coin in info.funds {
+        state_utils::credit_balance(
+            deps.storage,
+            &info.sender,
+            &CurrencyInfo::Native { denom: coin.denom },
+            coin.amount,
+        )?;
+    }
+
+    return Ok(Response::new());
+}
+
+fn execute_deposit_cw20(
+    deps: DepsMut,
+    sender: Addr,
+    currency: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    state_utils::credit_balance(
+        deps.storage,
+        &sender,
+        &CurrencyInfo::Cw20 { address: currency },
+        amount,
     )?;
 
-    return Ok(Response::new().add_messages(msgs));
+    return Ok(Response::new());
+}
+
+fn execute_withdraw(
+    deps: DepsMut,
+    info: MessageInfo,
+    currency: CurrencyInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    state_utils::debit_balance(deps.storage, &info.sender, &currency, amount)?;
+
+    return Ok(Response::new().add_message(create_funds_message(amount, currency, info.sender)));
+}
+
+fn execute_place_order_from_balance(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    market_id: u64,
+    currency: CurrencyInfo,
+    amount: Uint128,
+    opt_order_price: Option<Decimal>,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    // funds are locked in the order the same way as if they had been sent
+    state_utils::debit_balance(deps.storage, &info.sender, &currency, amount)?;
+
+    return match opt_order_price {
+        Some(order_price) => process_limit_order(
+            deps,
+            env,
+            info.sender,
+            currency.get_denom(),
+            amount,
+            market_id,
+            order_price,
+            FundingSource::Balance,
+        ),
+        None => process_market_order(
+            deps,
+            env,
+            info.sender,
+            currency.get_denom(),
+            amount,
+            market_id,
+            FundingSource::Balance,
+        ),
+    };
+}
+
+fn execute_register_referrer(
+    deps: DepsMut,
+    info: MessageInfo,
+    referrer: String,
+) -> Result<Response, ContractError> {
+    let referrer = deps.api.addr_validate(&referrer)?;
+    if referrer == info.sender {
+        return Err(ContractError::InvalidReferrer {});
+    }
+
+    if REFERRERS.has(deps.storage, info.sender.clone()) {
+        return Err(ContractError::ReferrerAlreadySet {});
+    }
+
+    REFERRERS.save(deps.storage, info.sender, &referrer)?;
+
+    return Ok(Response::new());
+}
+
+fn execute_claim_referral_rewards(
+    deps: DepsMut,
+    info: MessageInfo,
+    currency: CurrencyInfo,
+) -> Result<Response, ContractError> {
+    let key = state_utils::user_currency_key(&info.sender, &currency);
+    let rewards = REFERRAL_REWARDS
+        .may_load(deps.storage, key.clone())?
+        .unwrap_or_default();
+    if rewards.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    REFERRAL_REWARDS.remove(deps.storage, key);
+
+    return Ok(Response::new().add_message(create_funds_message(rewards, currency, info.sender)));
+}
+
+fn execute_lock_fee_token(
+    deps: DepsMut,
+    sender: Addr,
+    token: Addr,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    match FEE_DISCOUNT_CONFIG.may_load(deps.storage)? {
+        Some(config) if config.token == token => (),
+        _ => return Err(ContractError::InvalidFeeToken {}),
+    }
+
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    FEE_TOKEN_LOCKS.update(deps.storage, sender, |locked| -> StdResult<_> {
+        return Ok(locked.unwrap_or_default() + amount);
+    })?;
+
+    return Ok(Response::new());
+}
+
+fn execute_unlock_fee_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    let config = FEE_DISCOUNT_CONFIG
+        .may_load(deps.storage)?
+        .ok_or(ContractError::InvalidFeeToken {})?;
+
+    let locked = FEE_TOKEN_LOCKS
+        .may_load(deps.storage, info.sender.clone())?
+        .unwrap_or_default();
+    if locked < amount {
+        return Err(ContractError::InsufficientBalance {});
+    }
+
+    if locked == amount {
+        FEE_TOKEN_LOCKS.remove(deps.storage, info.sender.clone());
+    } else {
+        FEE_TOKEN_LOCKS.save(deps.storage, info.sender.clone(), &(locked - amount))?;
+    }
+
+    let mut unbondings = FEE_TOKEN_UNBONDINGS
+        .may_load(deps.storage, info.sender.clone())?
+        .unwrap_or_default();
+    unbondings.push(Unbonding {
+        amount: amount,
+        release_at: env.block.time.plus_seconds(config.unbonding_seconds),
+    });
+    FEE_TOKEN_UNBONDINGS.save(deps.storage, info.sender, &unbondings)?;
+
+    return Ok(Response::new());
+}
+
+fn execute_claim_unlocked_fee_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config = FEE_DISCOUNT_CONFIG
+        .may_load(deps.storage)?
+        .ok_or(ContractError::InvalidFeeToken {})?;
+
+    let (released, unbondings): (Vec<Unbonding>, Vec<Unbonding>) = FEE_TOKEN_UNBONDINGS
+        .may_load(deps.storage, info.sender.clone())?
+        .unwrap_or_default()
+        .into_iter()
+        .partition(|unbonding| unbonding.release_at <= env.block.time);
+
+    let amount: Uint128 = released.iter().map(|unbonding| unbonding.amount).sum();
+    if amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+
+    if unbondings.is_empty() {
+        FEE_TOKEN_UNBONDINGS.remove(deps.storage, info.sender.clone());
+    } else {
+        FEE_TOKEN_UNBONDINGS.save(deps.storage, info.sender.clone(), &unbondings)?;
+    }
+
+    return Ok(Response::new().add_message(create_funds_message(
+        amount,
+        CurrencyInfo::Cw20 {
+            address: config.token.to_string(),
+        },
+        info.sender,
+    )));
+}
+
+/// Anyone can create a market by paying the listing fee, which goes to the protocol fees
+fn execute_create_market(
+    deps: DepsMut,
+    payment_currency: CurrencyInfo,
+    payment_amount: Uint128,
+    base_currency: CurrencyInfo,
+    quote_currency: CurrencyInfo,
+    base_decimals: Option<u8>,
+    quote_decimals: Option<u8>,
+) -> Result<Response, ContractError> {
+    let listing_fee = LISTING_FEE
+        .may_load(deps.storage)?
+        .ok_or(ContractError::ListingDisabled {})?;
+
+    // decimals of native currencies are given by the lister, so only market listers can trust them
+    for currency in [&base_currency, &quote_currency] {
+        if let CurrencyInfo::Native { .. } = currency {
+            return Err(ContractError::NativeListingRestricted {});
+        }
+    }
+
+    if payment_currency != listing_fee.currency || payment_amount != listing_fee.amount {
+        return Err(ContractError::InvalidListingFee {
+            amount: listing_fee.amount,
+            denom: listing_fee.currency.get_denom(),
+        });
+    }
+
+    let mut settlement = Settlement::new();
+    settlement.add_fee(&listing_fee.currency, listing_fee.amount);
+    settlement.settle(deps.storage)?;
+
+    return add_market(
+        deps,
+        base_currency,
+        quote_currency,
+        base_decimals,
+        quote_decimals,
+        false,
+    );
 }
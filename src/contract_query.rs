@@ -1,39 +1,120 @@
-use cosmwasm_std::{to_binary, Addr, Binary, Deps, Env, StdResult, Uint256};
+use cosmwasm_std::{
+    to_binary, Addr, Binary, Coin, Decimal, Deps, Env, StdError, StdResult, Uint128, Uint256,
+};
+use cw_storage_plus::{Bound, Map};
 use erased_serde::Serialize;
 
 use crate::{
+    market_logic::{fee_discounts, fee_tiers, liquidity_consumer, market_stats},
     msg::{
-        GetAdminResponse, GetMarketBookResponse, GetMarketsResponse, GetUserAsksResponse,
-        GetUserBidsResponse, GetUserOrdersResponse, QueryMsg,
+        GetAdminResponse, GetConfigResponse, GetDepthResponse, GetEscrowTotalsResponse,
+        GetFeeDiscountsResponse, GetFeeTiersResponse, GetListingFeeResponse, GetMarketBookResponse,
+        GetMarketSummariesResponse, GetMarketSummaryResponse, GetMarketsResponse, GetOrderResponse,
+        GetProtocolFeesResponse, GetReferralEarningsResponse, GetReferralShareResponse,
+        GetReferrerResponse, GetRoleMembersResponse, GetUserAsksResponse, GetUserBalancesResponse,
+        GetUserBidsResponse, GetUserFeeTierResponse, GetUserFeeTokenLockResponse,
+        GetUserOrdersResponse, GetUserTradingAccessResponse, QueryMsg, SimulateMarketOrderResponse,
+        SimulateReverseResponse,
+    },
+    state::{
+        user_orders, ADMIN, BLOCKLIST, CLOSED_ORDERS, DEFAULT_MARKET_PARAMS, FEE_COLLECTOR,
+        FEE_DISCOUNT_CONFIG, FEE_TIER_CONFIG, FEE_TOKEN_LOCKS, FEE_TOKEN_UNBONDINGS, LAST_TRADES,
+        LEVELS_DATA, LEVEL_ORDERS, LISTING_FEE, MARKET_ALLOWLIST, MARKET_INFO, ORDER_LIMITS,
+        PENDING_ADMIN, PROTOCOL_FEES, REFERRAL_EARNINGS, REFERRAL_REWARDS, REFERRAL_SHARE_BPS,
+        REFERRERS, ROLE_MEMBERS, USER_BALANCES,
+    },
+    state_utils,
+    structs::{
+        BookLevel, DepthBucket, MarketInfo, MarketSummary, OrderSide, Role, SingleMarketInfo,
+        UserOrderInfo, UserOrderRecord,
+    },
+    utils::{
+        add_to_coins, compute_fee, compute_fill_notional, compute_gross_for_net, compute_midprice,
+        BPS_DENOMINATOR,
     },
-    state::{ADMIN, LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO, USER_ORDERS},
-    structs::{BookLevel, OrderSide, SingleMarketInfo},
+    ContractError,
 };
 
-pub fn route_query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn route_query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     let res: Box<dyn Serialize> = match msg {
-        QueryMsg::GetAdmin {} => get_admin(deps),
-        QueryMsg::GetMarkets {} => get_markets(deps),
+        QueryMsg::GetAdmin {} => get_admin(deps)?,
+        QueryMsg::GetRoleMembers { role } => get_role_members(deps, role)?,
+        QueryMsg::GetConfig {} => get_config(deps)?,
+        QueryMsg::GetMarkets { start_after, limit } => get_markets(deps, start_after, limit),
         QueryMsg::GetUserBids {
             user_address,
             target_market,
-        } => get_user_bids(deps, user_address, target_market),
+            start_after,
+            limit,
+        } => get_user_bids(deps, user_address, target_market, start_after, limit)?,
         QueryMsg::GetUserAsks {
             user_address,
             target_market,
-        } => get_user_asks(deps, user_address, target_market),
+            start_after,
+            limit,
+        } => get_user_asks(deps, user_address, target_market, start_after, limit)?,
         QueryMsg::GetMarketBook {
             market_id,
             nb_levels,
         } => get_market_book(deps, market_id, nb_levels),
+        QueryMsg::GetDepth {
+            market_id,
+            bucket_size,
+            max_buckets,
+        } => get_depth(deps, market_id, bucket_size, max_buckets)?,
         QueryMsg::GetUserOrders {
             user_address,
             target_market,
-        } => get_user_orders(deps, user_address, target_market),
-        //_ => panic!("Not implemented"),
+            start_after,
+            limit,
+        } => get_user_orders(deps, user_address, target_market, start_after, limit)?,
+        QueryMsg::SimulateMarketOrder {
+            market_id,
+            offer_currency,
+            offer_amount,
+            user_address,
+        } => simulate_market_order(
+            deps,
+            env,
+            market_id,
+            offer_currency,
+            offer_amount,
+            user_address,
+        )?,
+        QueryMsg::SimulateReverse {
+            market_id,
+            ask_currency,
+            ask_amount,
+            user_address,
+        } => simulate_reverse(deps, env, market_id, ask_currency, ask_amount, user_address)?,
+        QueryMsg::GetOrder { order_id } => get_order(deps, env, order_id)?,
+        QueryMsg::GetMarketSummary { market_id } => get_market_summary(deps, env, market_id)?,
+        QueryMsg::GetMarketSummaries { start_after, limit } => {
+            get_market_summaries(deps, env, start_after, limit)?
+        }
+        QueryMsg::GetUserBalances { user_address } => get_user_balances(deps, user_address)?,
+        QueryMsg::GetEscrowTotals {} => get_escrow_totals(deps)?,
+        QueryMsg::GetProtocolFees {} => get_protocol_fees(deps)?,
+        QueryMsg::GetFeeTiers {} => get_fee_tiers(deps)?,
+        QueryMsg::GetUserFeeTier {
+            user_address,
+            market_id,
+        } => get_user_fee_tier(deps, env, user_address, market_id)?,
+        QueryMsg::GetReferralShare {} => get_referral_share(deps)?,
+        QueryMsg::GetReferrer { user_address } => get_referrer(deps, user_address)?,
+        QueryMsg::GetReferralEarnings { referrer } => get_referral_earnings(deps, referrer)?,
+        QueryMsg::GetListingFee {} => get_listing_fee(deps)?,
+        QueryMsg::GetFeeDiscounts {} => get_fee_discounts(deps)?,
+        QueryMsg::GetUserFeeTokenLock { user_address } => {
+            get_user_fee_token_lock(deps, user_address)?
+        }
+        QueryMsg::GetUserTradingAccess {
+            user_address,
+            market_id,
+        } => get_user_trading_access(deps, user_address, market_id)?, //_ => panic!("Not implemented"),
     };
 
-    return Ok(to_binary(&res)?);
+    return to_binary(&res);
 }
 
 fn get_market_book(deps: Deps, market_id: u64, nb_levels: u32) -> Box<dyn Serialize> {
@@ -54,13 +135,14 @@ fn get_market_book(deps: Deps, market_id: u64, nb_levels: u32) -> Box<dyn Serial
                 let level_data = LEVELS_DATA.load(deps.storage, val_curr_id).unwrap();
                 let level_orders = LEVEL_ORDERS.load(deps.storage, val_curr_id).unwrap();
 
-                bids.push(BookLevel {
-                    price: level_data.price,
-                    quantity: level_orders
+                bids.push(BookLevel::new(
+                    &market_info,
+                    level_data.price,
+                    level_orders
                         .iter()
                         .map(|order| Uint256::from(order.amount))
                         .sum(),
-                });
+                ));
 
                 curr_depth += 1;
                 curr_id_level = level_data.id_next;
@@ -80,13 +162,14 @@ fn get_market_book(deps: Deps, market_id: u64, nb_levels: u32) -> Box<dyn Serial
                 let level_data = LEVELS_DATA.load(deps.storage, val_curr_id).unwrap();
                 let level_orders = LEVEL_ORDERS.load(deps.storage, val_curr_id).unwrap();
 
-                asks.push(BookLevel {
-                    price: level_data.price,
-                    quantity: level_orders
+                asks.push(BookLevel::new(
+                    &market_info,
+                    level_data.price,
+                    level_orders
                         .iter()
                         .map(|order| Uint256::from(order.amount))
                         .sum(),
-                });
+                ));
 
                 curr_depth += 1;
                 curr_id_level = level_data.id_next;
@@ -100,66 +183,731 @@ fn get_market_book(deps: Deps, market_id: u64, nb_levels: u32) -> Box<dyn Serial
     });
 }
 
+/// Levels of one side of the book from its top, grouped in buckets of bucket_size
+/// Bids are grouped down and asks up to a multiple of bucket_size, so that buckets of both sides never overlap
+fn load_depth_side(
+    deps: Deps,
+    top_level: Option<u64>,
+    order_side: OrderSide,
+    bucket_size: Decimal,
+    max_buckets: u32,
+) -> StdResult<Vec<DepthBucket>> {
+    let mut buckets: Vec<DepthBucket> = vec![];
+    let mut cumulative_quantity = Uint256::zero();
+    let mut cumulative_notional = Uint256::zero();
+
+    let mut curr_id_level = top_level;
+    while let Some(val_curr_id) = curr_id_level {
+        let level_data = LEVELS_DATA.load(deps.storage, val_curr_id)?;
+        let level_orders = LEVEL_ORDERS.load(deps.storage, val_curr_id)?;
+
+        let bucket_index = level_data
+            .price
+            .checked_div(bucket_size)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        let bucket_price = match order_side {
+            OrderSide::Buy => bucket_index.floor(),
+            OrderSide::Sell => bucket_index.ceil(),
+        }
+        .checked_mul(bucket_size)?;
+
+        if buckets.last().map(|bucket| bucket.price) != Some(bucket_price) {
+            if buckets.len() as u32 >= max_buckets {
+                break;
+            }
+            buckets.push(DepthBucket {
+                price: bucket_price,
+                quantity: Uint256::zero(),
+                cumulative_quantity: cumulative_quantity,
+                cumulative_notional: cumulative_notional,
+            });
+        }
+
+        // the notional is what the orders would be paid if filled
+        let quantity: Uint256 = level_orders
+            .iter()
+            .map(|order| Uint256::from(order.amount))
+            .sum();
+        let notional: Uint256 = level_orders
+            .iter()
+            .map(|order| Uint256::from(compute_fill_notional(order.amount, level_data.price)))
+            .sum();
+        cumulative_quantity += quantity;
+        cumulative_notional += notional;
+
+        let bucket = buckets.last_mut().unwrap();
+        bucket.quantity += quantity;
+        bucket.cumulative_quantity = cumulative_quantity;
+        bucket.cumulative_notional = cumulative_notional;
+
+        curr_id_level = level_data.id_next;
+    }
+
+    return Ok(buckets);
+}
+
+fn get_depth(
+    deps: Deps,
+    market_id: u64,
+    bucket_size: Decimal,
+    max_buckets: u32,
+) -> StdResult<Box<dyn Serialize>> {
+    if bucket_size.is_zero() {
+        return Err(StdError::generic_err("bucket size must be positive"));
+    }
+    let max_buckets = max_buckets.min(MAX_PAGE_LIMIT);
+    let market_info = MARKET_INFO.load(deps.storage, market_id)?;
+
+    return Ok(Box::new(GetDepthResponse {
+        bids: load_depth_side(
+            deps,
+            market_info.top_level_bid,
+            OrderSide::Buy,
+            bucket_size,
+            max_buckets,
+        )?,
+        asks: load_depth_side(
+            deps,
+            market_info.top_level_ask,
+            OrderSide::Sell,
+            bucket_size,
+            max_buckets,
+        )?,
+    }));
+}
+
+/// Number of items in a page, when not given and at most
+const DEFAULT_PAGE_LIMIT: u32 = 30;
+const MAX_PAGE_LIMIT: u32 = 100;
+
+/// Orders of a user after the order id start_after, optionally on a single market and side
+/// The cursor of the next page is the id of the last order returned, if there are more orders
+fn page_user_orders(
+    deps: Deps,
+    user_address: Addr,
+    target_market: Option<u64>,
+    order_side: Option<OrderSide>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<(Vec<UserOrderRecord>, Option<u64>)> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+
+    // one more order than the page is read to know if there is a next page
+    let mut orders: Vec<UserOrderRecord> = user_orders()
+        .idx
+        .user
+        .prefix(user_address)
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            cosmwasm_std::Order::Ascending,
+        )
+        .map(|elem| elem.map(|(_, order)| order))
+        .filter(|elem| match elem {
+            // errors are kept so they are returned
+            Err(_) => true,
+            Ok(order) => {
+                (target_market.is_none() || target_market == Some(order.market_id))
+                    && (order_side.is_none() || order_side.as_ref() == Some(&order.order_side))
+            }
+        })
+        .take(limit + 1)
+        .collect::<StdResult<_>>()?;
+
+    let mut next_start_after = None;
+    if orders.len() > limit {
+        orders.truncate(limit);
+        next_start_after = orders.last().map(|order| order.order_id);
+    }
+
+    return Ok((orders, next_start_after));
+}
+
 fn get_user_orders(
     deps: Deps,
     user_address: Addr,
     target_market: Option<u64>,
-) -> Box<dyn Serialize> {
-    let user_orders = USER_ORDERS
-        .load(deps.storage, user_address)
-        .unwrap_or_default();
-
-    let user_orders = match target_market {
-        None => user_orders,
-        Some(target_market_id) => user_orders
-            .into_iter()
-            .filter(|order| order.market_id == target_market_id)
-            .collect(),
-    };
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Box<dyn Serialize>> {
+    let (user_orders, next_start_after) =
+        page_user_orders(deps, user_address, target_market, None, start_after, limit)?;
 
-    return Box::new(GetUserOrdersResponse {
+    let user_orders = user_orders
+        .into_iter()
+        .map(|order| {
+            let market_info = MARKET_INFO.load(deps.storage, order.market_id)?;
+
+            Ok(UserOrderInfo {
+                human_price: market_info.to_human_price(order.price),
+                human_quantity: market_info.to_human_quantity(Uint256::from(order.quantity)),
+                order: order,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    return Ok(Box::new(GetUserOrdersResponse {
         orders: user_orders,
-    });
+        next_start_after: next_start_after,
+    }));
 }
 
-fn get_user_bids(deps: Deps, user_address: Addr, target_market: Option<u64>) -> Box<dyn Serialize> {
-    let user_orders = USER_ORDERS
-        .load(deps.storage, user_address)
-        .unwrap_or_default();
-
-    let user_orders = match target_market {
-        None => user_orders,
-        Some(target_market_id) => user_orders
-            .into_iter()
-            .filter(|order| {
-                order.market_id == target_market_id && order.order_side == OrderSide::Buy
-            })
-            .collect(),
+/// Best level of a side of the book, with the quantity resting at that price
+fn load_top_level(
+    deps: Deps,
+    market_info: &MarketInfo,
+    top_level: Option<u64>,
+) -> StdResult<Option<BookLevel>> {
+    let level_id = match top_level {
+        None => return Ok(None),
+        Some(level_id) => level_id,
     };
 
-    return Box::new(GetUserBidsResponse {
-        orders: user_orders,
+    let level_data = LEVELS_DATA.load(deps.storage, level_id)?;
+    let level_orders = LEVEL_ORDERS.load(deps.storage, level_id)?;
+
+    return Ok(Some(BookLevel::new(
+        market_info,
+        level_data.price,
+        level_orders
+            .iter()
+            .map(|order| Uint256::from(order.amount))
+            .sum(),
+    )));
+}
+
+fn build_market_summary(
+    deps: Deps,
+    env: &Env,
+    market_info: &MarketInfo,
+) -> StdResult<MarketSummary> {
+    let best_bid = load_top_level(deps, market_info, market_info.top_level_bid)?;
+    let best_ask = load_top_level(deps, market_info, market_info.top_level_ask)?;
+
+    let (mut spread, mut spread_bps, mut midprice) = (None, None, None);
+    if let (Some(bid), Some(ask)) = (&best_bid, &best_ask) {
+        // the book is never crossed, so the best ask is above the best bid
+        let mid = compute_midprice(bid.price, ask.price);
+        let abs_spread = ask.price - bid.price;
+        spread = Some(abs_spread);
+        spread_bps = Some(Uint128::from(BPS_DENOMINATOR).mul_floor(abs_spread / mid));
+        midprice = Some(mid);
+    }
+
+    return Ok(MarketSummary {
+        market_id: market_info.market_id,
+        best_bid: best_bid,
+        best_ask: best_ask,
+        spread: spread,
+        spread_bps: spread_bps,
+        midprice: midprice,
+        last_trade: LAST_TRADES.may_load(deps.storage, market_info.market_id)?,
+        volume_24h: market_stats::get_daily_volume(
+            deps.storage,
+            market_info.market_id,
+            env.block.time,
+        )?,
     });
 }
 
-fn get_user_asks(deps: Deps, user_address: Addr, target_market: Option<u64>) -> Box<dyn Serialize> {
-    let user_orders = USER_ORDERS
-        .load(deps.storage, user_address)
-        .unwrap_or_default();
+fn get_market_summary(deps: Deps, env: Env, market_id: u64) -> StdResult<Box<dyn Serialize>> {
+    let market_info = MARKET_INFO.load(deps.storage, market_id)?;
 
-    let user_orders = match target_market {
-        None => user_orders,
-        Some(target_market_id) => user_orders
-            .into_iter()
-            .filter(|order| {
-                order.market_id == target_market_id && order.order_side == OrderSide::Sell
-            })
-            .collect(),
+    return Ok(Box::new(GetMarketSummaryResponse {
+        summary: build_market_summary(deps, &env, &market_info)?,
+    }));
+}
+
+fn get_market_summaries(
+    deps: Deps,
+    env: Env,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Box<dyn Serialize>> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+
+    let mut summaries = MARKET_INFO
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            cosmwasm_std::Order::Ascending,
+        )
+        .take(limit + 1)
+        .map(|elem| build_market_summary(deps, &env, &elem?.1))
+        .collect::<StdResult<Vec<MarketSummary>>>()?;
+
+    let mut next_start_after = None;
+    if summaries.len() > limit {
+        summaries.truncate(limit);
+        next_start_after = summaries.last().map(|summary| summary.market_id);
+    }
+
+    return Ok(Box::new(GetMarketSummariesResponse {
+        summaries: summaries,
+        next_start_after: next_start_after,
+    }));
+}
+
+/// Taker fee of a user on a market, or the fee of the market without user
+fn load_taker_fee_bps(
+    deps: Deps,
+    env: &Env,
+    market_info: &MarketInfo,
+    user_address: Option<Addr>,
+) -> StdResult<u16> {
+    let user_address = match user_address {
+        Some(user_address) => user_address,
+        None => return Ok(market_info.taker_fee_bps),
+    };
+
+    let config = FEE_TIER_CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    let today = fee_tiers::day_index(env.block.time);
+    let user_fees =
+        fee_tiers::get_user_fees(deps.storage, &config, &user_address, market_info, today)?;
+
+    return Ok(user_fees.taker_fee_bps);
+}
+
+/// Market orders are only quoted when they could be placed
+fn load_tradable_market(deps: Deps, market_id: u64) -> StdResult<MarketInfo> {
+    let market_info = MARKET_INFO.load(deps.storage, market_id)?;
+    market_info
+        .check_can_place_orders()
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+
+    return Ok(market_info);
+}
+
+fn simulate_market_order(
+    deps: Deps,
+    env: Env,
+    market_id: u64,
+    offer_currency: String,
+    offer_amount: Uint128,
+    user_address: Option<Addr>,
+) -> StdResult<Box<dyn Serialize>> {
+    let market_info = load_tradable_market(deps, market_id)?;
+    let order_side = market_info
+        .get_order_side_from_currency(&offer_currency)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let taker_fee_bps = load_taker_fee_bps(deps, &env, &market_info, user_address)?;
+
+    let walk = liquidity_consumer::walk_book(
+        deps.storage,
+        &market_info,
+        None,
+        offer_amount,
+        order_side.clone(),
+    )?;
+
+    let proceeds = walk.proceeds(&order_side);
+    let fee_amount = compute_fee(proceeds, taker_fee_bps);
+
+    return Ok(Box::new(SimulateMarketOrderResponse {
+        order_side: order_side,
+        offer_used: offer_amount - walk.remaining_quantity,
+        refund_amount: walk.remaining_quantity,
+        return_amount: proceeds - fee_amount,
+        fee_amount: fee_amount,
+        taker_fee_bps: taker_fee_bps,
+        average_price: walk.average_price(),
+        worst_price: walk.consumed_orders.last().map(|level| level.price),
+        levels_consumed: walk.consumed_orders.len() as u32,
+        enough_liquidity: !walk.is_book_exhausted,
+    }));
+}
+
+fn simulate_reverse(
+    deps: Deps,
+    env: Env,
+    market_id: u64,
+    ask_currency: String,
+    ask_amount: Uint128,
+    user_address: Option<Addr>,
+) -> StdResult<Box<dyn Serialize>> {
+    if ask_amount.is_zero() {
+        return Err(StdError::generic_err(
+            ContractError::ZeroAmount {}.to_string(),
+        ));
+    }
+
+    let market_info = load_tradable_market(deps, market_id)?;
+    // the order offers the other currency of the market
+    let order_side = match market_info
+        .get_order_side_from_currency(&ask_currency)
+        .map_err(|err| StdError::generic_err(err.to_string()))?
+    {
+        OrderSide::Buy => OrderSide::Sell,
+        OrderSide::Sell => OrderSide::Buy,
+    };
+    let taker_fee_bps = load_taker_fee_bps(deps, &env, &market_info, user_address)?;
+    let proceeds = compute_gross_for_net(ask_amount, taker_fee_bps)?;
+
+    let simulate = |offer_amount: Uint128| {
+        return liquidity_consumer::walk_book(
+            deps.storage,
+            &market_info,
+            None,
+            offer_amount,
+            order_side.clone(),
+        );
+    };
+
+    let (mut offer_amount, enough_liquidity) = liquidity_consumer::estimate_offer(
+        deps.storage,
+        &market_info,
+        order_side.clone(),
+        proceeds,
+    )?;
+    let mut walk = simulate(offer_amount)?;
+    if enough_liquidity {
+        // the estimate can be a few units short, the offer grows until it is enough
+        // then the smallest offer which is enough is searched back
+        let mut short_offer = offer_amount;
+        let mut step = Uint128::one();
+        while walk.proceeds(&order_side) < proceeds && !walk.is_book_exhausted {
+            short_offer = offer_amount;
+            offer_amount = offer_amount.checked_add(step)?;
+            step = step.checked_add(step)?;
+            walk = simulate(offer_amount)?;
+        }
+        while offer_amount - short_offer > Uint128::one() {
+            let mid_offer = short_offer + (offer_amount - short_offer) / Uint128::new(2);
+            let mid_walk = simulate(mid_offer)?;
+            if mid_walk.proceeds(&order_side) >= proceeds {
+                offer_amount = mid_offer;
+                walk = mid_walk;
+            } else {
+                short_offer = mid_offer;
+            }
+        }
+    }
+
+    let walk_proceeds = walk.proceeds(&order_side);
+    let fee_amount = compute_fee(walk_proceeds, taker_fee_bps);
+    let average_price = walk.average_price();
+    let price_impact_bps = match (average_price, walk.consumed_orders.first()) {
+        (Some(average_price), Some(top_level)) => {
+            let distance = match average_price > top_level.price {
+                true => average_price - top_level.price,
+                false => top_level.price - average_price,
+            };
+            Some(Uint128::from(BPS_DENOMINATOR).mul_floor(distance / top_level.price))
+        }
+        _ => None,
     };
 
-    return Box::new(GetUserAsksResponse {
+    return Ok(Box::new(SimulateReverseResponse {
+        order_side: order_side,
+        offer_amount: offer_amount,
+        return_amount: walk_proceeds - fee_amount,
+        fee_amount: fee_amount,
+        taker_fee_bps: taker_fee_bps,
+        average_price: average_price,
+        worst_price: walk.consumed_orders.last().map(|level| level.price),
+        price_impact_bps: price_impact_bps,
+        enough_liquidity: walk_proceeds >= proceeds,
+    }));
+}
+
+/// Open orders are read from the book records, closed ones from the closed orders still retained
+fn get_order(deps: Deps, env: Env, order_id: u64) -> StdResult<Box<dyn Serialize>> {
+    let (order, status, closed_at) = match user_orders().may_load(deps.storage, order_id)? {
+        Some(order) => {
+            let status = order.open_status();
+            (order, status, None)
+        }
+        None => match CLOSED_ORDERS.may_load(deps.storage, order_id)? {
+            Some(closed)
+                if !state_utils::is_closed_order_expired(closed.closed_at, env.block.time) =>
+            {
+                (closed.order, closed.status, Some(closed.closed_at))
+            }
+            _ => return Err(StdError::not_found("order")),
+        },
+    };
+
+    let mut average_fill_price = None;
+    if !order.filled_quantity.is_zero() {
+        average_fill_price = Some(Decimal::from_ratio(
+            order.filled_notional,
+            order.filled_quantity,
+        ));
+    }
+
+    return Ok(Box::new(GetOrderResponse {
+        order_id: order.order_id,
+        market_id: order.market_id,
+        order_side: order.order_side,
+        owner: order.user,
+        price: order.price,
+        original_quantity: order.original_quantity,
+        remaining_quantity: order.quantity,
+        filled_quantity: order.filled_quantity,
+        average_fill_price: average_fill_price,
+        created_at: order.created_at,
+        status: status,
+        closed_at: closed_at,
+    }));
+}
+
+fn get_user_balances(deps: Deps, user_address: Addr) -> StdResult<Box<dyn Serialize>> {
+    let balances = USER_BALANCES
+        .sub_prefix(user_address)
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|elem| {
+            elem.map(|((_, denom), amount)| Coin {
+                denom: denom,
+                amount: amount,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    return Ok(Box::new(GetUserBalancesResponse { balances: balances }));
+}
+
+/// Walks all open orders, balances and fee token locks without bound
+/// meant for off-chain audits, it can exceed the gas limit of a query made by a contract
+fn get_escrow_totals(deps: Deps) -> StdResult<Box<dyn Serialize>> {
+    let mut orders: Vec<Coin> = vec![];
+    for elem in user_orders().range(deps.storage, None, None, cosmwasm_std::Order::Ascending) {
+        let (_, order) = elem?;
+        let market_info = MARKET_INFO.load(deps.storage, order.market_id)?;
+        add_to_coins(
+            &mut orders,
+            market_info
+                .get_currency_info_from_side(order.order_side.clone())
+                .get_denom(),
+            order.locked,
+        );
+    }
+
+    let mut balances: Vec<Coin> = vec![];
+    for elem in USER_BALANCES.range(deps.storage, None, None, cosmwasm_std::Order::Ascending) {
+        let ((_, _, denom), amount) = elem?;
+        add_to_coins(&mut balances, denom, amount);
+    }
+
+    let fees = load_protocol_fees(deps)?;
+
+    let mut referral_rewards: Vec<Coin> = vec![];
+    for elem in REFERRAL_REWARDS.range(deps.storage, None, None, cosmwasm_std::Order::Ascending) {
+        let ((_, _, denom), amount) = elem?;
+        add_to_coins(&mut referral_rewards, denom, amount);
+    }
+
+    // fee tokens locked or unbonding
+    let mut fee_token_locks: Vec<Coin> = vec![];
+    if let Some(config) = FEE_DISCOUNT_CONFIG.may_load(deps.storage)? {
+        let mut locked = Uint128::zero();
+        for elem in FEE_TOKEN_LOCKS.range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        {
+            locked = locked.checked_add(elem?.1)?;
+        }
+        for elem in
+            FEE_TOKEN_UNBONDINGS.range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        {
+            for unbonding in elem?.1 {
+                locked = locked.checked_add(unbonding.amount)?;
+            }
+        }
+        add_to_coins(&mut fee_token_locks, config.token.to_string(), locked);
+    }
+
+    let mut total = orders.clone();
+    for coin in balances
+        .iter()
+        .chain(fees.iter())
+        .chain(referral_rewards.iter())
+        .chain(fee_token_locks.iter())
+    {
+        add_to_coins(&mut total, coin.denom.clone(), coin.amount);
+    }
+
+    orders.sort_by(|a, b| a.denom.cmp(&b.denom));
+    balances.sort_by(|a, b| a.denom.cmp(&b.denom));
+    referral_rewards.sort_by(|a, b| a.denom.cmp(&b.denom));
+    total.sort_by(|a, b| a.denom.cmp(&b.denom));
+
+    return Ok(Box::new(GetEscrowTotalsResponse {
+        orders: orders,
+        balances: balances,
+        fees: fees,
+        referral_rewards: referral_rewards,
+        fee_token_locks: fee_token_locks,
+        total: total,
+    }));
+}
+
+fn get_protocol_fees(deps: Deps) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetProtocolFeesResponse {
+        fees: load_protocol_fees(deps)?,
+    }));
+}
+
+fn get_fee_tiers(deps: Deps) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetFeeTiersResponse {
+        config: FEE_TIER_CONFIG.may_load(deps.storage)?.unwrap_or_default(),
+    }));
+}
+
+fn get_user_fee_tier(
+    deps: Deps,
+    env: Env,
+    user_address: Addr,
+    market_id: u64,
+) -> StdResult<Box<dyn Serialize>> {
+    let market_info = MARKET_INFO.load(deps.storage, market_id)?;
+    let config = FEE_TIER_CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    let today = fee_tiers::day_index(env.block.time);
+
+    let volume =
+        fee_tiers::get_user_volume(deps.storage, &config, &user_address, &market_info, today)?;
+    let user_fees =
+        fee_tiers::get_user_fees(deps.storage, &config, &user_address, &market_info, today)?;
+
+    return Ok(Box::new(GetUserFeeTierResponse {
+        tier: user_fees.tier,
+        volume: volume,
+        window_days: config.window_days,
+        maker_fee_bps: user_fees.maker_fee_bps,
+        taker_fee_bps: user_fees.taker_fee_bps,
+        taker_discount_bps: user_fees.taker_discount_bps,
+    }));
+}
+
+fn get_referral_share(deps: Deps) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetReferralShareResponse {
+        share_bps: REFERRAL_SHARE_BPS
+            .may_load(deps.storage)?
+            .unwrap_or_default(),
+    }));
+}
+
+fn get_referrer(deps: Deps, user_address: Addr) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetReferrerResponse {
+        referrer: REFERRERS.may_load(deps.storage, user_address)?,
+    }));
+}
+
+fn get_referral_earnings(deps: Deps, referrer: Addr) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetReferralEarningsResponse {
+        pending: load_referral_coins(deps, &REFERRAL_REWARDS, &referrer)?,
+        earned: load_referral_coins(deps, &REFERRAL_EARNINGS, &referrer)?,
+    }));
+}
+
+fn load_referral_coins(
+    deps: Deps,
+    map: &Map<(Addr, String, String), Uint128>,
+    referrer: &Addr,
+) -> StdResult<Vec<Coin>> {
+    return map
+        .sub_prefix(referrer.clone())
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|elem| {
+            elem.map(|((_, denom), amount)| Coin {
+                denom: denom,
+                amount: amount,
+            })
+        })
+        .collect();
+}
+
+fn get_listing_fee(deps: Deps) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetListingFeeResponse {
+        listing_fee: LISTING_FEE.may_load(deps.storage)?,
+    }));
+}
+
+fn get_fee_discounts(deps: Deps) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetFeeDiscountsResponse {
+        config: FEE_DISCOUNT_CONFIG.may_load(deps.storage)?,
+    }));
+}
+
+fn get_user_fee_token_lock(deps: Deps, user_address: Addr) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetUserFeeTokenLockResponse {
+        locked: FEE_TOKEN_LOCKS
+            .may_load(deps.storage, user_address.clone())?
+            .unwrap_or_default(),
+        unbonding: FEE_TOKEN_UNBONDINGS
+            .may_load(deps.storage, user_address.clone())?
+            .unwrap_or_default(),
+        taker_discount_bps: fee_discounts::get_taker_discount_bps(deps.storage, &user_address)?,
+    }));
+}
+
+fn get_user_trading_access(
+    deps: Deps,
+    user_address: Addr,
+    market_id: u64,
+) -> StdResult<Box<dyn Serialize>> {
+    let market_info = MARKET_INFO.load(deps.storage, market_id)?;
+
+    return Ok(Box::new(GetUserTradingAccessResponse {
+        blocklisted: BLOCKLIST.has(deps.storage, user_address.clone()),
+        allowlisted: MARKET_ALLOWLIST.has(deps.storage, (market_id, user_address.clone())),
+        can_trade: state_utils::check_can_trade(deps.storage, &market_info, &user_address).is_ok(),
+    }));
+}
+
+fn load_protocol_fees(deps: Deps) -> StdResult<Vec<Coin>> {
+    return PROTOCOL_FEES
+        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .map(|elem| {
+            elem.map(|((_, denom), amount)| Coin {
+                denom: denom,
+                amount: amount,
+            })
+        })
+        .collect();
+}
+
+fn get_user_bids(
+    deps: Deps,
+    user_address: Addr,
+    target_market: Option<u64>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Box<dyn Serialize>> {
+    let (user_orders, next_start_after) = page_user_orders(
+        deps,
+        user_address,
+        target_market,
+        Some(OrderSide::Buy),
+        start_after,
+        limit,
+    )?;
+
+    return Ok(Box::new(GetUserBidsResponse {
         orders: user_orders,
-    });
+        next_start_after: next_start_after,
+    }));
+}
+
+fn get_user_asks(
+    deps: Deps,
+    user_address: Addr,
+    target_market: Option<u64>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Box<dyn Serialize>> {
+    let (user_orders, next_start_after) = page_user_orders(
+        deps,
+        user_address,
+        target_market,
+        Some(OrderSide::Sell),
+        start_after,
+        limit,
+    )?;
+
+    return Ok(Box::new(GetUserAsksResponse {
+        orders: user_orders,
+        next_start_after: next_start_after,
+    }));
 }
 
 /*
@@ -193,24 +941,72 @@ fn get_user_bids(deps: Deps, user_address: Addr, target_market: Option<u64>) ->
 }
 */
 
-fn get_markets(deps: Deps) -> Box<dyn Serialize> {
-    let markets = MARKET_INFO
-        .range(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+fn get_markets(deps: Deps, start_after: Option<u64>, limit: Option<u32>) -> Box<dyn Serialize> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT) as usize;
+
+    let mut markets: Vec<SingleMarketInfo> = MARKET_INFO
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            cosmwasm_std::Order::Ascending,
+        )
+        .take(limit + 1)
         .filter_map(|elem| match elem {
             Err(_) => None,
             Ok((_, market_info)) => Some(SingleMarketInfo {
                 market_id: market_info.market_id,
                 quote_currency: market_info.quote_currency,
                 base_currency: market_info.base_currency,
+                base_decimals: market_info.base_decimals,
+                quote_decimals: market_info.quote_decimals,
+                maker_fee_bps: market_info.maker_fee_bps,
+                taker_fee_bps: market_info.taker_fee_bps,
+                status: market_info.status,
+                permissioned: market_info.permissioned,
+                tick_size: market_info.tick_size,
+                lot_size: market_info.lot_size,
             }),
         })
         .collect();
 
-    return Box::new(GetMarketsResponse { markets: markets });
-}
+    let mut next_start_after = None;
+    if markets.len() > limit {
+        markets.truncate(limit);
+        next_start_after = markets.last().map(|market| market.market_id);
+    }
 
-fn get_admin(deps: Deps) -> Box<dyn Serialize> {
-    return Box::new(GetAdminResponse {
-        admin: ADMIN.load(deps.storage).ok(),
+    return Box::new(GetMarketsResponse {
+        markets: markets,
+        next_start_after: next_start_after,
     });
 }
+
+fn get_admin(deps: Deps) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetAdminResponse {
+        admin: ADMIN.may_load(deps.storage)?,
+        pending_admin: PENDING_ADMIN.may_load(deps.storage)?,
+    }));
+}
+
+fn get_config(deps: Deps) -> StdResult<Box<dyn Serialize>> {
+    return Ok(Box::new(GetConfigResponse {
+        fee_collector: FEE_COLLECTOR.may_load(deps.storage)?,
+        default_market_params: DEFAULT_MARKET_PARAMS
+            .may_load(deps.storage)?
+            .unwrap_or_default(),
+        order_limits: ORDER_LIMITS.may_load(deps.storage)?.unwrap_or_default(),
+    }));
+}
+
+fn get_role_members(deps: Deps, role: Role) -> StdResult<Box<dyn Serialize>> {
+    let members = ROLE_MEMBERS
+        .prefix(role.as_str().to_string())
+        .keys(deps.storage, None, None, cosmwasm_std::Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    return Ok(Box::new(GetRoleMembersResponse {
+        role: role,
+        members: members,
+    }));
+}
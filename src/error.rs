@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Addr, Decimal, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -12,6 +12,48 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Address does not hold the role")]
+    RoleNotGranted {},
+
+    #[error("No admin has been proposed")]
+    NoPendingAdmin {},
+
+    #[error("Admin proposal has expired")]
+    AdminProposalExpired {},
+
+    #[error("Admin proposal must expire in the future")]
+    InvalidExpiry {},
+
+    #[error("Can't migrate from contract {name}")]
+    InvalidContractName { name: String },
+
+    #[error("Invalid contract version {version}")]
+    InvalidContractVersion { version: String },
+
+    #[error("Can't downgrade from version {stored} to {current}")]
+    CannotDowngrade { stored: String, current: String },
+
+    #[error("Price must be positive")]
+    ZeroPrice {},
+
+    #[error("Price must be a multiple of the tick size {tick_size}")]
+    PriceNotOnTick { tick_size: Decimal },
+
+    #[error("Tick size and lot size must be positive")]
+    InvalidMarketParams {},
+
+    #[error("Order limits must be positive")]
+    InvalidOrderLimits {},
+
+    #[error("Can't have more than {max} open orders on a market")]
+    TooManyUserOrders { max: u32 },
+
+    #[error("Can't have more than {max} orders at a price level")]
+    LevelFull { max: u32 },
+
+    #[error("No recipient given and no fee collector set")]
+    NoFeeCollector {},
+
     #[error("Unimplemented")]
     Unimplemented {},
 
@@ -29,4 +71,94 @@ pub enum ContractError {
 
     #[error("Not enough liquidity to execute market order")]
     NotEnoughLiquidityMarketOrder {},
+
+    #[error("Decimals must be provided for native currencies")]
+    MissingDecimals {},
+
+    #[error("Currency decimals can't be more than 18")]
+    InvalidDecimals {},
+
+    #[error("Fee can't be more than {max_bps} basis points")]
+    InvalidFee { max_bps: u16 },
+
+    #[error("Taker fee must cover the maker rebate")]
+    RebateNotCovered {},
+
+    #[error("Referral share can't be more than {max_bps} basis points")]
+    InvalidReferralShare { max_bps: u16 },
+
+    #[error("Referrer is already set")]
+    ReferrerAlreadySet {},
+
+    #[error("Users can't refer themselves")]
+    InvalidReferrer {},
+
+    #[error("Discounts must be sorted by strictly increasing locked amount, and at most 10000 basis points")]
+    InvalidFeeDiscounts {},
+
+    #[error("Unbonding period can't be more than {max_seconds} seconds")]
+    InvalidUnbondingPeriod { max_seconds: u64 },
+
+    #[error("Only the fee token can be locked")]
+    InvalidFeeToken {},
+
+    #[error("Fee token can't be changed once set")]
+    FeeTokenAlreadySet {},
+
+    #[error("Fee tiers must be sorted by strictly increasing volume")]
+    InvalidFeeTiers {},
+
+    #[error("Volume window must be between 1 and {max_days} days")]
+    InvalidVolumeWindow { max_days: u64 },
+
+    #[error("Currency is not a valid native denom or cw20 token")]
+    InvalidCurrency {},
+
+    #[error("Base and quote currencies must be different")]
+    SameCurrencyMarket {},
+
+    #[error("Market {id} already lists this pair")]
+    MarketAlreadyExists { id: u64 },
+
+    #[error("Market {id} already lists the inverse pair")]
+    InverseMarketExists { id: u64 },
+
+    #[error("Markets can only be added by the admin")]
+    ListingDisabled {},
+
+    #[error("Listing fee must be paid exactly, {amount} {denom}")]
+    InvalidListingFee { amount: Uint128, denom: String },
+
+    #[error("Only market listers can list native currencies")]
+    NativeListingRestricted {},
+
+    #[error("Market {id} is not active")]
+    MarketNotActive { id: u64 },
+
+    #[error("Market {id} is paused")]
+    MarketPaused { id: u64 },
+
+    #[error("Trader is not allowlisted on market {id}")]
+    NotAllowlisted { id: u64 },
+
+    #[error("Trader is blocklisted")]
+    Blocklisted {},
+
+    #[error("Market {id} is not delisted")]
+    MarketNotDelisted { id: u64 },
+
+    #[error("{address} is not blocklisted")]
+    NotBlocklisted { address: Addr },
+
+    #[error("Delisted markets can't change status")]
+    MarketDelisted {},
+
+    #[error("Order is too small to trade a single unit at this price")]
+    OrderTooSmall {},
+
+    #[error("Amount must be greater than zero")]
+    ZeroAmount {},
+
+    #[error("Insufficient internal balance")]
+    InsufficientBalance {},
 }
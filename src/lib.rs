@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
 pub mod contract;
 mod error;
 
@@ -11,6 +13,7 @@ pub mod state;
 pub mod structs;
 
 pub mod market_logic;
+pub mod migrations;
 pub mod state_utils;
 pub mod utils;
 
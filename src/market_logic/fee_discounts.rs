@@ -0,0 +1,47 @@
+use cosmwasm_std::{Addr, StdResult, Storage};
+
+use crate::{
+    state::{FEE_DISCOUNT_CONFIG, FEE_TOKEN_LOCKS},
+    utils::BPS_DENOMINATOR,
+};
+
+/// Discount on the taker fee of user, from the highest threshold reached by its locked fee tokens
+pub fn get_taker_discount_bps(storage: &dyn Storage, user: &Addr) -> StdResult<u16> {
+    let config = match FEE_DISCOUNT_CONFIG.may_load(storage)? {
+        None => return Ok(0),
+        Some(config) => config,
+    };
+
+    let locked = FEE_TOKEN_LOCKS
+        .may_load(storage, user.clone())?
+        .unwrap_or_default();
+
+    return Ok(config
+        .discounts
+        .iter()
+        .rev()
+        .find(|discount| discount.min_locked <= locked)
+        .map(|discount| discount.discount_bps)
+        .unwrap_or_default());
+}
+
+/// Fee left once discounted, the discount is rounded down
+pub fn apply_discount(fee_bps: u16, discount_bps: u16) -> u16 {
+    let discount = u32::from(fee_bps) * u32::from(discount_bps) / u32::from(BPS_DENOMINATOR);
+
+    return fee_bps - discount as u16;
+}
+
+/// Largest discount which can be given, 0 when there is no fee token
+pub fn get_max_discount_bps(storage: &dyn Storage) -> StdResult<u16> {
+    return Ok(FEE_DISCOUNT_CONFIG
+        .may_load(storage)?
+        .and_then(|config| {
+            config
+                .discounts
+                .iter()
+                .map(|discount| discount.discount_bps)
+                .max()
+        })
+        .unwrap_or_default());
+}
@@ -0,0 +1,289 @@
+use cosmwasm_std::{Addr, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::Bound;
+
+use crate::{
+    market_logic::fee_discounts,
+    state::{USER_QUOTE_VOLUMES, USER_VOLUMES},
+    structs::{FeeTierConfig, MarketInfo, UserFees, VolumeScope},
+};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Index of the day of a block, volumes are bucketed per day
+pub fn day_index(time: Timestamp) -> u64 {
+    return time.seconds() / SECONDS_PER_DAY;
+}
+
+/// First day counted in the window ending today
+fn window_start(config: &FeeTierConfig, today: u64) -> u64 {
+    return (today + 1).saturating_sub(config.window_days);
+}
+
+/// Volume of user on a market over the window
+fn get_market_volume(
+    storage: &dyn Storage,
+    config: &FeeTierConfig,
+    user: &Addr,
+    market_id: u64,
+    today: u64,
+) -> StdResult<Uint128> {
+    return USER_VOLUMES
+        .prefix((user.clone(), market_id))
+        .range(
+            storage,
+            Some(Bound::inclusive(window_start(config, today))),
+            None,
+            Order::Ascending,
+        )
+        .map(|elem| elem.map(|(_, volume)| volume))
+        .sum();
+}
+
+/// Volume of user on all markets quoted in denom over the window
+fn get_quote_volume(
+    storage: &dyn Storage,
+    config: &FeeTierConfig,
+    user: &Addr,
+    denom: &str,
+    today: u64,
+) -> StdResult<Uint128> {
+    return USER_QUOTE_VOLUMES
+        .prefix((user.clone(), denom.to_string()))
+        .range(
+            storage,
+            Some(Bound::inclusive(window_start(config, today))),
+            None,
+            Order::Ascending,
+        )
+        .map(|elem| elem.map(|(_, volume)| volume))
+        .sum();
+}
+
+/// Volume of user over the window, on the market or on all markets sharing the chosen quote currency
+pub fn get_user_volume(
+    storage: &dyn Storage,
+    config: &FeeTierConfig,
+    user: &Addr,
+    market_info: &MarketInfo,
+    today: u64,
+) -> StdResult<Uint128> {
+    return match &config.scope {
+        VolumeScope::Market => {
+            get_market_volume(storage, config, user, market_info.market_id, today)
+        }
+        VolumeScope::QuoteCurrency { denom } => {
+            get_quote_volume(storage, config, user, denom, today)
+        }
+    };
+}
+
+/// Fees of user on a market
+/// Tiers can only lower the fees of a market, never raise them, and locked fee tokens discount the taker fee
+pub fn get_user_fees(
+    storage: &dyn Storage,
+    config: &FeeTierConfig,
+    user: &Addr,
+    market_info: &MarketInfo,
+    today: u64,
+) -> StdResult<UserFees> {
+    let mut user_fees = UserFees {
+        tier: None,
+        maker_fee_bps: market_info.maker_fee_bps,
+        taker_fee_bps: market_info.taker_fee_bps,
+        taker_discount_bps: fee_discounts::get_taker_discount_bps(storage, user)?,
+    };
+
+    let volume = match config.tiers.is_empty() {
+        true => Uint128::zero(),
+        false => get_user_volume(storage, config, user, market_info, today)?,
+    };
+    if let Some(tier) = config
+        .tiers
+        .iter()
+        .rposition(|tier| tier.min_volume <= volume)
+    {
+        user_fees.tier = Some(tier as u32);
+        user_fees.maker_fee_bps = user_fees
+            .maker_fee_bps
+            .min(config.tiers[tier].maker_fee_bps);
+        user_fees.taker_fee_bps = user_fees
+            .taker_fee_bps
+            .min(config.tiers[tier].taker_fee_bps);
+    }
+
+    user_fees.taker_fee_bps =
+        fee_discounts::apply_discount(user_fees.taker_fee_bps, user_fees.taker_discount_bps);
+
+    return Ok(user_fees);
+}
+
+/// Add traded notional to the volume of user for today, and drop days which left the window
+/// The volume is counted both on the market and on its quote currency, so that either scope is read directly
+pub fn record_volume(
+    storage: &mut dyn Storage,
+    config: &FeeTierConfig,
+    user: &Addr,
+    market_info: &MarketInfo,
+    today: u64,
+    notional: Uint128,
+) -> StdResult<()> {
+    if notional.is_zero() {
+        return Ok(());
+    }
+    let market_id = market_info.market_id;
+    let quote_denom = market_info.quote_currency.get_denom();
+
+    let expired_days: Vec<u64> = USER_VOLUMES
+        .prefix((user.clone(), market_id))
+        .keys(
+            storage,
+            None,
+            Some(Bound::exclusive(window_start(config, today))),
+            Order::Ascending,
+        )
+        .collect::<StdResult<_>>()?;
+    for day in expired_days {
+        USER_VOLUMES.remove(storage, (user.clone(), market_id, day));
+    }
+
+    USER_VOLUMES.update(
+        storage,
+        (user.clone(), market_id, today),
+        |volume| -> StdResult<_> {
+            return Ok(volume.unwrap_or_default() + notional);
+        },
+    )?;
+
+    let expired_days: Vec<u64> = USER_QUOTE_VOLUMES
+        .prefix((user.clone(), quote_denom.clone()))
+        .keys(
+            storage,
+            None,
+            Some(Bound::exclusive(window_start(config, today))),
+            Order::Ascending,
+        )
+        .collect::<StdResult<_>>()?;
+    for day in expired_days {
+        USER_QUOTE_VOLUMES.remove(storage, (user.clone(), quote_denom.clone(), day));
+    }
+
+    USER_QUOTE_VOLUMES.update(
+        storage,
+        (user.clone(), quote_denom, today),
+        |volume| -> StdResult<_> {
+            return Ok(volume.unwrap_or_default() + notional);
+        },
+    )?;
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{testing::mock_dependencies, Addr, Uint128};
+
+    use crate::{
+        contract_admin_execute::add_market,
+        state::MARKET_INFO,
+        structs::{CurrencyInfo, FeeTier, FeeTierConfig, VolumeScope},
+    };
+
+    use super::{get_user_fees, get_user_volume, record_volume};
+
+    #[test]
+    fn fee_tiers_volume_window_and_tiers() {
+        let mut deps = mock_dependencies();
+
+        for base in ["heur", "hgbp"] {
+            add_market(
+                deps.as_mut(),
+                CurrencyInfo::Native { denom: base.into() },
+                CurrencyInfo::Native {
+                    denom: "husd".into(),
+                },
+                Some(6),
+                Some(6),
+                false,
+            )
+            .unwrap();
+        }
+
+        let other_market_info = MARKET_INFO.load(deps.as_ref().storage, 1).unwrap();
+        let mut market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
+        market_info.maker_fee_bps = 10;
+        market_info.taker_fee_bps = 20;
+
+        let mut config = FeeTierConfig {
+            window_days: 30,
+            scope: VolumeScope::Market,
+            tiers: vec![
+                FeeTier {
+                    min_volume: Uint128::new(1_000),
+                    maker_fee_bps: 8,
+                    taker_fee_bps: 15,
+                },
+                FeeTier {
+                    min_volume: Uint128::new(10_000),
+                    maker_fee_bps: 12,
+                    taker_fee_bps: 10,
+                },
+            ],
+        };
+
+        let user = Addr::unchecked("user");
+        let storage = deps.as_mut().storage;
+
+        record_volume(
+            storage,
+            &config,
+            &user,
+            &market_info,
+            100,
+            Uint128::new(5_000),
+        )
+        .unwrap();
+        record_volume(
+            storage,
+            &config,
+            &user,
+            &market_info,
+            110,
+            Uint128::new(6_000),
+        )
+        .unwrap();
+        record_volume(
+            storage,
+            &config,
+            &user,
+            &other_market_info,
+            110,
+            Uint128::new(700),
+        )
+        .unwrap();
+
+        // both days are in the window, tier fees can't be above market fees
+        let user_fees = get_user_fees(storage, &config, &user, &market_info, 110).unwrap();
+        assert_eq!(user_fees.tier, Some(1));
+        assert_eq!(user_fees.maker_fee_bps, 10);
+        assert_eq!(user_fees.taker_fee_bps, 10);
+
+        // first day has left the window
+        let user_fees = get_user_fees(storage, &config, &user, &market_info, 130).unwrap();
+        assert_eq!(user_fees.tier, Some(0));
+        assert_eq!(user_fees.maker_fee_bps, 8);
+
+        // volume on all markets quoted in husd
+        config.scope = VolumeScope::QuoteCurrency {
+            denom: "husd".into(),
+        };
+        assert_eq!(
+            get_user_volume(storage, &config, &user, &market_info, 130).unwrap(),
+            Uint128::new(6_700)
+        );
+
+        // nothing left in the window
+        let user_fees = get_user_fees(storage, &config, &user, &market_info, 200).unwrap();
+        assert_eq!(user_fees.tier, None);
+        assert_eq!(user_fees.taker_fee_bps, 20);
+    }
+}
@@ -1,28 +1,290 @@
 use std::cmp::Ordering;
 
-use cosmwasm_std::{Addr, CosmosMsg, Decimal, DepsMut, Uint128};
+use cosmwasm_std::{Addr, Decimal, DepsMut, Env, Response, StdError, StdResult, Storage, Uint128};
 
 use crate::{
-    market_logic::liquidity_provider,
-    state::{LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO, USER_ORDERS},
+    market_logic::{fee_tiers, liquidity_provider, market_stats},
+    state::{
+        user_orders, FEE_TIER_CONFIG, LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO, REFERRAL_SHARE_BPS,
+        REFERRERS,
+    },
     state_utils,
-    structs::{CurrencyStatus, LevelOrder, MarketInfo, OrderSide, UserOrderRecord},
-    utils::{create_funds_message, wrapped_comparison},
+    structs::{FundingSource, MarketInfo, OrderSide, OrderStatus, Settlement},
+    utils::{compute_escrow, compute_fee, compute_fill_notional, wrapped_comparison},
     ContractError,
 };
 
-use crate::structs::LevelOrders;
-
-use super::structs::{ConsumedOrdersLevel, LiquidityConsumer};
+use super::structs::{BookWalk, ConsumedOrdersLevel, LiquidityConsumer};
 
+/// Consume liquidity from the book for a taker order
+/// order_quantity is the amount received, so quote currency for a buy and base currency for a sell
+/// What is left of a limit order rests in the book, and any amount that can't be used is refunded
+/// Fees depend on the volume tier of each user before the trade, and the traded notional adds to their volume
+#[allow(clippy::too_many_arguments)]
 pub fn process_liquidity_taker(
     deps: DepsMut,
+    env: &Env,
     sender: Addr,
     market_id: u64,
     opt_order_price: Option<Decimal>, // optional, to check if market or limit order
     order_quantity: Uint128,
     order_side: OrderSide,
-) -> Result<Vec<CosmosMsg>, ContractError> {
+    funding: FundingSource,
+) -> Result<Response, ContractError> {
+    // access market info
+    let market_info = MARKET_INFO.load(deps.storage, market_id)?;
+
+    let BookWalk {
+        consumed_orders,
+        emptied_levels,
+        partial_level,
+        base_traded,
+        quote_traded,
+        mut remaining_quantity,
+        is_book_exhausted,
+    } = walk_book(
+        deps.storage,
+        &market_info,
+        opt_order_price,
+        order_quantity,
+        order_side.clone(),
+    )?;
+
+    // a market order can't rest in the book with what is left
+    if opt_order_price.is_none() && is_book_exhausted {
+        return Err(ContractError::NotEnoughLiquidityMarketOrder {});
+    }
+
+    // levels fully consumed are removed, this also moves the top of book to the next level
+    for level_id in emptied_levels {
+        state_utils::remove_level(deps.storage, market_id, level_id)?;
+    }
+    // there are remaining orders at the last level, so the order has been filled as much as it can be
+    if let Some((level_id, level_orders)) = partial_level {
+        LEVEL_ORDERS.save(deps.storage, level_id, &level_orders)?;
+    }
+
+    // a market order too small to buy a single unit at the top of book
+    if opt_order_price.is_none() && base_traded.is_zero() {
+        return Err(ContractError::OrderTooSmall {});
+    }
+
+    // process consumed orders in state of user orders
+    // proceeds of makers, net of maker fees or with their rebates, are credited to their internal balance
+    // and netted per recipient and currency, so a maker filled at several levels is only credited once
+    let fee_tier_config = FEE_TIER_CONFIG.may_load(deps.storage)?.unwrap_or_default();
+    let today = fee_tiers::day_index(env.block.time);
+
+    let taker_fees =
+        fee_tiers::get_user_fees(deps.storage, &fee_tier_config, &sender, &market_info, today)?;
+
+    // volumes are recorded once all fills are priced, so a trade is charged at a single tier
+    // and the fees of each maker are only looked up once, with the maker fee and the volume of the trade
+    let mut maker_volumes: Vec<(Addr, i16, Uint128)> = vec![];
+    let mut total_rebates = Uint128::zero();
+    let mut settlement = Settlement::new();
+    for cons in &consumed_orders {
+        for order in &cons.orders {
+            let mut maker_order = user_orders().load(deps.storage, order.order_id)?;
+
+            let notional = compute_fill_notional(order.amount, cons.price);
+            maker_order.quantity -= order.amount;
+            maker_order.filled_quantity += order.amount;
+            maker_order.filled_notional += notional;
+
+            // the fill is paid out of what the maker locked
+            let (maker_currency_received, maker_proceeds, maker_delivered) = match order_side {
+                // the trader buys, makers sold base currency for its quote value
+                OrderSide::Buy => {
+                    maker_order.locked -= order.amount;
+                    (&market_info.quote_currency, notional, order.amount)
+                }
+                // the trader sells, makers bought base currency
+                OrderSide::Sell => {
+                    maker_order.locked -= notional;
+                    (&market_info.base_currency, order.amount, notional)
+                }
+            };
+            let maker_currency =
+                market_info.get_currency_info_from_side(maker_order.order_side.clone());
+
+            let maker_fee_bps = match maker_volumes
+                .iter_mut()
+                .find(|(user, _, _)| *user == order.user)
+            {
+                Some((_, maker_fee_bps, volume)) => {
+                    *volume += notional;
+                    *maker_fee_bps
+                }
+                None => {
+                    let maker_fees = fee_tiers::get_user_fees(
+                        deps.storage,
+                        &fee_tier_config,
+                        &order.user,
+                        &market_info,
+                        today,
+                    )?;
+                    maker_volumes.push((order.user.clone(), maker_fees.maker_fee_bps, notional));
+                    maker_fees.maker_fee_bps
+                }
+            };
+
+            if maker_fee_bps >= 0 {
+                let maker_fee = compute_fee(maker_proceeds, maker_fee_bps as u16);
+                settlement.add_fee(maker_currency_received, maker_fee);
+                settlement.add_credit(
+                    &order.user,
+                    maker_currency_received,
+                    maker_proceeds - maker_fee,
+                );
+            } else {
+                // rebates are paid in the currency the trader receives, so that the taker fee funds them
+                let maker_rebate = compute_fee(maker_delivered, maker_fee_bps.unsigned_abs());
+                settlement.add_credit(&order.user, maker_currency_received, maker_proceeds);
+                settlement.add_credit(&order.user, &maker_currency, maker_rebate);
+                total_rebates += maker_rebate;
+            }
+
+            // a filled order gives back what it still locks, which is rounding dust
+            if maker_order.quantity.is_zero() {
+                settlement.add_credit(&order.user, &maker_currency, maker_order.locked);
+                state_utils::close_order(
+                    deps.storage,
+                    maker_order,
+                    OrderStatus::Filled,
+                    env.block.time,
+                )?;
+            } else {
+                user_orders().save(deps.storage, maker_order.order_id, &maker_order)?;
+            }
+        }
+    }
+
+    // what is left of a limit order is added as a maker order
+    let mut resting_order_id = None;
+    if let Some(order_price) = opt_order_price {
+        let resting_quantity = market_info.round_to_lot(match order_side {
+            OrderSide::Sell => remaining_quantity,
+            OrderSide::Buy => remaining_quantity.checked_div_floor(order_price).unwrap(),
+        });
+
+        if !resting_quantity.is_zero() {
+            let order_id = liquidity_provider::add_maker_order(
+                deps.storage,
+                sender.clone(),
+                market_id,
+                order_price,
+                resting_quantity,
+                order_side.clone(),
+                env.block.time,
+            )?;
+            resting_order_id = Some(order_id);
+
+            // the order was partly filled before resting
+            if !base_traded.is_zero() {
+                user_orders().update(
+                    deps.storage,
+                    order_id,
+                    |order| -> Result<_, ContractError> {
+                        let mut order = order.unwrap();
+                        order.original_quantity += base_traded;
+                        order.filled_quantity = base_traded;
+                        order.filled_notional = quote_traded;
+
+                        return Ok(order);
+                    },
+                )?;
+            }
+
+            remaining_quantity -= compute_escrow(resting_quantity, order_price, &order_side);
+        }
+    }
+
+    // proceeds of the trader, and what could not be used of the order, follow the funding of the order
+    let (trader_currency_received, trader_proceeds, trader_currency_sent) = match order_side {
+        OrderSide::Buy => (
+            &market_info.base_currency,
+            base_traded,
+            &market_info.quote_currency,
+        ),
+        OrderSide::Sell => (
+            &market_info.quote_currency,
+            quote_traded,
+            &market_info.base_currency,
+        ),
+    };
+
+    for (maker, _, volume) in maker_volumes {
+        fee_tiers::record_volume(
+            deps.storage,
+            &fee_tier_config,
+            &maker,
+            &market_info,
+            today,
+            volume,
+        )?;
+    }
+    fee_tiers::record_volume(
+        deps.storage,
+        &fee_tier_config,
+        &sender,
+        &market_info,
+        today,
+        quote_traded,
+    )?;
+    // the last level consumed gives the last price
+    if let Some(last_level) = consumed_orders.last() {
+        market_stats::record_trade(
+            deps.storage,
+            market_id,
+            env.block.time,
+            last_level.price,
+            base_traded,
+            quote_traded,
+        )?;
+    }
+
+    // the taker fee covers the rebates, checked when fees are set
+    let taker_fee = compute_fee(trader_proceeds, taker_fees.taker_fee_bps);
+    let mut protocol_fee = taker_fee
+        .checked_sub(total_rebates)
+        .map_err(StdError::from)?;
+
+    // the referrer of the trader gets a share of what the protocol keeps of the taker fee
+    if let Some(referrer) = REFERRERS.may_load(deps.storage, sender.clone())? {
+        let referral_share_bps = REFERRAL_SHARE_BPS
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+        let referral_reward = compute_fee(protocol_fee, referral_share_bps);
+        settlement.add_referral_reward(&referrer, trader_currency_received, referral_reward);
+        protocol_fee -= referral_reward;
+    }
+    settlement.add_fee(trader_currency_received, protocol_fee);
+    settlement.add_for_funding(
+        &funding,
+        &sender,
+        trader_currency_received,
+        trader_proceeds - taker_fee,
+    );
+    settlement.add_for_funding(&funding, &sender, trader_currency_sent, remaining_quantity);
+
+    let mut response = Response::new().add_messages(settlement.settle(deps.storage)?);
+    if let Some(order_id) = resting_order_id {
+        response = response.add_attribute("order_id", order_id.to_string());
+    }
+
+    return Ok(response);
+}
+
+/// Walk the book from its top for a taker order, without changing the state
+/// The levels consumed are only returned, process_liquidity_taker removes or saves them
+pub fn walk_book(
+    storage: &dyn Storage,
+    market_info: &MarketInfo,
+    opt_order_price: Option<Decimal>,
+    order_quantity: Uint128,
+    order_side: OrderSide,
+) -> StdResult<BookWalk> {
     // we start by setting some comparators
     // this allows us to reuse code for bids and asks
     let (_closer_to_midprice_comparator, further_to_midprice_comparator) = match order_side {
@@ -30,74 +292,28 @@ pub fn process_liquidity_taker(
         OrderSide::Buy => (Ordering::Less, Ordering::Greater),
     };
 
-    // access market info
-    let mut market_info = MARKET_INFO.load(deps.storage, market_id)?;
-    let currency_status = MarketInfo::get_currency_status_from_order_side(order_side.clone());
-
-    //let mut id_prev_level: Option<u64> = None;
     let mut id_current_level = match order_side {
         OrderSide::Buy => market_info.top_level_ask,
         OrderSide::Sell => market_info.top_level_bid,
     };
 
-    let mut to_send_back = Uint128::zero();
-    let mut consumed_orders: Vec<ConsumedOrdersLevel> = vec![];
-    let mut remaining_quantity: Uint128 = order_quantity;
+    let mut walk = BookWalk::new(order_quantity);
     loop {
+        if walk.remaining_quantity.is_zero() {
+            break;
+        }
+
         match id_current_level {
-            // no current market, if it is a taker then we are at the end of the list and must put a limit order
-            // and update market info
+            // end of the list, the book has been fully consumed
+            // a limit order will rest in the book with what is left
             None => {
-                match opt_order_price {
-                    None => return Err(ContractError::NotEnoughLiquidityMarketOrder {}),
-                    Some(val_order_price) => {
-                        // end of the line, need to set a limit order
-                        // this means the book has been fully consumed, so must remove top level of market
-                        match order_side {
-                            OrderSide::Buy => {
-                                market_info.top_level_ask = None;
-                            }
-                            OrderSide::Sell => {
-                                market_info.top_level_bid = None;
-                            }
-                        }
-
-                        // and save
-                        MARKET_INFO.save(deps.storage, market_id, &market_info)?;
-
-                        // now insert the new level
-                        liquidity_provider::process_limit_maker(
-                            deps.storage,
-                            sender.clone(),
-                            market_id,
-                            val_order_price,
-                            remaining_quantity,
-                            order_side.clone(),
-                        )?;
-
-                        // need to add order for user
-                        USER_ORDERS.update(
-                            deps.storage,
-                            sender.clone(),
-                            |orders| -> Result<_, ContractError> {
-                                let mut orders = orders.unwrap_or_default();
-                                orders.push(UserOrderRecord {
-                                    market_id: market_id,
-                                    order_side: order_side.clone(),
-                                    price: opt_order_price.unwrap(),
-                                    quantity: remaining_quantity,
-                                });
-                                return Ok(orders);
-                            },
-                        )?;
-                        break;
-                    }
-                }
+                walk.is_book_exhausted = true;
+                break;
             }
-            // there is a following market in the list, compare its price to the order price
-            // and stop with insert or continue consuming liquidity
+            // there is a following level in the list, compare its price to the order price
+            // and stop or continue consuming liquidity
             Some(val_id_current_level) => {
-                let curr_level_data = LEVELS_DATA.load(deps.storage, val_id_current_level)?;
+                let curr_level_data = LEVELS_DATA.load(storage, val_id_current_level)?;
                 // seperate between market and limit orders
                 let is_consume_level = match opt_order_price {
                     None => true,
@@ -108,178 +324,122 @@ pub fn process_liquidity_taker(
                     ),
                 };
 
-                if is_consume_level {
-                    // consume the level
-                    let mut level_orders = LEVEL_ORDERS.load(deps.storage, val_id_current_level)?;
-                    let consumption_result =
-                        level_orders.consume(curr_level_data.price, remaining_quantity);
-
-                    match currency_status {
-                        CurrencyStatus::QuoteCurrency => {
-                            // received base currency in input, so output is quote currency
-                            to_send_back += consumption_result.to_send_back;
-                        }
-                        CurrencyStatus::BaseCurrency => {
-                            // need to convert amount
-                            to_send_back += consumption_result
-                                .to_send_back
-                                .checked_mul_ceil(curr_level_data.price)
-                                .unwrap();
-                        }
-                    }
+                if !is_consume_level {
+                    // we do not consume the next level, this is a limit taker
+                    break;
+                }
 
-                    if consumption_result.remaining_to_consume.is_zero() {
-                        // check if there are orders remaining in the current level to update market info
-                        if level_orders.len() == 0 {
-                            // if there are none, remove this level
-                            match order_side {
-                                OrderSide::Buy => {
-                                    market_info.top_level_ask = curr_level_data.id_next;
-                                }
-                                OrderSide::Sell => {
-                                    market_info.top_level_bid = curr_level_data.id_next;
-                                }
-                            }
-                            MARKET_INFO.save(deps.storage, market_id, &market_info)?;
-                            
-                            state_utils::remove_level(
-                                deps.storage,
-                                market_id,
-                                val_id_current_level,
-                            )?;
-                        } else {
-                            // there are remaning orders, update market info top of book with current level
-                            match order_side {
-                                OrderSide::Buy => {
-                                    market_info.top_level_ask = id_current_level;
-                                }
-                                OrderSide::Sell => {
-                                    market_info.top_level_bid = id_current_level;
-                                }
-                            }
-                            MARKET_INFO.save(deps.storage, market_id, &market_info)?;
-
-                            // and save the remaining orders
-                            LEVEL_ORDERS.save(deps.storage, val_id_current_level, &level_orders)?;
-                        }
-
-                        consumed_orders.push(ConsumedOrdersLevel::from_consumption_result(
-                            curr_level_data.price,
-                            consumption_result,
-                        ));
-
-                        // nothing left in the consuming order, can break out of the consuming logic
-                        break;
-                    } else {
-                        // order has not been fully filled yet, continue in consumption logic
-                        // level has been fully consumed, remove it
-                        state_utils::remove_level(deps.storage, market_id, val_id_current_level)?;
-                        remaining_quantity = consumption_result.remaining_to_consume;
-
-                        consumed_orders.push(ConsumedOrdersLevel::from_consumption_result(
-                            curr_level_data.price,
-                            consumption_result,
-                        ));
-
-                        // set next level to consume
-                        id_current_level = curr_level_data.id_next;
-                    }
-                } else {
-                    // we do not consume the next level
-                    // this means this is a limit taker, so need to add a level
-                    liquidity_provider::process_limit_maker(
-                        deps.storage,
-                        sender.clone(),
-                        market_id,
-                        opt_order_price.unwrap(),
-                        remaining_quantity,
-                        order_side.clone(),
-                    )?;
+                // quantity of base currency that can be taken from this level
+                let to_consume = match order_side {
+                    OrderSide::Sell => walk.remaining_quantity,
+                    OrderSide::Buy => walk
+                        .remaining_quantity
+                        .checked_div_floor(curr_level_data.price)
+                        .map_err(|err| StdError::generic_err(err.to_string()))?,
+                };
 
-                    // need to add order for user
-                    USER_ORDERS.update(
-                        deps.storage,
-                        sender.clone(),
-                        |orders| -> Result<_, ContractError> {
-                            let mut orders = orders.unwrap_or_default();
-                            orders.push(UserOrderRecord {
-                                market_id: market_id,
-                                order_side: order_side.clone(),
-                                price: opt_order_price.unwrap(),
-                                quantity: remaining_quantity,
-                            });
-                            return Ok(orders);
-                        },
-                    )?;
+                // what is left can't buy a single unit at this price, nor further in the book
+                if to_consume.is_zero() {
+                    break;
+                }
+
+                // consume the level
+                let mut level_orders = LEVEL_ORDERS.load(storage, val_id_current_level)?;
+                let consumption_result = level_orders.consume(curr_level_data.price, to_consume);
+
+                // each fill is valued on its own, so that trader and makers agree on the amounts
+                let consumed = to_consume - consumption_result.remaining_to_consume;
+                let notional: Uint128 = consumption_result
+                    .bin_records_consumed
+                    .iter()
+                    .map(|order| compute_fill_notional(order.amount, curr_level_data.price))
+                    .sum();
+
+                walk.base_traded += consumed;
+                walk.quote_traded += notional;
+                walk.remaining_quantity = match order_side {
+                    OrderSide::Sell => walk.remaining_quantity - consumed,
+                    OrderSide::Buy => walk.remaining_quantity - notional,
+                };
+
+                walk.consumed_orders
+                    .push(ConsumedOrdersLevel::from_consumption_result(
+                        curr_level_data.price,
+                        consumption_result,
+                    ));
+
+                if level_orders.is_empty() {
+                    // level has been fully consumed, move on to the next level
+                    walk.emptied_levels.push(val_id_current_level);
+                    id_current_level = curr_level_data.id_next;
+                } else {
+                    walk.partial_level = Some((val_id_current_level, level_orders));
                     break;
                 }
             }
         }
     }
 
-    // process consumed orders in state of user orders
-    let currency_info = market_info.get_currency_info_from_side(order_side.clone());
+    return Ok(walk);
+}
 
-    let mut messages: Vec<CosmosMsg> = vec![];
-    for cons in &consumed_orders {
-        for order in &cons.orders {
-            USER_ORDERS.update(
-                deps.storage,
-                order.user.clone(),
-                |user_orders| -> Result<_, ContractError> {
-                    //let mut user_orders = user_orders.unwrap();
-
-                    let user_orders = user_orders
-                        .unwrap()
-                        .into_iter()
-                        .filter_map(|mut user_order| {
-                            if user_order.market_id == market_id && user_order.price == cons.price {
-                                user_order.quantity -= order.amount;
-                                if user_order.quantity.is_zero() {
-                                    None
-                                } else {
-                                    Some(user_order)
-                                }
-                            } else {
-                                Some(user_order)
-                            }
-                        })
-                        .collect();
-
-                    return Ok(user_orders);
-                },
-            )?;
+/// Smallest offer of a taker order to receive proceeds before fees, base currency for a buy and quote currency for a sell
+/// Returns the offer and whether the book holds enough, else the offer which takes the whole side
+/// A buy values each fill on its own and spends what is left at the next level,
+/// so the offer can fall a few units short, which walk_book tells
+pub fn estimate_offer(
+    storage: &dyn Storage,
+    market_info: &MarketInfo,
+    order_side: OrderSide,
+    proceeds: Uint128,
+) -> StdResult<(Uint128, bool)> {
+    let mut id_current_level = match order_side {
+        OrderSide::Buy => market_info.top_level_ask,
+        OrderSide::Sell => market_info.top_level_bid,
+    };
 
-            let return_amount = match currency_status {
-                CurrencyStatus::QuoteCurrency => {
-                    // received base currency in input, so output is quote currency
-                    order.amount.checked_mul_ceil(cons.price).unwrap()
+    let mut offer = Uint128::zero();
+    let mut remaining_proceeds = proceeds;
+    while let Some(val_id_current_level) = id_current_level {
+        if remaining_proceeds.is_zero() {
+            break;
+        }
+
+        let curr_level_data = LEVELS_DATA.load(storage, val_id_current_level)?;
+        let price = curr_level_data.price;
+        // orders are consumed from the end of the level
+        for order in LEVEL_ORDERS
+            .load(storage, val_id_current_level)?
+            .iter()
+            .rev()
+        {
+            match order_side {
+                OrderSide::Sell => {
+                    let notional = compute_fill_notional(order.amount, price);
+                    if notional >= remaining_proceeds {
+                        let last_offer = remaining_proceeds
+                            .checked_div_ceil(price)
+                            .map_err(|err| StdError::generic_err(err.to_string()))?;
+                        return Ok((offer.checked_add(last_offer)?, true));
+                    }
+                    offer = offer.checked_add(order.amount)?;
+                    remaining_proceeds -= notional;
                 }
-                CurrencyStatus::BaseCurrency => {
-                    // need to convert amount
-                    order.amount
+                OrderSide::Buy => {
+                    if order.amount >= remaining_proceeds {
+                        let last_offer = remaining_proceeds
+                            .checked_mul_ceil(price)
+                            .map_err(|err| StdError::generic_err(err.to_string()))?;
+                        return Ok((offer.checked_add(last_offer)?, true));
+                    }
+                    offer = offer.checked_add(compute_fill_notional(order.amount, price))?;
+                    remaining_proceeds -= order.amount;
                 }
-            };
-
-            messages.push(create_funds_message(
-                return_amount,
-                currency_info.clone(),
-                order.user.clone(),
-            ));
+            }
         }
-    }
-
-    // add funds to send back to trader
-    let trader_currency_info = market_info.get_currency_info_from_side(match order_side {
-        OrderSide::Buy => OrderSide::Sell,
-        OrderSide::Sell => OrderSide::Buy,
-    });
 
-    messages.push(create_funds_message(
-        to_send_back,
-        trader_currency_info,
-        sender,
-    ));
+        id_current_level = curr_level_data.id_next;
+    }
 
-    return Ok(messages);
+    return Ok((offer, remaining_proceeds.is_zero()));
 }
@@ -1,4 +1,4 @@
 pub mod consumer_logic;
 pub mod structs;
 
-pub use consumer_logic::process_liquidity_taker;
+pub use consumer_logic::{estimate_offer, process_liquidity_taker, walk_book};
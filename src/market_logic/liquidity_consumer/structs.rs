@@ -2,7 +2,7 @@ use cosmwasm_std::{Decimal, Uint128};
 
 use crate::structs::LevelOrder;
 
-use crate::structs::LevelOrders;
+use crate::structs::{LevelOrders, OrderSide};
 
 #[derive(Debug)]
 pub struct ConsumptionResult {
@@ -40,6 +40,54 @@ impl ConsumedOrdersLevel {
     }
 }
 
+/// Outcome of walking the book for a taker order
+pub struct BookWalk {
+    /// orders filled, per level from the top of book
+    pub consumed_orders: Vec<ConsumedOrdersLevel>,
+    /// levels left without orders
+    pub emptied_levels: Vec<u64>,
+    /// the last level consumed, with the orders left in it
+    pub partial_level: Option<(u64, LevelOrders)>,
+    /// base currency bought or sold by the trader, and its value in quote currency
+    pub base_traded: Uint128,
+    pub quote_traded: Uint128,
+    /// what is left of the order, in the currency received
+    pub remaining_quantity: Uint128,
+    /// the whole side of the book was consumed before the order was filled
+    pub is_book_exhausted: bool,
+}
+
+impl BookWalk {
+    pub fn new(order_quantity: Uint128) -> Self {
+        return BookWalk {
+            consumed_orders: vec![],
+            emptied_levels: vec![],
+            partial_level: None,
+            base_traded: Uint128::zero(),
+            quote_traded: Uint128::zero(),
+            remaining_quantity: order_quantity,
+            is_book_exhausted: false,
+        };
+    }
+
+    /// what the trader receives before fees, base currency for a buy and quote currency for a sell
+    pub fn proceeds(&self, order_side: &OrderSide) -> Uint128 {
+        return match order_side {
+            OrderSide::Buy => self.base_traded,
+            OrderSide::Sell => self.quote_traded,
+        };
+    }
+
+    /// average price of the fills, none if nothing was traded
+    pub fn average_price(&self) -> Option<Decimal> {
+        if self.base_traded.is_zero() {
+            return None;
+        }
+
+        return Some(Decimal::from_ratio(self.quote_traded, self.base_traded));
+    }
+}
+
 pub trait LiquidityConsumer {
     fn consume(
         &mut self,
@@ -52,7 +100,7 @@ pub trait LiquidityConsumer {
 impl LiquidityConsumer for LevelOrders {
     fn consume(
         &mut self,
-        price: Decimal,
+        _price: Decimal,
         quantity: Uint128,
         //order_side: OrderSide,
     ) -> ConsumptionResult {
@@ -65,10 +113,11 @@ impl LiquidityConsumer for LevelOrders {
                     rslt.bin_records_consumed.push(curr);
                 } else {
                     rslt.bin_records_consumed.push(LevelOrder {
+                        order_id: curr.order_id,
                         user: curr.user.clone(),
                         amount: rslt.remaining_to_consume,
                     });
-                    curr.amount -= curr.amount;
+                    curr.amount -= rslt.remaining_to_consume;
                     rslt.to_send_back += rslt.remaining_to_consume; //.checked_div_floor(price).unwrap();
                     rslt.remaining_to_consume = Uint128::zero();
 
@@ -83,7 +132,7 @@ impl LiquidityConsumer for LevelOrders {
             }
         }
 
-        if self.len() == 0 {
+        if self.is_empty() {
             rslt.is_fully_consumed = true;
         }
 
@@ -126,10 +175,12 @@ mod tests {
 
             let mut level_orders = vec![
                 LevelOrder {
+                    order_id: 0,
                     user: user_1,
                     amount: Uint128::new(1),
                 },
                 LevelOrder {
+                    order_id: 0,
                     user: user_2,
                     amount: Uint128::new(1),
                 },
@@ -138,7 +189,7 @@ mod tests {
             let price = Decimal::from_atomics(5u128, 1).unwrap();
             println!("{}", price);
 
-            let order_side = OrderSide::Sell;
+            let _order_side = OrderSide::Sell;
 
             // so levels are bids, we have a seller here
             // so to clear it all, need to sell 1
@@ -159,10 +210,12 @@ mod tests {
 
             let mut level_orders = vec![
                 LevelOrder {
+                    order_id: 0,
                     user: user_1,
                     amount: Uint128::new(1),
                 },
                 LevelOrder {
+                    order_id: 0,
                     user: user_2,
                     amount: Uint128::new(1),
                 },
@@ -170,7 +223,7 @@ mod tests {
 
             let price = Decimal::from_atomics(5u128, 1).unwrap();
 
-            let order_side = OrderSide::Sell;
+            let _order_side = OrderSide::Sell;
 
             // so levels are bids, we have a seller here
             // so to clear it all, need to sell 1
@@ -189,21 +242,23 @@ mod tests {
         fn consumer_sell_order_price_1_no_remainder() {
             let user_1 = Addr::unchecked("user1");
             let user_2 = Addr::unchecked("user2");
-            let user_3 = Addr::unchecked("user3");
+            let _user_3 = Addr::unchecked("user3");
 
             let mut level_orders = vec![
                 LevelOrder {
+                    order_id: 0,
                     user: user_1,
                     amount: Uint128::new(1),
                 },
                 LevelOrder {
+                    order_id: 0,
                     user: user_2,
                     amount: Uint128::new(1),
                 },
             ];
 
             let price = Decimal::from_atomics(1u128, 0).unwrap();
-            let order_side = OrderSide::Sell;
+            let _order_side = OrderSide::Sell;
 
             // so levels are bids, we have a seller here
             // so to clear it all, need to sell 2
@@ -225,10 +280,12 @@ mod tests {
 
             let mut level_orders = vec![
                 LevelOrder {
+                    order_id: 0,
                     user: user_1,
                     amount: Uint128::new(1),
                 },
                 LevelOrder {
+                    order_id: 0,
                     user: user_2,
                     amount: Uint128::new(1),
                 },
@@ -237,7 +294,7 @@ mod tests {
             let price = Decimal::from_atomics(5u128, 1).unwrap();
             println!("{}", price);
 
-            let order_side = OrderSide::Buy;
+            let _order_side = OrderSide::Buy;
 
             // so levels are asks, we have a buyer here
             // so to clear it all, need to sell 1
@@ -258,10 +315,12 @@ mod tests {
 
             let mut level_orders = vec![
                 LevelOrder {
+                    order_id: 0,
                     user: user_1,
                     amount: Uint128::new(1),
                 },
                 LevelOrder {
+                    order_id: 0,
                     user: user_2,
                     amount: Uint128::new(1),
                 },
@@ -269,7 +328,7 @@ mod tests {
 
             let price = Decimal::from_atomics(5u128, 1).unwrap();
 
-            let order_side = OrderSide::Buy;
+            let _order_side = OrderSide::Buy;
 
             // so levels are asks, we have a buyer here
             // so to clear it all, need to sell 1
@@ -291,17 +350,19 @@ mod tests {
 
             let mut level_orders = vec![
                 LevelOrder {
+                    order_id: 0,
                     user: user_1,
                     amount: Uint128::new(1),
                 },
                 LevelOrder {
+                    order_id: 0,
                     user: user_2,
                     amount: Uint128::new(1),
                 },
             ];
 
             let price = Decimal::from_atomics(1u128, 0).unwrap();
-            let order_side = OrderSide::Buy;
+            let _order_side = OrderSide::Buy;
 
             // so levels are asks, we have a buyer here
             // so to clear it all, need to sell 2
@@ -311,5 +372,37 @@ mod tests {
             assert_eq!(level_orders.len(), 0);
             assert!(rslt.remaining_to_consume.is_zero());
         }
+
+        /// a partially consumed order must stay in the level with what is left of it
+        #[test]
+        fn consumer_buy_order_partial_keeps_remainder() {
+            let user_1 = Addr::unchecked("user1");
+            let user_2 = Addr::unchecked("user2");
+
+            let mut level_orders = vec![
+                LevelOrder {
+                    order_id: 0,
+                    user: user_1.clone(),
+                    amount: Uint128::new(5),
+                },
+                LevelOrder {
+                    order_id: 0,
+                    user: user_2.clone(),
+                    amount: Uint128::new(3),
+                },
+            ];
+
+            let price = Decimal::one();
+            let rslt = level_orders.consume(price, Uint128::new(4));
+            assert!(!rslt.is_fully_consumed);
+            assert!(rslt.remaining_to_consume.is_zero());
+            assert_eq!(rslt.bin_records_consumed.len(), 2);
+            assert_eq!(rslt.bin_records_consumed[1].user, user_1);
+            assert_eq!(rslt.bin_records_consumed[1].amount, Uint128::new(1));
+
+            assert_eq!(level_orders.len(), 1);
+            assert_eq!(level_orders[0].user, user_1);
+            assert_eq!(level_orders[0].amount, Uint128::new(4));
+        }
     }
 }
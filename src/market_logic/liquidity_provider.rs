@@ -1,14 +1,76 @@
 use std::cmp::Ordering;
 
-use cosmwasm_std::{Addr, Decimal, Response, Storage, Uint128};
+use cosmwasm_std::{Addr, Decimal, Order, Response, StdResult, Storage, Timestamp, Uint128};
 
 use crate::{
-    state::{LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO},
-    structs::{LevelData, LevelOrder, OrderSide},
-    utils::{create_id_level_no_status, wrapped_comparison},
+    state::{user_orders, LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO, ORDER_LIMITS},
+    state_utils,
+    structs::{LevelData, LevelOrder, OrderSide, UserOrderRecord},
+    utils::{compute_escrow, create_id_level_no_status, wrapped_comparison},
     ContractError,
 };
 
+/// Record a new order for the user and add it to the book
+/// The order locks the escrow of its quantity, anything received above it must be refunded by the caller
+/// Orders are rejected once the user reaches the cap of open orders on the market
+/// Returns the id of the new order
+pub fn add_maker_order(
+    storage: &mut dyn Storage,
+    sender: Addr,
+    market_id: u64,
+    order_price: Decimal,
+    order_quantity: Uint128,
+    order_side: OrderSide,
+    created_at: Timestamp,
+) -> Result<u64, ContractError> {
+    let order_limits = ORDER_LIMITS.may_load(storage)?.unwrap_or_default();
+    if let Some(max) = order_limits.max_orders_per_user {
+        // only up to the cap is walked, whatever the number of orders on other markets
+        let open_orders = user_orders()
+            .idx
+            .user_market
+            .prefix((sender.clone(), market_id))
+            .keys(storage, None, None, Order::Ascending)
+            .take(max as usize)
+            .collect::<StdResult<Vec<_>>>()?
+            .len();
+        if open_orders >= max as usize {
+            return Err(ContractError::TooManyUserOrders { max: max });
+        }
+    }
+
+    let order_id = state_utils::next_order_id(storage)?;
+    user_orders().save(
+        storage,
+        order_id,
+        &UserOrderRecord {
+            order_id: order_id,
+            user: sender.clone(),
+            order_side: order_side.clone(),
+            price: order_price,
+            market_id: market_id,
+            quantity: order_quantity,
+            locked: compute_escrow(order_quantity, order_price, &order_side),
+            original_quantity: order_quantity,
+            filled_quantity: Uint128::zero(),
+            filled_notional: Uint128::zero(),
+            created_at: created_at,
+        },
+    )?;
+
+    process_limit_maker(
+        storage,
+        sender,
+        market_id,
+        order_price,
+        order_quantity,
+        order_side,
+        order_id,
+    )?;
+
+    return Ok(order_id);
+}
+
 pub fn process_limit_maker(
     storage: &mut dyn Storage,
     sender: Addr,
@@ -16,6 +78,7 @@ pub fn process_limit_maker(
     order_price: Decimal,
     order_quantity: Uint128,
     order_side: OrderSide,
+    order_id: u64,
 ) -> Result<Response, ContractError> {
     // we start by setting some comparators
     // this allows us to reuse code for bids and asks
@@ -65,6 +128,7 @@ pub fn process_limit_maker(
                         };
 
                         let level_orders = vec![LevelOrder {
+                            order_id: order_id,
                             user: sender.clone(),
                             amount: order_quantity,
                         }];
@@ -86,6 +150,7 @@ pub fn process_limit_maker(
                         };
 
                         let level_orders = vec![LevelOrder {
+                            order_id: order_id,
                             user: sender.clone(),
                             amount: order_quantity,
                         }];
@@ -129,6 +194,7 @@ pub fn process_limit_maker(
                     };
 
                     let level_orders = vec![LevelOrder {
+                        order_id: order_id,
                         user: sender.clone(),
                         amount: order_quantity,
                     }];
@@ -181,13 +247,23 @@ pub fn process_limit_maker(
 
                     break;
                 } else if wrapped_comparison(curr_level_data.price, order_price, Ordering::Equal) {
-                    // is a match on price, add to orders
+                    // is a match on price, add to orders if the level isn't full
+                    let max_orders_per_level = ORDER_LIMITS
+                        .may_load(storage)?
+                        .unwrap_or_default()
+                        .max_orders_per_level;
                     LEVEL_ORDERS.update(
                         storage,
                         val_id_current_level,
                         |elem| -> Result<_, ContractError> {
                             let mut elem = elem.unwrap();
+                            if let Some(max) = max_orders_per_level {
+                                if elem.len() >= max as usize {
+                                    return Err(ContractError::LevelFull { max: max });
+                                }
+                            }
                             elem.push(LevelOrder {
+                                order_id: order_id,
                                 user: sender.clone(),
                                 amount: order_quantity,
                             });
@@ -212,15 +288,16 @@ pub fn process_limit_maker(
 
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::{testing::mock_dependencies, Addr, Decimal, Uint128};
+    use cosmwasm_std::{testing::mock_dependencies, Addr, Decimal, Timestamp, Uint128};
 
     use crate::{
         contract_admin_execute::add_market,
-        state::MARKET_INFO,
-        structs::{CurrencyInfo, OrderSide},
+        state::{MARKET_INFO, ORDER_LIMITS},
+        structs::{CurrencyInfo, OrderLimits, OrderSide},
+        ContractError,
     };
 
-    use super::process_limit_maker;
+    use super::{add_maker_order, process_limit_maker};
 
     mod only_bids {
         use crate::{state::LEVELS_DATA, utils::create_id_level_no_status};
@@ -240,6 +317,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -250,6 +330,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
         }
@@ -267,6 +348,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -277,6 +361,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -287,6 +372,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
         }
@@ -305,6 +391,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -314,9 +403,10 @@ mod tests {
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
                 0,
-                top_price.clone(),
+                top_price,
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -327,6 +417,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -355,6 +446,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -367,6 +461,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -374,9 +469,10 @@ mod tests {
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
                 0,
-                top_price.clone(),
+                top_price,
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -404,6 +500,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -418,6 +517,7 @@ mod tests {
                 mid_price,
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -425,9 +525,10 @@ mod tests {
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
                 0,
-                top_price.clone(),
+                top_price,
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -435,9 +536,10 @@ mod tests {
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
                 0,
-                bottom_price.clone(),
+                bottom_price,
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -489,6 +591,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -499,6 +604,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
         }
@@ -516,6 +622,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -526,6 +635,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -536,6 +646,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
         }
@@ -554,6 +665,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -563,9 +677,10 @@ mod tests {
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
                 0,
-                top_price.clone(),
+                top_price,
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -576,6 +691,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -604,6 +720,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -616,6 +735,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -623,9 +743,10 @@ mod tests {
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
                 0,
-                top_price.clone(),
+                top_price,
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -653,6 +774,9 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
@@ -667,6 +791,7 @@ mod tests {
                 mid_price,
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -674,9 +799,10 @@ mod tests {
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
                 0,
-                top_price.clone(),
+                top_price,
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -684,9 +810,10 @@ mod tests {
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
                 0,
-                bottom_price.clone(),
+                bottom_price,
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -718,4 +845,57 @@ mod tests {
             assert!(bottom_level_info.id_next.is_none());
         }
     }
+
+    /// the cap on open orders of a user only counts the orders on the same market
+    #[test]
+    fn liq_provider_user_cap_per_market() {
+        let mut deps = mock_dependencies();
+        for (base, quote) in [("heur", "husd"), ("hjpy", "husd")] {
+            add_market(
+                deps.as_mut(),
+                CurrencyInfo::Native { denom: base.into() },
+                CurrencyInfo::Native {
+                    denom: quote.into(),
+                },
+                Some(6),
+                Some(6),
+                false,
+            )
+            .unwrap();
+        }
+        ORDER_LIMITS
+            .save(
+                deps.as_mut().storage,
+                &OrderLimits {
+                    max_orders_per_user: Some(1),
+                    max_orders_per_level: None,
+                },
+            )
+            .unwrap();
+
+        let user = Addr::unchecked("user");
+        for market_id in [0, 1] {
+            add_maker_order(
+                deps.as_mut().storage,
+                user.clone(),
+                market_id,
+                Decimal::one(),
+                Uint128::new(100),
+                OrderSide::Buy,
+                Timestamp::from_seconds(0),
+            )
+            .unwrap();
+        }
+        let err = add_maker_order(
+            deps.as_mut().storage,
+            user.clone(),
+            0,
+            Decimal::percent(50),
+            Uint128::new(100),
+            OrderSide::Buy,
+            Timestamp::from_seconds(0),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::TooManyUserOrders { max: 1 });
+    }
 }
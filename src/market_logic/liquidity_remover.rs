@@ -1,35 +1,33 @@
-use cosmwasm_std::{Decimal, MessageInfo, Response, Storage, Uint128};
+use cosmwasm_std::{Addr, Decimal, Order, Response, StdResult, Storage, Timestamp};
 
 use crate::{
-    state::{LEVEL_ORDERS, MARKET_INFO},
+    state::{user_orders, LEVEL_ORDERS, MARKET_INFO},
     state_utils,
-    structs::{LevelOrder, OrderSide},
+    structs::{LevelOrder, OrderSide, OrderStatus, UserOrderRecord},
     utils::create_id_level_no_status,
     ContractError,
 };
 
 pub fn remove_order(
     storage: &mut dyn Storage,
-    info: MessageInfo,
+    order_id: u64,
     market_id: u64,
     order_price: Decimal,
-    order_quantity: Uint128,
-    _order_side: OrderSide,
 ) -> Result<Response, ContractError> {
     let market_info = MARKET_INFO.load(storage, market_id)?;
 
     // get market id
     let id = create_id_level_no_status(&market_info, order_price);
 
-    // remove order that matches data. We do not allow multiple orders by the same user at the same level
+    // remove the order from the level
     let orders: Vec<LevelOrder> = LEVEL_ORDERS
         .load(storage, id)?
         .into_iter()
-        .filter(|order| order.amount != order_quantity && order.user != info.sender)
+        .filter(|order| order.order_id != order_id)
         .collect();
 
     // remove level if no more orders
-    if orders.len() == 0 {
+    if orders.is_empty() {
         state_utils::remove_level(storage, market_id, id)?;
     } else {
         // else store it back
@@ -39,14 +37,102 @@ pub fn remove_order(
     return Ok(Response::new());
 }
 
+/// Refund up to limit resting orders of a market, best levels first, to the internal balances of their owners
+/// Returns the number of orders refunded
+pub fn refund_orders(
+    storage: &mut dyn Storage,
+    market_id: u64,
+    limit: u32,
+    now: Timestamp,
+) -> Result<u32, ContractError> {
+    let mut refunded: u32 = 0;
+    while refunded < limit {
+        let market_info = MARKET_INFO.load(storage, market_id)?;
+        let (level_id, order_side) = match (market_info.top_level_bid, market_info.top_level_ask) {
+            (Some(level_id), _) => (level_id, OrderSide::Buy),
+            (None, Some(level_id)) => (level_id, OrderSide::Sell),
+            (None, None) => break,
+        };
+
+        let mut level_orders = LEVEL_ORDERS.load(storage, level_id)?;
+        let currency_info = market_info.get_currency_info_from_side(order_side.clone());
+
+        while refunded < limit {
+            let level_order = match level_orders.pop() {
+                None => break,
+                Some(level_order) => level_order,
+            };
+
+            let order = user_orders().load(storage, level_order.order_id)?;
+            state_utils::credit_balance(storage, &level_order.user, &currency_info, order.locked)?;
+            state_utils::close_order(storage, order, OrderStatus::Expired, now)?;
+            refunded += 1;
+        }
+
+        if level_orders.is_empty() {
+            state_utils::remove_level(storage, market_id, level_id)?;
+        } else {
+            LEVEL_ORDERS.save(storage, level_id, &level_orders)?;
+        }
+    }
+
+    return Ok(refunded);
+}
+
+/// Cancel up to limit open orders of a user, refunding what they lock to the internal balance of the user
+/// Returns the number of orders cancelled
+pub fn cancel_user_orders(
+    storage: &mut dyn Storage,
+    user: &Addr,
+    limit: u32,
+    now: Timestamp,
+) -> Result<u32, ContractError> {
+    let orders: Vec<UserOrderRecord> = user_orders()
+        .idx
+        .user
+        .prefix(user.clone())
+        .range(storage, None, None, Order::Ascending)
+        .take(limit as usize)
+        .map(|elem| elem.map(|(_, order)| order))
+        .collect::<StdResult<_>>()?;
+    let cancelled = orders.len() as u32;
+    for order in orders {
+        remove_order(storage, order.order_id, order.market_id, order.price)?;
+
+        let market_info = MARKET_INFO.load(storage, order.market_id)?;
+        let currency_info = market_info.get_currency_info_from_side(order.order_side.clone());
+        state_utils::credit_balance(storage, user, &currency_info, order.locked)?;
+        state_utils::close_order(storage, order, OrderStatus::Cancelled, now)?;
+    }
+
+    return Ok(cancelled);
+}
+
+pub fn has_open_orders(storage: &dyn Storage, user: &Addr) -> StdResult<bool> {
+    return Ok(user_orders()
+        .idx
+        .user
+        .prefix(user.clone())
+        .keys(storage, None, None, Order::Ascending)
+        .next()
+        .transpose()?
+        .is_some());
+}
+
+pub fn is_book_empty(storage: &dyn Storage, market_id: u64) -> Result<bool, ContractError> {
+    let market_info = MARKET_INFO.load(storage, market_id)?;
+
+    return Ok(market_info.top_level_bid.is_none() && market_info.top_level_ask.is_none());
+}
+
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::{testing::mock_dependencies, Addr, Decimal, MessageInfo, Uint128};
+    use cosmwasm_std::{testing::mock_dependencies, Addr, Decimal, Uint128};
 
     use crate::{
         contract_admin_execute::add_market,
         market_logic::liquidity_provider::process_limit_maker,
-        state::MARKET_INFO,
+        state::{LEVEL_ORDERS, MARKET_INFO},
         structs::{CurrencyInfo, OrderSide},
     };
 
@@ -67,14 +153,12 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
-            let info = MessageInfo {
-                sender: Addr::unchecked("user"),
-                funds: vec![],
-            };
-
             process_limit_maker(
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
@@ -82,6 +166,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Buy,
+                0,
             )
             .unwrap();
 
@@ -89,20 +174,54 @@ mod tests {
             let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
             assert!(market_info.top_level_bid.is_some());
 
-            remove_order(
+            remove_order(deps.as_mut().storage, 0, 0, Decimal::one()).unwrap();
+
+            // check market status, there shouldn't be anything left
+            let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
+            assert!(market_info.top_level_bid.is_none());
+        }
+    }
+
+    /// removing an order must leave the orders of other users at the same level untouched
+    #[test]
+    fn liq_remover_keeps_other_users_orders() {
+        let mut deps = mock_dependencies();
+
+        add_market(
+            deps.as_mut(),
+            CurrencyInfo::Native {
+                denom: "husd".into(),
+            },
+            CurrencyInfo::Native {
+                denom: "heur".into(),
+            },
+            Some(6),
+            Some(6),
+            false,
+        )
+        .unwrap();
+
+        for (order_id, user) in [(0, "user1"), (1, "user2")] {
+            process_limit_maker(
                 deps.as_mut().storage,
-                info,
+                Addr::unchecked(user),
                 0,
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Buy,
+                order_id,
             )
             .unwrap();
-
-            // check market status, there shouldn't be anything left
-            let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
-            assert!(market_info.top_level_bid.is_none());
         }
+
+        remove_order(deps.as_mut().storage, 0, 0, Decimal::one()).unwrap();
+
+        let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
+        let level_orders = LEVEL_ORDERS
+            .load(deps.as_ref().storage, market_info.top_level_bid.unwrap())
+            .unwrap();
+        assert_eq!(level_orders.len(), 1);
+        assert_eq!(level_orders[0].user, Addr::unchecked("user2"));
     }
 
     mod only_asks {
@@ -120,14 +239,12 @@ mod tests {
                 CurrencyInfo::Native {
                     denom: "heur".into(),
                 },
+                Some(6),
+                Some(6),
+                false,
             )
             .unwrap();
 
-            let info = MessageInfo {
-                sender: Addr::unchecked("user"),
-                funds: vec![],
-            };
-
             process_limit_maker(
                 deps.as_mut().storage,
                 Addr::unchecked("user").clone(),
@@ -135,6 +252,7 @@ mod tests {
                 Decimal::one(),
                 Uint128::new(100),
                 OrderSide::Sell,
+                0,
             )
             .unwrap();
 
@@ -142,15 +260,7 @@ mod tests {
             let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
             assert!(market_info.top_level_ask.is_some());
 
-            remove_order(
-                deps.as_mut().storage,
-                info,
-                0,
-                Decimal::one(),
-                Uint128::new(100),
-                OrderSide::Sell,
-            )
-            .unwrap();
+            remove_order(deps.as_mut().storage, 0, 0, Decimal::one()).unwrap();
 
             // check market status, there shouldn't be anything left
             let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
@@ -0,0 +1,157 @@
+use cosmwasm_std::{Decimal, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::Bound;
+
+use crate::{
+    state::{LAST_TRADES, MARKET_VOLUMES},
+    structs::{LastTrade, MarketVolume},
+};
+
+const SECONDS_PER_HOUR: u64 = 3_600;
+
+/// Volumes are bucketed per hour, the last 24 buckets make the daily volume
+const VOLUME_WINDOW_HOURS: u64 = 24;
+
+/// Index of the hour of a block
+fn hour_index(time: Timestamp) -> u64 {
+    return time.seconds() / SECONDS_PER_HOUR;
+}
+
+/// First hour counted in the window ending at the hour of now
+fn window_start(now: Timestamp) -> u64 {
+    return (hour_index(now) + 1).saturating_sub(VOLUME_WINDOW_HOURS);
+}
+
+/// Record a trade on a market: its last price, and the base and quote traded for the daily volume
+/// Buckets which left the window are dropped
+pub fn record_trade(
+    storage: &mut dyn Storage,
+    market_id: u64,
+    now: Timestamp,
+    price: Decimal,
+    base_traded: Uint128,
+    quote_traded: Uint128,
+) -> StdResult<()> {
+    if base_traded.is_zero() {
+        return Ok(());
+    }
+
+    LAST_TRADES.save(
+        storage,
+        market_id,
+        &LastTrade {
+            price: price,
+            time: now,
+        },
+    )?;
+
+    let expired_hours: Vec<u64> = MARKET_VOLUMES
+        .prefix(market_id)
+        .keys(
+            storage,
+            None,
+            Some(Bound::exclusive(window_start(now))),
+            Order::Ascending,
+        )
+        .collect::<StdResult<_>>()?;
+    for hour in expired_hours {
+        MARKET_VOLUMES.remove(storage, (market_id, hour));
+    }
+
+    MARKET_VOLUMES.update(
+        storage,
+        (market_id, hour_index(now)),
+        |volume| -> StdResult<_> {
+            let mut volume = volume.unwrap_or_default();
+            volume.base += base_traded;
+            volume.quote += quote_traded;
+
+            return Ok(volume);
+        },
+    )?;
+
+    return Ok(());
+}
+
+/// Base and quote traded on a market over the last 24 hours, counted by whole hours
+pub fn get_daily_volume(
+    storage: &dyn Storage,
+    market_id: u64,
+    now: Timestamp,
+) -> StdResult<MarketVolume> {
+    let mut total = MarketVolume::default();
+    for elem in MARKET_VOLUMES.prefix(market_id).range(
+        storage,
+        Some(Bound::inclusive(window_start(now))),
+        None,
+        Order::Ascending,
+    ) {
+        let (_, volume) = elem?;
+        total.base += volume.base;
+        total.quote += volume.quote;
+    }
+
+    return Ok(total);
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{testing::mock_dependencies, Decimal, Timestamp, Uint128};
+
+    use crate::state::{LAST_TRADES, MARKET_VOLUMES};
+
+    use super::{get_daily_volume, record_trade};
+
+    #[test]
+    fn daily_volume_rolls_by_hour() {
+        let mut deps = mock_dependencies();
+        let start = Timestamp::from_seconds(100 * 3_600);
+
+        record_trade(
+            deps.as_mut().storage,
+            0,
+            start,
+            Decimal::one(),
+            Uint128::new(10),
+            Uint128::new(10),
+        )
+        .unwrap();
+        record_trade(
+            deps.as_mut().storage,
+            0,
+            start.plus_seconds(23 * 3_600),
+            Decimal::percent(200),
+            Uint128::new(5),
+            Uint128::new(10),
+        )
+        .unwrap();
+
+        let volume =
+            get_daily_volume(deps.as_ref().storage, 0, start.plus_seconds(23 * 3_600)).unwrap();
+        assert_eq!(volume.base, Uint128::new(15));
+        assert_eq!(volume.quote, Uint128::new(20));
+
+        // the first hour left the window, and is dropped on the next trade
+        let later = start.plus_seconds(24 * 3_600);
+        let volume = get_daily_volume(deps.as_ref().storage, 0, later).unwrap();
+        assert_eq!(volume.base, Uint128::new(5));
+
+        record_trade(
+            deps.as_mut().storage,
+            0,
+            later,
+            Decimal::percent(150),
+            Uint128::new(2),
+            Uint128::new(3),
+        )
+        .unwrap();
+        assert!(!MARKET_VOLUMES.has(deps.as_ref().storage, (0, 100)));
+        assert_eq!(
+            LAST_TRADES.load(deps.as_ref().storage, 0).unwrap().price,
+            Decimal::percent(150)
+        );
+
+        // other markets are counted on their own
+        let volume = get_daily_volume(deps.as_ref().storage, 1, later).unwrap();
+        assert!(volume.base.is_zero());
+    }
+}
@@ -1,6 +1,9 @@
+pub mod fee_discounts;
+pub mod fee_tiers;
 pub mod liquidity_consumer;
 pub mod liquidity_provider;
 pub mod liquidity_remover;
+pub mod market_stats;
 
 /*
 fn compute_price(price: Uint128, consumption_results: &Vec<ConsumptionResult>) {
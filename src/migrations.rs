@@ -0,0 +1,500 @@
+use cosmwasm_std::{Addr, DepsMut, Env, Order, Response, StdResult, Uint128};
+use cw2::{get_contract_version, set_contract_version};
+use semver::Version;
+
+use crate::{
+    contract_admin_execute::get_currency_decimals,
+    msg::MigrateMsg,
+    state::{user_orders, LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO, MARKET_PAIRS, ORDER_ID_TRACKER},
+    state_utils,
+    structs::{CurrencyInfo, LevelOrder, MarketInfo, MarketStatus, OrderSide, UserOrderRecord},
+    utils::compute_escrow,
+    ContractError,
+};
+
+/// Name the contract was instantiated with before it was renamed
+const LEGACY_CONTRACT_NAME: &str = "AzoyaLabs:ContractTemplate";
+
+type MigrationStep = fn(DepsMut, &Env, &MigrateMsg) -> Result<(), ContractError>;
+
+/// Storage migrations, in order, with the version that introduced them
+/// a step runs when migrating from a version older than its own
+const MIGRATIONS: &[(&str, MigrationStep)] = &[
+    ("0.2.0", migrate_markets),
+    ("0.2.0", migrate_user_orders_to_indexed_map),
+    ("0.2.0", migrate_level_orders),
+];
+
+/// Run the migrations needed to go from the stored version to the current one
+/// Downgrades are refused, as older code can't read newer storage layouts
+pub fn migrate(
+    mut deps: DepsMut,
+    env: Env,
+    msg: MigrateMsg,
+    contract_name: &str,
+    contract_version: &str,
+) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != contract_name && stored.contract != LEGACY_CONTRACT_NAME {
+        return Err(ContractError::InvalidContractName {
+            name: stored.contract,
+        });
+    }
+
+    let stored_version = parse_version(&stored.version)?;
+    let current_version = parse_version(contract_version)?;
+    if stored_version > current_version {
+        return Err(ContractError::CannotDowngrade {
+            stored: stored.version,
+            current: contract_version.to_string(),
+        });
+    }
+
+    for (step_version, step) in MIGRATIONS {
+        if stored_version < parse_version(step_version)? {
+            step(deps.branch(), &env, &msg)?;
+        }
+    }
+
+    set_contract_version(deps.storage, contract_name, contract_version)?;
+
+    return Ok(Response::new()
+        .add_attribute("previous_version", stored.version)
+        .add_attribute("version", contract_version));
+}
+
+fn parse_version(version: &str) -> Result<Version, ContractError> {
+    return Version::parse(version).map_err(|_| ContractError::InvalidContractVersion {
+        version: version.to_string(),
+    });
+}
+
+/// Layouts replaced by migrations, kept to read what was stored with them
+mod legacy {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Addr, Decimal, Uint128};
+    use cw_storage_plus::Map;
+
+    use crate::structs::{CurrencyInfo, OrderSide};
+
+    #[cw_serde]
+    pub struct MarketInfo {
+        pub market_id: u64,
+        pub base_currency: CurrencyInfo,
+        pub quote_currency: CurrencyInfo,
+        pub top_level_bid: Option<u64>,
+        pub top_level_ask: Option<u64>,
+    }
+
+    /// Up to 0.1.0, markets had no decimals, fees or status
+    pub const MARKET_INFO: Map<u64, MarketInfo> = Map::new("market_info");
+
+    #[cw_serde]
+    pub struct UserOrderRecord {
+        pub market_id: u64,
+        pub order_side: OrderSide,
+        pub price: Decimal,
+        pub quantity: Uint128,
+    }
+
+    /// Up to 0.1.0, all the orders of a user were stored in a single list
+    pub const USER_ORDERS: Map<Addr, Vec<UserOrderRecord>> = Map::new("user_orders");
+
+    #[cw_serde]
+    pub struct LevelOrder {
+        pub user: Addr,
+        pub amount: Uint128,
+    }
+
+    /// Up to 0.1.0, orders of a level only recorded their user
+    pub const LEVEL_ORDERS: Map<u64, Vec<LevelOrder>> = Map::new("level_orders");
+}
+
+/// Add decimals, fees and status to markets, and index them by currency pair
+/// decimals of native currencies can't be queried, so they are given in the migrate message
+fn migrate_markets(deps: DepsMut, _env: &Env, msg: &MigrateMsg) -> Result<(), ContractError> {
+    let legacy_markets: Vec<(u64, legacy::MarketInfo)> = legacy::MARKET_INFO
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    let native_decimals = msg.native_decimals.clone().unwrap_or_default();
+    let currency_decimals = |currency: &CurrencyInfo| {
+        let decimals = match currency {
+            CurrencyInfo::Native { denom } => native_decimals
+                .iter()
+                .find(|native| native.denom == *denom)
+                .map(|native| native.decimals),
+            CurrencyInfo::Cw20 { .. } => None,
+        };
+        return get_currency_decimals(deps.as_ref(), currency, decimals);
+    };
+
+    let mut markets = vec![];
+    for (market_id, market) in legacy_markets {
+        markets.push(MarketInfo {
+            market_id: market_id,
+            base_decimals: currency_decimals(&market.base_currency)?,
+            quote_decimals: currency_decimals(&market.quote_currency)?,
+            base_currency: market.base_currency,
+            quote_currency: market.quote_currency,
+            maker_fee_bps: 0,
+            taker_fee_bps: 0,
+            status: MarketStatus::Active,
+            permissioned: false,
+            tick_size: None,
+            lot_size: None,
+            top_level_bid: market.top_level_bid,
+            top_level_ask: market.top_level_ask,
+        });
+    }
+
+    for market in markets {
+        MARKET_PAIRS.save(
+            deps.storage,
+            (
+                market.base_currency.get_denom(),
+                market.quote_currency.get_denom(),
+            ),
+            &market.market_id,
+        )?;
+        MARKET_INFO.save(deps.storage, market.market_id, &market)?;
+    }
+
+    return Ok(());
+}
+
+/// Give every open order an id and store it on its own, indexed by user
+/// orders lock the escrow of what is left of them, which is what they were funded with
+/// fills before the migration are unknown, so orders count as created at the migration with what is left of them
+fn migrate_user_orders_to_indexed_map(
+    deps: DepsMut,
+    env: &Env,
+    _msg: &MigrateMsg,
+) -> Result<(), ContractError> {
+    let legacy_orders: Vec<(Addr, Vec<legacy::UserOrderRecord>)> = legacy::USER_ORDERS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()?;
+
+    let mut order_id = ORDER_ID_TRACKER.may_load(deps.storage)?.unwrap_or_default();
+    for (user, orders) in legacy_orders {
+        for order in orders {
+            user_orders().save(
+                deps.storage,
+                order_id,
+                &UserOrderRecord {
+                    order_id: order_id,
+                    user: user.clone(),
+                    market_id: order.market_id,
+                    locked: compute_escrow(order.quantity, order.price, &order.order_side),
+                    order_side: order.order_side,
+                    price: order.price,
+                    quantity: order.quantity,
+                    original_quantity: order.quantity,
+                    filled_quantity: Uint128::zero(),
+                    filled_notional: Uint128::zero(),
+                    created_at: env.block.time,
+                },
+            )?;
+            order_id += 1;
+        }
+        legacy::USER_ORDERS.remove(deps.storage, user);
+    }
+    ORDER_ID_TRACKER.save(deps.storage, &order_id)?;
+
+    return Ok(());
+}
+
+/// Record the id of each order resting in the book, so fills load orders directly
+/// a user had at most one order per level, which is matched by market, price and side
+fn migrate_level_orders(deps: DepsMut, _env: &Env, _msg: &MigrateMsg) -> Result<(), ContractError> {
+    let markets: Vec<MarketInfo> = MARKET_INFO
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|elem| elem.map(|(_, market)| market))
+        .collect::<StdResult<_>>()?;
+
+    for market in markets {
+        for (top_level, order_side) in [
+            (market.top_level_bid, OrderSide::Buy),
+            (market.top_level_ask, OrderSide::Sell),
+        ] {
+            let mut id_current_level = top_level;
+            while let Some(level_id) = id_current_level {
+                let level_data = LEVELS_DATA.load(deps.storage, level_id)?;
+
+                let mut level_orders = vec![];
+                for level_order in legacy::LEVEL_ORDERS.load(deps.storage, level_id)? {
+                    let order = state_utils::load_user_orders(deps.storage, &level_order.user)?
+                        .into_iter()
+                        .find(|order| {
+                            order.market_id == market.market_id
+                                && order.price == level_data.price
+                                && order.order_side == order_side
+                        })
+                        .ok_or(ContractError::OrderDoesNotExist {})?;
+                    level_orders.push(LevelOrder {
+                        order_id: order.order_id,
+                        user: level_order.user,
+                        amount: level_order.amount,
+                    });
+                }
+                LEVEL_ORDERS.save(deps.storage, level_id, &level_orders)?;
+
+                id_current_level = level_data.id_next;
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{
+        testing::{mock_dependencies, mock_env},
+        Addr, Decimal, Uint128,
+    };
+    use cw2::{get_contract_version, set_contract_version};
+
+    use crate::{
+        msg::{MigrateMsg, NativeDecimals},
+        state::{LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO, MARKET_PAIRS, ORDER_ID_TRACKER},
+        state_utils::load_user_orders,
+        structs::{CurrencyInfo, LevelData, LevelOrder, MarketStatus, OrderSide, UserOrderRecord},
+        ContractError,
+    };
+
+    use super::{legacy, migrate, LEGACY_CONTRACT_NAME};
+
+    const CONTRACT_NAME: &str = "AzoyaLabs:SeleneMarkets";
+
+    fn native(denom: &str) -> CurrencyInfo {
+        return CurrencyInfo::Native {
+            denom: denom.into(),
+        };
+    }
+
+    #[test]
+    fn migrate_from_legacy_layout() {
+        let mut deps = mock_dependencies();
+        set_contract_version(deps.as_mut().storage, LEGACY_CONTRACT_NAME, "0.1.0").unwrap();
+
+        legacy::MARKET_INFO
+            .save(
+                deps.as_mut().storage,
+                0,
+                &legacy::MarketInfo {
+                    market_id: 0,
+                    base_currency: native("heur"),
+                    quote_currency: native("husd"),
+                    top_level_bid: Some(7),
+                    top_level_ask: None,
+                },
+            )
+            .unwrap();
+
+        let user_1 = Addr::unchecked("user1");
+        let user_2 = Addr::unchecked("user2");
+        let price = Decimal::from_atomics(Uint128::new(7), 1).unwrap();
+        let lower_price = Decimal::from_atomics(Uint128::new(6), 1).unwrap();
+        let legacy_order = |price: Decimal, quantity: u128| legacy::UserOrderRecord {
+            market_id: 0,
+            order_side: OrderSide::Buy,
+            price: price,
+            quantity: Uint128::new(quantity),
+        };
+        legacy::USER_ORDERS
+            .save(
+                deps.as_mut().storage,
+                user_1.clone(),
+                &vec![legacy_order(price, 10), legacy_order(lower_price, 143)],
+            )
+            .unwrap();
+        legacy::USER_ORDERS
+            .save(
+                deps.as_mut().storage,
+                user_2.clone(),
+                &vec![legacy_order(price, 30)],
+            )
+            .unwrap();
+
+        // bids at 0.7 then 0.6
+        let legacy_level_order = |user: &Addr, amount: u128| legacy::LevelOrder {
+            user: user.clone(),
+            amount: Uint128::new(amount),
+        };
+        for (level_id, level_data, level_orders) in [
+            (
+                7,
+                LevelData {
+                    id_previous: None,
+                    id_next: Some(8),
+                    price: price,
+                },
+                vec![
+                    legacy_level_order(&user_1, 10),
+                    legacy_level_order(&user_2, 30),
+                ],
+            ),
+            (
+                8,
+                LevelData {
+                    id_previous: Some(7),
+                    id_next: None,
+                    price: lower_price,
+                },
+                vec![legacy_level_order(&user_1, 143)],
+            ),
+        ] {
+            LEVELS_DATA
+                .save(deps.as_mut().storage, level_id, &level_data)
+                .unwrap();
+            legacy::LEVEL_ORDERS
+                .save(deps.as_mut().storage, level_id, &level_orders)
+                .unwrap();
+        }
+
+        // decimals of native currencies are required
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            MigrateMsg {
+                native_decimals: None,
+            },
+            CONTRACT_NAME,
+            "0.2.0",
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::MissingDecimals {});
+
+        let msg = MigrateMsg {
+            native_decimals: Some(vec![
+                NativeDecimals {
+                    denom: "heur".into(),
+                    decimals: 6,
+                },
+                NativeDecimals {
+                    denom: "husd".into(),
+                    decimals: 8,
+                },
+            ]),
+        };
+        let res = migrate(deps.as_mut(), mock_env(), msg, CONTRACT_NAME, "0.2.0").unwrap();
+        assert_eq!(res.attributes[0].value, "0.1.0");
+
+        let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
+        assert_eq!(market_info.base_decimals, 6);
+        assert_eq!(market_info.quote_decimals, 8);
+        assert_eq!(market_info.status, MarketStatus::Active);
+        assert_eq!(market_info.top_level_bid, Some(7));
+        assert_eq!(
+            MARKET_PAIRS
+                .load(deps.as_ref().storage, ("heur".into(), "husd".into()))
+                .unwrap(),
+            0
+        );
+
+        // bids lock the quote value of their quantity, rounded up
+        let orders = load_user_orders(deps.as_ref().storage, &user_1).unwrap();
+        assert_eq!(
+            orders,
+            vec![
+                UserOrderRecord {
+                    order_id: 0,
+                    user: user_1.clone(),
+                    market_id: 0,
+                    order_side: OrderSide::Buy,
+                    price: price,
+                    quantity: Uint128::new(10),
+                    locked: Uint128::new(7),
+                    original_quantity: Uint128::new(10),
+                    filled_quantity: Uint128::zero(),
+                    filled_notional: Uint128::zero(),
+                    created_at: mock_env().block.time,
+                },
+                UserOrderRecord {
+                    order_id: 1,
+                    user: user_1.clone(),
+                    market_id: 0,
+                    order_side: OrderSide::Buy,
+                    price: lower_price,
+                    quantity: Uint128::new(143),
+                    locked: Uint128::new(86),
+                    original_quantity: Uint128::new(143),
+                    filled_quantity: Uint128::zero(),
+                    filled_notional: Uint128::zero(),
+                    created_at: mock_env().block.time,
+                },
+            ]
+        );
+        let orders = load_user_orders(deps.as_ref().storage, &user_2).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].order_id, 2);
+
+        assert_eq!(ORDER_ID_TRACKER.load(deps.as_ref().storage).unwrap(), 3);
+        assert!(legacy::USER_ORDERS.is_empty(deps.as_ref().storage));
+
+        // orders resting in the book now point to their ids
+        let level_order = |order_id: u64, user: &Addr, amount: u128| LevelOrder {
+            order_id: order_id,
+            user: user.clone(),
+            amount: Uint128::new(amount),
+        };
+        assert_eq!(
+            LEVEL_ORDERS.load(deps.as_ref().storage, 7).unwrap(),
+            vec![level_order(0, &user_1, 10), level_order(2, &user_2, 30)]
+        );
+        assert_eq!(
+            LEVEL_ORDERS.load(deps.as_ref().storage, 8).unwrap(),
+            vec![level_order(1, &user_1, 143)]
+        );
+
+        let version = get_contract_version(deps.as_ref().storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+        assert_eq!(version.version, "0.2.0");
+    }
+
+    #[test]
+    fn migrate_refuses_downgrade_and_other_contracts() {
+        let mut deps = mock_dependencies();
+        let msg = MigrateMsg {
+            native_decimals: None,
+        };
+
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.3.0").unwrap();
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            msg.clone(),
+            CONTRACT_NAME,
+            "0.2.0",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CannotDowngrade {
+                stored: "0.3.0".into(),
+                current: "0.2.0".into(),
+            }
+        );
+
+        set_contract_version(deps.as_mut().storage, "crates.io:cw20-base", "0.1.0").unwrap();
+        let err = migrate(
+            deps.as_mut(),
+            mock_env(),
+            msg.clone(),
+            CONTRACT_NAME,
+            "0.2.0",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::InvalidContractName {
+                name: "crates.io:cw20-base".into(),
+            }
+        );
+
+        // migrating to the same version runs no step
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.2.0").unwrap();
+        migrate(deps.as_mut(), mock_env(), msg, CONTRACT_NAME, "0.2.0").unwrap();
+    }
+}
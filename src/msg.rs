@@ -1,28 +1,175 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Decimal};
+use cosmwasm_std::{Addr, Coin, Decimal, Timestamp, Uint128};
 use cw20::Cw20ReceiveMsg;
 
-use crate::structs::{BookLevel, CurrencyInfo, SingleMarketInfo, UserOrderRecord};
+use crate::structs::{
+    BookLevel, CurrencyInfo, DepthBucket, FeeDiscount, FeeDiscountConfig, FeeTier, FeeTierConfig,
+    ListingFee, MarketParams, MarketStatus, MarketSummary, OrderLimits, OrderSide, OrderStatus,
+    PendingAdmin, Role, SingleMarketInfo, Unbonding, UserOrderInfo, UserOrderRecord, VolumeScope,
+};
 
 #[cw_serde]
-pub struct InstantiateMsg {}
+pub struct InstantiateMsg {
+    /// defaults to the sender
+    pub admin: Option<String>,
+    /// default recipient of protocol fee withdrawals
+    pub fee_collector: Option<String>,
+    /// parameters given to new markets
+    pub default_market_params: Option<MarketParams>,
+    /// markets added on instantiation, with the default parameters
+    pub markets: Option<Vec<InitialMarket>>,
+    /// no cap by default
+    pub order_limits: Option<OrderLimits>,
+}
+
+#[cw_serde]
+pub struct InitialMarket {
+    pub base_currency: CurrencyInfo,
+    pub quote_currency: CurrencyInfo,
+    pub base_decimals: Option<u8>,
+    pub quote_decimals: Option<u8>,
+    pub allow_inverse: Option<bool>,
+}
 
 #[cw_serde]
 pub enum AdminExecuteMsg {
-    UpdateAdmin {
+    /// propose a new admin, which must accept before it can act
+    /// the proposal can expire, and replaces any previous one
+    ProposeAdmin {
         new_admin: String,
+        expires_at: Option<Timestamp>,
+    },
+    CancelAdminProposal {},
+    /// remove the admin for good, along with all roles, nothing can be changed by an admin afterwards
+    /// a fee collector must be set, protocol fees can then be withdrawn to it by anyone
+    RenounceAdmin {},
+    GrantRole {
+        role: Role,
+        address: String,
     },
+    RevokeRole {
+        role: Role,
+        address: String,
+    },
+    /// decimals are required for native currencies, they are read from the token for cw20
     AddMarket {
         base_currency: CurrencyInfo,
         quote_currency: CurrencyInfo,
+        base_decimals: Option<u8>,
+        quote_decimals: Option<u8>,
+        /// list the pair even if its inverse is already listed, false by default
+        allow_inverse: Option<bool>,
+    },
+    /// fees in basis points, charged on what makers and takers receive
+    /// a negative maker fee is a rebate, which the taker fee must cover
+    UpdateMarketFees {
+        market_id: u64,
+        maker_fee_bps: i16,
+        taker_fee_bps: u16,
+    },
+    /// move a market to another status, delisting can't be undone
+    UpdateMarketStatus {
+        market_id: u64,
+        status: MarketStatus,
+    },
+    /// restrict a market to its allowlist, or open it to everyone
+    UpdateMarketPermissioned {
+        market_id: u64,
+        permissioned: bool,
+    },
+    UpdateMarketAllowlist {
+        market_id: u64,
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// blocklisted addresses can't place orders on any market
+    UpdateBlocklist {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// cancel up to limit resting orders of a blocklisted address, crediting what they lock to its internal balance
+    CancelBlocklistedOrders {
+        address: String,
+        limit: Option<u32>,
+    },
+    /// refund up to limit resting orders of a delisted market to the internal balances of their owners
+    RefundDelistedOrders {
+        market_id: u64,
+        limit: Option<u32>,
+    },
+    /// send all the protocol fees accrued in currency to recipient, the fee collector by default
+    WithdrawFees {
+        currency: CurrencyInfo,
+        recipient: Option<String>,
+    },
+    UpdateFeeCollector {
+        fee_collector: Option<String>,
+    },
+    /// set the parameters given to new markets
+    UpdateDefaultMarketParams {
+        params: MarketParams,
+    },
+    /// limit prices must be a multiple of tick_size, resting orders are rounded down to a multiple of lot_size
+    UpdateMarketSizes {
+        market_id: u64,
+        tick_size: Option<Decimal>,
+        lot_size: Option<Uint128>,
+    },
+    /// correct the decimals of a market, required for native currencies and read from the token for cw20
+    UpdateMarketDecimals {
+        market_id: u64,
+        base_decimals: Option<u8>,
+        quote_decimals: Option<u8>,
+    },
+    /// set the volume based fee tiers, tiers must be sorted by increasing min_volume
+    /// volume is counted over the last window_days days
+    UpdateFeeTiers {
+        window_days: u64,
+        scope: VolumeScope,
+        tiers: Vec<FeeTier>,
+    },
+    /// share of the taker fees of referred users paid to their referrer, in basis points
+    UpdateReferralShare {
+        share_bps: u16,
+    },
+    /// designate the cw20 token locked for taker fee discounts, and the discounts given
+    /// discounts must be sorted by increasing min_locked
+    UpdateFeeDiscounts {
+        token: String,
+        unbonding_seconds: u64,
+        discounts: Vec<FeeDiscount>,
+    },
+    /// set the fee paid by anyone creating a market, none to only let the admin add markets
+    UpdateListingFee {
+        listing_fee: Option<ListingFee>,
+    },
+    /// set the caps on open orders per user on a market and per price level, none for no cap
+    UpdateOrderLimits {
+        order_limits: OrderLimits,
     },
 }
 
 /// messages to be used in a cw20::send message
 #[cw_serde]
 pub enum SeleneCw20Msg {
-    LimitOrder { market_id: u64, price: Decimal },
-    MarketOrder { market_id: u64 },
+    LimitOrder {
+        market_id: u64,
+        price: Decimal,
+    },
+    MarketOrder {
+        market_id: u64,
+    },
+    /// credit the sent tokens to the internal balance of the sender
+    Deposit {},
+    /// lock fee tokens for taker fee discounts
+    LockFeeToken {},
+    /// create a market of two cw20 tokens, paying the listing fee with the sent tokens
+    CreateMarket {
+        base_currency: CurrencyInfo,
+        quote_currency: CurrencyInfo,
+        base_decimals: Option<u8>,
+        quote_decimals: Option<u8>,
+    },
 }
 
 #[cw_serde]
@@ -41,12 +188,59 @@ pub enum ExecuteMsg {
         market_id: u64,
         price: Decimal,
     },
+    /// credit the sent native coins to the internal balance of the sender
+    Deposit {},
+    /// send back funds from the internal balance of the sender
+    Withdraw {
+        currency: CurrencyInfo,
+        amount: Uint128,
+    },
+    /// place an order funded from the internal balance of the sender
+    /// a limit order if a price is given, a market order otherwise
+    PlaceOrderFromBalance {
+        market_id: u64,
+        currency: CurrencyInfo,
+        amount: Uint128,
+        price: Option<Decimal>,
+    },
+    /// set the referrer of the sender, which can only be done once
+    RegisterReferrer {
+        referrer: String,
+    },
+    /// send the referral rewards of the sender accrued in currency
+    ClaimReferralRewards {
+        currency: CurrencyInfo,
+    },
+    /// become admin, by the address proposed by the current admin
+    AcceptAdmin {},
+    /// create a market of two cw20 tokens, paying the listing fee with the sent native coins
+    CreateMarket {
+        base_currency: CurrencyInfo,
+        quote_currency: CurrencyInfo,
+        base_decimals: Option<u8>,
+        quote_decimals: Option<u8>,
+    },
+    /// start unbonding locked fee tokens, they stop counting for discounts right away
+    UnlockFeeToken {
+        amount: Uint128,
+    },
+    /// send the fee tokens which finished unbonding
+    ClaimUnlockedFeeToken {},
 
     Admin(AdminExecuteMsg),
 }
 
 #[cw_serde]
-pub struct MigrateMsg {}
+pub struct MigrateMsg {
+    /// decimals of the native currencies of existing markets, when migrating from 0.1.0
+    pub native_decimals: Option<Vec<NativeDecimals>>,
+}
+
+#[cw_serde]
+pub struct NativeDecimals {
+    pub denom: String,
+    pub decimals: u8,
+}
 
 #[cw_serde]
 #[derive(QueryResponses)]
@@ -54,29 +248,131 @@ pub enum QueryMsg {
     #[returns(GetAdminResponse)]
     GetAdmin {},
 
+    #[returns(GetRoleMembersResponse)]
+    GetRoleMembers { role: Role },
+
+    #[returns(GetConfigResponse)]
+    GetConfig {},
+
+    /// markets by increasing id, after the market start_after
     #[returns(GetMarketsResponse)]
-    GetMarkets {},
+    GetMarkets {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
 
+    /// orders by increasing order id, after the order start_after
     #[returns(GetUserBidsResponse)]
     GetUserBids {
         user_address: Addr,
         target_market: Option<u64>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
     },
 
     #[returns(GetUserAsksResponse)]
     GetUserAsks {
         user_address: Addr,
         target_market: Option<u64>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
     },
 
     #[returns(GetUserOrdersResponse)]
     GetUserOrders {
         user_address: Addr,
         target_market: Option<u64>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    #[returns(GetMarketSummaryResponse)]
+    GetMarketSummary { market_id: u64 },
+
+    /// summaries of markets by increasing id, after the market start_after
+    #[returns(GetMarketSummariesResponse)]
+    GetMarketSummaries {
+        start_after: Option<u64>,
+        limit: Option<u32>,
     },
 
+    /// quote of a market order, walking the book as the order would without executing it
+    /// fees are those of user_address if given, else the taker fee of the market
+    #[returns(SimulateMarketOrderResponse)]
+    SimulateMarketOrder {
+        market_id: u64,
+        offer_currency: String,
+        offer_amount: Uint128,
+        user_address: Option<Addr>,
+    },
+
+    /// smallest market order offering the other currency of the market to receive ask_amount of ask_currency after fees
+    /// fees are those of user_address if given, else the taker fee of the market
+    #[returns(SimulateReverseResponse)]
+    SimulateReverse {
+        market_id: u64,
+        ask_currency: String,
+        ask_amount: Uint128,
+        user_address: Option<Addr>,
+    },
+
+    /// open orders, and closed orders during their retention window
+    #[returns(GetOrderResponse)]
+    GetOrder { order_id: u64 },
+
     #[returns(GetMarketBookResponse)]
     GetMarketBook { market_id: u64, nb_levels: u32 },
+
+    /// levels grouped by multiples of bucket_size, at most max_buckets per side
+    #[returns(GetDepthResponse)]
+    GetDepth {
+        market_id: u64,
+        bucket_size: Decimal,
+        max_buckets: u32,
+    },
+
+    #[returns(GetUserBalancesResponse)]
+    GetUserBalances { user_address: Addr },
+
+    /// Amounts the contract owes, per denom: funds locked in open orders and internal balances
+    /// The contract holdings should always be equal to the total
+    /// Unbounded, meant to be queried off-chain
+    #[returns(GetEscrowTotalsResponse)]
+    GetEscrowTotals {},
+
+    #[returns(GetProtocolFeesResponse)]
+    GetProtocolFees {},
+
+    #[returns(GetFeeTiersResponse)]
+    GetFeeTiers {},
+
+    /// Volume of a user over the window, and the fees it currently pays on a market
+    #[returns(GetUserFeeTierResponse)]
+    GetUserFeeTier { user_address: Addr, market_id: u64 },
+
+    #[returns(GetReferralShareResponse)]
+    GetReferralShare {},
+
+    #[returns(GetReferrerResponse)]
+    GetReferrer { user_address: Addr },
+
+    /// Rewards of a referrer which can be claimed, and all rewards earned since the start
+    #[returns(GetReferralEarningsResponse)]
+    GetReferralEarnings { referrer: Addr },
+
+    #[returns(GetListingFeeResponse)]
+    GetListingFee {},
+
+    #[returns(GetFeeDiscountsResponse)]
+    GetFeeDiscounts {},
+
+    /// Fee tokens locked and unbonding for a user, and the discount it gets
+    #[returns(GetUserFeeTokenLockResponse)]
+    GetUserFeeTokenLock { user_address: Addr },
+
+    /// whether the user can place orders on the market, given its allowlist and the blocklist
+    #[returns(GetUserTradingAccessResponse)]
+    GetUserTradingAccess { user_address: Addr, market_id: u64 },
 }
 
 #[cw_serde]
@@ -85,27 +381,192 @@ pub struct GetMarketBookResponse {
     pub asks: Vec<BookLevel>,
 }
 
+#[cw_serde]
+pub struct GetDepthResponse {
+    pub bids: Vec<DepthBucket>,
+    pub asks: Vec<DepthBucket>,
+}
+
 #[cw_serde]
 pub struct GetMarketsResponse {
     pub markets: Vec<SingleMarketInfo>,
+    /// start_after of the next page, none on the last page
+    pub next_start_after: Option<u64>,
 }
 
 #[cw_serde]
 pub struct GetUserOrdersResponse {
-    pub orders: Vec<UserOrderRecord>,
+    pub orders: Vec<UserOrderInfo>,
+    pub next_start_after: Option<u64>,
+}
+
+#[cw_serde]
+pub struct GetMarketSummaryResponse {
+    pub summary: MarketSummary,
+}
+
+#[cw_serde]
+pub struct GetMarketSummariesResponse {
+    pub summaries: Vec<MarketSummary>,
+    pub next_start_after: Option<u64>,
+}
+
+#[cw_serde]
+pub struct SimulateMarketOrderResponse {
+    pub order_side: OrderSide,
+    /// part of the offer which would be traded, the rest is refunded
+    pub offer_used: Uint128,
+    pub refund_amount: Uint128,
+    /// received by the trader, net of the taker fee
+    pub return_amount: Uint128,
+    pub fee_amount: Uint128,
+    pub taker_fee_bps: u16,
+    /// prices in quote currency per base currency, none if nothing would be traded
+    pub average_price: Option<Decimal>,
+    pub worst_price: Option<Decimal>,
+    pub levels_consumed: u32,
+    /// the book can fill the whole offer, else the order would fail
+    pub enough_liquidity: bool,
+}
+
+#[cw_serde]
+pub struct SimulateReverseResponse {
+    pub order_side: OrderSide,
+    /// smallest offer to receive the amount asked, or the offer taking the whole side of the book
+    pub offer_amount: Uint128,
+    /// received by the trader, net of the taker fee, at least the amount asked
+    pub return_amount: Uint128,
+    pub fee_amount: Uint128,
+    pub taker_fee_bps: u16,
+    pub average_price: Option<Decimal>,
+    pub worst_price: Option<Decimal>,
+    /// distance of the average price from the top of book, in basis points
+    pub price_impact_bps: Option<Uint128>,
+    /// the book holds enough to receive the amount asked
+    pub enough_liquidity: bool,
+}
+
+#[cw_serde]
+pub struct GetOrderResponse {
+    pub order_id: u64,
+    pub market_id: u64,
+    pub order_side: OrderSide,
+    pub owner: Addr,
+    pub price: Decimal,
+    /// quantities of base currency
+    pub original_quantity: Uint128,
+    pub remaining_quantity: Uint128,
+    pub filled_quantity: Uint128,
+    /// none until the order is filled
+    pub average_fill_price: Option<Decimal>,
+    pub created_at: Timestamp,
+    pub status: OrderStatus,
+    pub closed_at: Option<Timestamp>,
 }
 
 #[cw_serde]
 pub struct GetUserBidsResponse {
     pub orders: Vec<UserOrderRecord>,
+    pub next_start_after: Option<u64>,
 }
 
 #[cw_serde]
 pub struct GetUserAsksResponse {
     pub orders: Vec<UserOrderRecord>,
+    pub next_start_after: Option<u64>,
+}
+
+#[cw_serde]
+pub struct GetConfigResponse {
+    pub fee_collector: Option<Addr>,
+    pub default_market_params: MarketParams,
+    pub order_limits: OrderLimits,
+}
+
+#[cw_serde]
+pub struct GetRoleMembersResponse {
+    pub role: Role,
+    pub members: Vec<Addr>,
 }
 
 #[cw_serde]
 pub struct GetAdminResponse {
     pub admin: Option<Addr>,
+    pub pending_admin: Option<PendingAdmin>,
+}
+
+#[cw_serde]
+pub struct GetUserBalancesResponse {
+    pub balances: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct GetEscrowTotalsResponse {
+    pub orders: Vec<Coin>,
+    pub balances: Vec<Coin>,
+    pub fees: Vec<Coin>,
+    pub referral_rewards: Vec<Coin>,
+    pub fee_token_locks: Vec<Coin>,
+    pub total: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct GetProtocolFeesResponse {
+    pub fees: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct GetFeeTiersResponse {
+    pub config: FeeTierConfig,
+}
+
+#[cw_serde]
+pub struct GetUserFeeTierResponse {
+    /// index of the tier in the tier table, none if below the first tier
+    pub tier: Option<u32>,
+    pub volume: Uint128,
+    pub window_days: u64,
+    pub maker_fee_bps: i16,
+    pub taker_fee_bps: u16,
+    pub taker_discount_bps: u16,
+}
+
+#[cw_serde]
+pub struct GetReferralShareResponse {
+    pub share_bps: u16,
+}
+
+#[cw_serde]
+pub struct GetReferrerResponse {
+    pub referrer: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct GetReferralEarningsResponse {
+    pub pending: Vec<Coin>,
+    pub earned: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct GetFeeDiscountsResponse {
+    pub config: Option<FeeDiscountConfig>,
+}
+
+#[cw_serde]
+pub struct GetUserFeeTokenLockResponse {
+    pub locked: Uint128,
+    pub unbonding: Vec<Unbonding>,
+    pub taker_discount_bps: u16,
+}
+
+#[cw_serde]
+pub struct GetListingFeeResponse {
+    pub listing_fee: Option<ListingFee>,
+}
+
+#[cw_serde]
+pub struct GetUserTradingAccessResponse {
+    pub blocklisted: bool,
+    pub allowlisted: bool,
+    pub can_trade: bool,
 }
@@ -1,16 +1,43 @@
-use cosmwasm_std::Addr;
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{Addr, Empty, Uint128};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 
-use crate::structs::{LevelData, LevelOrders, MarketInfo, UserOrderRecord};
+use crate::structs::{
+    ClosedOrder, FeeDiscountConfig, FeeTierConfig, LastTrade, LevelData, LevelOrders, ListingFee,
+    MarketInfo, MarketParams, MarketVolume, OrderLimits, PendingAdmin, Unbonding, UserOrderRecord,
+};
 
 pub const ADMIN: Item<Addr> = Item::new("admin");
 
+/// Admin proposed by the current admin, which must accept to become admin
+pub const PENDING_ADMIN: Item<PendingAdmin> = Item::new("pending_admin");
+
+/// Holders of each role, keyed by role and address
+pub const ROLE_MEMBERS: Map<(String, Addr), Empty> = Map::new("role_members");
+
+/// Traders allowed on permissioned markets, keyed by market id and address
+pub const MARKET_ALLOWLIST: Map<(u64, Addr), Empty> = Map::new("market_allowlist");
+
+/// Addresses which can't place orders on any market
+pub const BLOCKLIST: Map<Addr, Empty> = Map::new("blocklist");
+
+/// Default recipient of protocol fee withdrawals
+pub const FEE_COLLECTOR: Item<Addr> = Item::new("fee_collector");
+
+/// Parameters given to new markets
+pub const DEFAULT_MARKET_PARAMS: Item<MarketParams> = Item::new("default_market_params");
+
+/// Caps on the number of open orders
+pub const ORDER_LIMITS: Item<OrderLimits> = Item::new("order_limits");
+
 /// Allocate ID to new markets  
 pub const MARKET_ID_TRACKER: Item<u64> = Item::new("market_id_tracker");
 
 /// Map market id to market info
 pub const MARKET_INFO: Map<u64, MarketInfo> = Map::new("market_info");
 
+/// Index of markets, map (base denom, quote denom) to market id
+pub const MARKET_PAIRS: Map<(String, String), u64> = Map::new("market_pairs");
+
 //pub const MARKET_ORDERS: Map<(u64, OrderSide), >
 
 /// Map level id to info about level (price and linked nodes as market is doubly linked list)
@@ -19,8 +46,81 @@ pub const LEVELS_DATA: Map<u64, LevelData> = Map::new("levels_data");
 /// Map level id to orders at this price
 pub const LEVEL_ORDERS: Map<u64, LevelOrders> = Map::new("level_orders");
 
-// Tracking user orders
-pub const USER_ORDERS: Map<Addr, Vec<UserOrderRecord>> = Map::new("user_orders");
+pub struct UserOrderIndexes<'a> {
+    pub user: MultiIndex<'a, Addr, UserOrderRecord, u64>,
+    pub user_market: MultiIndex<'a, (Addr, u64), UserOrderRecord, u64>,
+}
+
+impl<'a> IndexList<UserOrderRecord> for UserOrderIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<UserOrderRecord>> + '_> {
+        let v: Vec<&dyn Index<UserOrderRecord>> = vec![&self.user, &self.user_market];
+        return Box::new(v.into_iter());
+    }
+}
+
+/// Open orders, map order id to order, indexed by user
+pub fn user_orders<'a>() -> IndexedMap<'a, u64, UserOrderRecord, UserOrderIndexes<'a>> {
+    let indexes = UserOrderIndexes {
+        user: MultiIndex::new(|_pk, order| order.user.clone(), "orders", "orders__user"),
+        user_market: MultiIndex::new(
+            |_pk, order| (order.user.clone(), order.market_id),
+            "orders",
+            "orders__user_market",
+        ),
+    };
+    return IndexedMap::new("orders", indexes);
+}
+
+/// Orders filled, cancelled or expired, kept until the retention window is over
+pub const CLOSED_ORDERS: Map<u64, ClosedOrder> = Map::new("closed_orders");
+
+/// Closed orders by (closing time in seconds, order id), to prune the oldest first
+pub const CLOSED_ORDERS_BY_TIME: Map<(u64, u64), Empty> = Map::new("closed_orders_by_time");
 
 /// Allocate id to new orders
 pub const ORDER_ID_TRACKER: Item<u64> = Item::new("order_id_tracker");
+
+/// Internal balances, map (user, currency type, denom) to the amount available for new orders or withdrawal
+pub const USER_BALANCES: Map<(Addr, String, String), Uint128> = Map::new("user_balances");
+
+/// Protocol fees accrued per (currency type, denom), until withdrawn by the admin
+pub const PROTOCOL_FEES: Map<(String, String), Uint128> = Map::new("protocol_fees");
+
+/// Volume based fee tiers, markets fees apply to everyone if not set
+pub const FEE_TIER_CONFIG: Item<FeeTierConfig> = Item::new("fee_tier_config");
+
+/// Last fill of each market
+pub const LAST_TRADES: Map<u64, LastTrade> = Map::new("last_trades");
+
+/// Traded volume of markets, map (market id, hour) to volume
+pub const MARKET_VOLUMES: Map<(u64, u64), MarketVolume> = Map::new("market_volumes");
+
+/// Traded notional in quote currency, map (user, market id, day) to volume
+pub const USER_VOLUMES: Map<(Addr, u64, u64), Uint128> = Map::new("user_volumes");
+
+/// Same volume counted on all markets quoted in a currency, map (user, quote denom, day) to volume
+pub const USER_QUOTE_VOLUMES: Map<(Addr, String, u64), Uint128> = Map::new("user_quote_volumes");
+
+/// Referrer of each user, set once by the user
+pub const REFERRERS: Map<Addr, Addr> = Map::new("referrers");
+
+/// Share of the taker fees of referred users paid to their referrer, in basis points
+pub const REFERRAL_SHARE_BPS: Item<u16> = Item::new("referral_share_bps");
+
+/// Referral rewards not yet claimed, map (referrer, currency type, denom) to amount
+pub const REFERRAL_REWARDS: Map<(Addr, String, String), Uint128> = Map::new("referral_rewards");
+
+/// Referral rewards earned since the start, map (referrer, currency type, denom) to amount
+pub const REFERRAL_EARNINGS: Map<(Addr, String, String), Uint128> = Map::new("referral_earnings");
+
+/// Token locked by users for taker fee discounts, not set until the admin designates one
+pub const FEE_DISCOUNT_CONFIG: Item<FeeDiscountConfig> = Item::new("fee_discount_config");
+
+/// Fee tokens locked by each user
+pub const FEE_TOKEN_LOCKS: Map<Addr, Uint128> = Map::new("fee_token_locks");
+
+/// Fee tokens unlocked by each user and waiting for their release
+pub const FEE_TOKEN_UNBONDINGS: Map<Addr, Vec<Unbonding>> = Map::new("fee_token_unbondings");
+
+/// Fee paid by anyone creating a market, markets can only be added by the admin if not set
+pub const LISTING_FEE: Item<ListingFee> = Item::new("listing_fee");
@@ -1,10 +1,176 @@
-use cosmwasm_std::Storage;
+use cosmwasm_std::{Addr, Empty, Order, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::Bound;
 
 use crate::{
-    state::{LEVELS_DATA, LEVEL_ORDERS, MARKET_INFO},
+    state::{
+        user_orders, BLOCKLIST, CLOSED_ORDERS, CLOSED_ORDERS_BY_TIME, LEVELS_DATA, LEVEL_ORDERS,
+        MARKET_ALLOWLIST, MARKET_INFO, ORDER_ID_TRACKER, USER_BALANCES,
+    },
+    structs::{ClosedOrder, CurrencyInfo, MarketInfo, OrderStatus, UserOrderRecord},
     ContractError,
 };
 
+/// How long closed orders can still be queried
+pub const CLOSED_ORDER_RETENTION_SECONDS: u64 = 7 * 86_400;
+
+/// Closed orders pruned at most each time an order is closed, to keep the cost of closing bounded
+const MAX_PRUNED_CLOSED_ORDERS: usize = 10;
+
+/// Key of what a user holds in a currency, in the internal balances and referral rewards
+pub fn user_currency_key(user: &Addr, currency: &CurrencyInfo) -> (Addr, String, String) {
+    let (currency_type, denom) = currency.get_key();
+    return (user.clone(), currency_type, denom);
+}
+
+/// Add amount to the internal balance of user
+pub fn credit_balance(
+    storage: &mut dyn Storage,
+    user: &Addr,
+    currency: &CurrencyInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    USER_BALANCES.update(
+        storage,
+        user_currency_key(user, currency),
+        |balance| -> Result<_, ContractError> {
+            return Ok(balance.unwrap_or_default() + amount);
+        },
+    )?;
+
+    return Ok(());
+}
+
+/// Remove amount from the internal balance of user, fails if the balance is too low
+pub fn debit_balance(
+    storage: &mut dyn Storage,
+    user: &Addr,
+    currency: &CurrencyInfo,
+    amount: Uint128,
+) -> Result<(), ContractError> {
+    let key = user_currency_key(user, currency);
+    let balance = USER_BALANCES
+        .may_load(storage, key.clone())?
+        .unwrap_or_default();
+
+    let balance = balance
+        .checked_sub(amount)
+        .map_err(|_| ContractError::InsufficientBalance {})?;
+
+    if balance.is_zero() {
+        USER_BALANCES.remove(storage, key);
+    } else {
+        USER_BALANCES.save(storage, key, &balance)?;
+    }
+
+    return Ok(());
+}
+
+/// Open orders of a user, by increasing order id
+pub fn load_user_orders(storage: &dyn Storage, user: &Addr) -> StdResult<Vec<UserOrderRecord>> {
+    return user_orders()
+        .idx
+        .user
+        .prefix(user.clone())
+        .range(storage, None, None, Order::Ascending)
+        .map(|elem| elem.map(|(_, order)| order))
+        .collect();
+}
+
+/// Open orders of a user on a market, by increasing order id
+pub fn load_user_market_orders(
+    storage: &dyn Storage,
+    user: &Addr,
+    market_id: u64,
+) -> StdResult<Vec<UserOrderRecord>> {
+    return user_orders()
+        .idx
+        .user_market
+        .prefix((user.clone(), market_id))
+        .range(storage, None, None, Order::Ascending)
+        .map(|elem| elem.map(|(_, order)| order))
+        .collect();
+}
+
+/// Remove an order from the book records, and keep it as closed until the retention window is over
+/// Orders whose retention window is over are pruned along the way
+pub fn close_order(
+    storage: &mut dyn Storage,
+    order: UserOrderRecord,
+    status: OrderStatus,
+    now: Timestamp,
+) -> StdResult<()> {
+    prune_closed_orders(storage, now)?;
+
+    user_orders().remove(storage, order.order_id)?;
+    CLOSED_ORDERS_BY_TIME.save(storage, (now.seconds(), order.order_id), &Empty {})?;
+    CLOSED_ORDERS.save(
+        storage,
+        order.order_id,
+        &ClosedOrder {
+            order: order,
+            status: status,
+            closed_at: now,
+        },
+    )?;
+
+    return Ok(());
+}
+
+/// Whether the retention window of an order closed at closed_at is over
+pub fn is_closed_order_expired(closed_at: Timestamp, now: Timestamp) -> bool {
+    return closed_at.plus_seconds(CLOSED_ORDER_RETENTION_SECONDS) <= now;
+}
+
+fn prune_closed_orders(storage: &mut dyn Storage, now: Timestamp) -> StdResult<()> {
+    let retained_from = now.seconds().saturating_sub(CLOSED_ORDER_RETENTION_SECONDS);
+    let expired: Vec<(u64, u64)> = CLOSED_ORDERS_BY_TIME
+        .keys(
+            storage,
+            None,
+            Some(Bound::exclusive((retained_from + 1, 0))),
+            Order::Ascending,
+        )
+        .take(MAX_PRUNED_CLOSED_ORDERS)
+        .collect::<StdResult<_>>()?;
+
+    for (closed_at, order_id) in expired {
+        CLOSED_ORDERS_BY_TIME.remove(storage, (closed_at, order_id));
+        CLOSED_ORDERS.remove(storage, order_id);
+    }
+
+    return Ok(());
+}
+
+/// Allocate the id of a new order
+pub fn next_order_id(storage: &mut dyn Storage) -> StdResult<u64> {
+    let order_id = ORDER_ID_TRACKER.may_load(storage)?.unwrap_or_default();
+    ORDER_ID_TRACKER.save(storage, &(order_id + 1))?;
+
+    return Ok(order_id);
+}
+
+/// Blocklisted traders can't place orders, and permissioned markets are restricted to their allowlist
+/// cancellations and withdrawals are not checked, so funds can always be recovered
+pub fn check_can_trade(
+    storage: &dyn Storage,
+    market_info: &MarketInfo,
+    trader: &Addr,
+) -> Result<(), ContractError> {
+    if BLOCKLIST.has(storage, trader.clone()) {
+        return Err(ContractError::Blocklisted {});
+    }
+
+    if market_info.permissioned
+        && !MARKET_ALLOWLIST.has(storage, (market_info.market_id, trader.clone()))
+    {
+        return Err(ContractError::NotAllowlisted {
+            id: market_info.market_id,
+        });
+    }
+
+    return Ok(());
+}
+
 pub fn remove_previous_id(storage: &mut dyn Storage, level_id: u64) -> Result<(), ContractError> {
     LEVELS_DATA.update(storage, level_id, |data| -> Result<_, ContractError> {
         let mut data = data.unwrap();
@@ -47,14 +213,10 @@ pub fn remove_level(
             |market_info| -> Result<_, ContractError> {
                 let mut market_info = market_info.unwrap();
 
-                if let Some(val_top_bid_id) = market_info.top_level_bid {
-                    if val_top_bid_id == level_id {
-                        market_info.top_level_bid = level_data.id_next;
-                    }
-                } else if let Some(val_top_ask_id) = market_info.top_level_ask {
-                    if val_top_ask_id == level_id {
-                        market_info.top_level_ask = level_data.id_next;
-                    }
+                if market_info.top_level_bid == Some(level_id) {
+                    market_info.top_level_bid = level_data.id_next;
+                } else if market_info.top_level_ask == Some(level_id) {
+                    market_info.top_level_ask = level_data.id_next;
                 }
 
                 return Ok(market_info);
@@ -116,6 +278,9 @@ mod tests {
             CurrencyInfo::Native {
                 denom: "heur".into(),
             },
+            Some(6),
+            Some(6),
+            false,
         )
         .unwrap();
 
@@ -126,6 +291,7 @@ mod tests {
             Decimal::one(),
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -156,6 +322,9 @@ mod tests {
             CurrencyInfo::Native {
                 denom: "heur".into(),
             },
+            Some(6),
+            Some(6),
+            false,
         )
         .unwrap();
 
@@ -166,6 +335,7 @@ mod tests {
             Decimal::one(),
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -177,6 +347,7 @@ mod tests {
             top_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -214,6 +385,9 @@ mod tests {
             CurrencyInfo::Native {
                 denom: "heur".into(),
             },
+            Some(6),
+            Some(6),
+            false,
         )
         .unwrap();
 
@@ -224,6 +398,7 @@ mod tests {
             Decimal::one(),
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -235,6 +410,7 @@ mod tests {
             top_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -275,6 +451,9 @@ mod tests {
             CurrencyInfo::Native {
                 denom: "heur".into(),
             },
+            Some(6),
+            Some(6),
+            false,
         )
         .unwrap();
 
@@ -286,6 +465,7 @@ mod tests {
             middle_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -297,6 +477,7 @@ mod tests {
             top_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -308,6 +489,7 @@ mod tests {
             bottom_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -375,6 +557,9 @@ mod tests {
             CurrencyInfo::Native {
                 denom: "heur".into(),
             },
+            Some(6),
+            Some(6),
+            false,
         )
         .unwrap();
 
@@ -386,6 +571,7 @@ mod tests {
             middle_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -397,6 +583,7 @@ mod tests {
             top_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -408,6 +595,7 @@ mod tests {
             bottom_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -475,6 +663,9 @@ mod tests {
             CurrencyInfo::Native {
                 denom: "heur".into(),
             },
+            Some(6),
+            Some(6),
+            false,
         )
         .unwrap();
 
@@ -486,6 +677,7 @@ mod tests {
             middle_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -497,6 +689,7 @@ mod tests {
             top_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -508,6 +701,7 @@ mod tests {
             bottom_price,
             Uint128::new(100),
             OrderSide::Buy,
+            0,
         )
         .unwrap();
 
@@ -558,4 +752,63 @@ mod tests {
         assert!(middle_level_data.id_next.is_none());
         assert_eq!(middle_level_data.id_previous.unwrap(), top_level_id);
     }
+
+    /// Create a market with a bid and two asks then remove top ask
+    /// top ask id should have been updated even if there are bids
+    #[test]
+    fn test_utils_remove_top_ask_with_bids() {
+        // set up
+        let mut deps = mock_dependencies();
+
+        add_market(
+            deps.as_mut(),
+            CurrencyInfo::Native {
+                denom: "husd".into(),
+            },
+            CurrencyInfo::Native {
+                denom: "heur".into(),
+            },
+            Some(6),
+            Some(6),
+            false,
+        )
+        .unwrap();
+
+        process_limit_maker(
+            deps.as_mut().storage,
+            Addr::unchecked("user").clone(),
+            0,
+            Decimal::one(),
+            Uint128::new(100),
+            OrderSide::Buy,
+            0,
+        )
+        .unwrap();
+
+        let top_ask_price = Decimal::from_atomics(Uint128::new(11), 1).unwrap();
+        let bottom_ask_price = Decimal::from_atomics(Uint128::new(12), 1).unwrap();
+        for price in [top_ask_price, bottom_ask_price] {
+            process_limit_maker(
+                deps.as_mut().storage,
+                Addr::unchecked("user").clone(),
+                0,
+                price,
+                Uint128::new(100),
+                OrderSide::Sell,
+                0,
+            )
+            .unwrap();
+        }
+
+        let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
+        let top_ask_id = create_id_level_no_status(&market_info, top_ask_price);
+        let bottom_ask_id = create_id_level_no_status(&market_info, bottom_ask_price);
+        assert_eq!(market_info.top_level_ask.unwrap(), top_ask_id);
+
+        remove_level(deps.as_mut().storage, 0, top_ask_id).unwrap();
+
+        let market_info = MARKET_INFO.load(deps.as_ref().storage, 0).unwrap();
+        assert_eq!(market_info.top_level_ask.unwrap(), bottom_ask_id);
+        assert!(market_info.top_level_bid.is_some());
+    }
 }
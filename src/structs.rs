@@ -1,8 +1,14 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Decimal, Storage, Uint128, Uint256};
-use serde::{Deserialize, Serialize};
+use cosmwasm_std::{
+    Addr, CosmosMsg, Decimal, Decimal256, StdResult, Storage, Timestamp, Uint128, Uint256,
+};
 
-use crate::{state::LEVELS_DATA, ContractError};
+use crate::{
+    state::{LEVELS_DATA, PROTOCOL_FEES, REFERRAL_EARNINGS, REFERRAL_REWARDS},
+    state_utils::{credit_balance, user_currency_key},
+    utils::create_funds_message,
+    ContractError,
+};
 
 #[cw_serde]
 pub enum OrderSide {
@@ -31,6 +37,15 @@ impl CurrencyInfo {
         };
     }
 
+    /// Storage key of the currency, its type comes first so a cw20 address and a native denom
+    /// with the same name are never mixed up
+    pub fn get_key(&self) -> (String, String) {
+        return match self {
+            CurrencyInfo::Cw20 { address } => ("cw20".to_string(), address.to_owned()),
+            CurrencyInfo::Native { denom } => ("native".to_string(), denom.to_owned()),
+        };
+    }
+
     pub fn is_match(&self, target: &str) -> bool {
         return match self {
             CurrencyInfo::Cw20 { address } => address.eq(target),
@@ -58,11 +73,96 @@ impl CurrencyStatus {
     }
 }
 
+/// Admin proposed by the current admin, until it accepts
+#[cw_serde]
+pub struct PendingAdmin {
+    pub address: Addr,
+    pub expires_at: Option<Timestamp>,
+}
+
+/// Permissions granted by the admin, which holds all of them
+#[cw_serde]
+pub enum Role {
+    /// every admin action, except transferring or renouncing the admin
+    SuperAdmin,
+    /// add markets, set the listing fee and manage the lifecycle of markets
+    MarketLister,
+    /// set fees and withdraw protocol fees
+    FeeManager,
+    /// pause markets or restrict them to cancellations
+    Guardian,
+}
+
+impl Role {
+    /// Storage key of the role
+    pub fn as_str(&self) -> &'static str {
+        return match self {
+            Role::SuperAdmin => "super_admin",
+            Role::MarketLister => "market_lister",
+            Role::FeeManager => "fee_manager",
+            Role::Guardian => "guardian",
+        };
+    }
+}
+
+/// Fee paid to create a market without the admin
+#[cw_serde]
+pub struct ListingFee {
+    pub currency: CurrencyInfo,
+    pub amount: Uint128,
+}
+
+/// Parameters given to new markets
+#[cw_serde]
+#[derive(Default)]
+pub struct MarketParams {
+    pub tick_size: Option<Decimal>,
+    pub lot_size: Option<Uint128>,
+    pub maker_fee_bps: i16,
+    pub taker_fee_bps: u16,
+}
+
+/// Caps keeping the orders a fill or a cancellation walks through bounded
+#[cw_serde]
+#[derive(Default)]
+pub struct OrderLimits {
+    /// open orders of a user on a single market
+    pub max_orders_per_user: Option<u32>,
+    /// orders resting at a single price level
+    pub max_orders_per_level: Option<u32>,
+}
+
+/// Lifecycle of a market
+#[cw_serde]
+pub enum MarketStatus {
+    /// orders can be placed and cancelled
+    Active,
+    /// orders can only be cancelled
+    CancelOnly,
+    /// nothing can be done on the market
+    Paused,
+    /// the book is being refunded, the market can't be used again
+    Delisted,
+}
+
 #[cw_serde]
 pub struct MarketInfo {
     pub market_id: u64,
     pub base_currency: CurrencyInfo,
     pub quote_currency: CurrencyInfo,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    /// fees in basis points, charged on what makers and takers receive
+    /// a negative maker fee is a rebate, paid out of the taker fee
+    pub maker_fee_bps: i16,
+    pub taker_fee_bps: u16,
+    pub status: MarketStatus,
+    /// only allowlisted traders can place orders on a permissioned market
+    pub permissioned: bool,
+    /// limit prices must be a multiple of the tick size
+    pub tick_size: Option<Decimal>,
+    /// resting orders are rounded down to a multiple of the lot size, in base currency
+    pub lot_size: Option<Uint128>,
     pub top_level_bid: Option<u64>, // Option<u64>
     pub top_level_ask: Option<u64>, // Option<u64>
 }
@@ -72,9 +172,56 @@ pub struct SingleMarketInfo {
     pub market_id: u64,
     pub base_currency: CurrencyInfo,
     pub quote_currency: CurrencyInfo,
+    pub base_decimals: u8,
+    pub quote_decimals: u8,
+    pub maker_fee_bps: i16,
+    pub taker_fee_bps: u16,
+    pub status: MarketStatus,
+    pub permissioned: bool,
+    pub tick_size: Option<Decimal>,
+    pub lot_size: Option<Uint128>,
 }
 
 impl MarketInfo {
+    /// Limit prices must fall on a tick of the market
+    pub fn check_tick(&self, price: Decimal) -> Result<(), ContractError> {
+        if let Some(tick_size) = self.tick_size {
+            if !(price % tick_size).is_zero() {
+                return Err(ContractError::PriceNotOnTick {
+                    tick_size: tick_size,
+                });
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Quantity of base currency rounded down to a whole number of lots
+    pub fn round_to_lot(&self, quantity: Uint128) -> Uint128 {
+        return match self.lot_size {
+            None => quantity,
+            Some(lot_size) => quantity - quantity % lot_size,
+        };
+    }
+
+    /// New orders can only be placed on active markets
+    pub fn check_can_place_orders(&self) -> Result<(), ContractError> {
+        if self.status != MarketStatus::Active {
+            return Err(ContractError::MarketNotActive { id: self.market_id });
+        }
+
+        return Ok(());
+    }
+
+    /// Orders can be cancelled unless the market is paused
+    pub fn check_can_cancel_orders(&self) -> Result<(), ContractError> {
+        if self.status == MarketStatus::Paused {
+            return Err(ContractError::MarketPaused { id: self.market_id });
+        }
+
+        return Ok(());
+    }
+
     pub fn get_currency_status(&self, target_denom: &str) -> Result<CurrencyStatus, ContractError> {
         if self.is_base_currency(target_denom) {
             return Ok(CurrencyStatus::BaseCurrency);
@@ -150,6 +297,20 @@ impl MarketInfo {
         }
     }
 
+    /// Price of a whole unit of base currency in whole units of quote currency
+    /// Stored prices are ratios between atomic amounts
+    pub fn to_human_price(&self, price: Decimal) -> Decimal256 {
+        let decimal_places =
+            Decimal::DECIMAL_PLACES + self.quote_decimals as u32 - self.base_decimals as u32;
+
+        return Decimal256::from_atomics(price.atomics(), decimal_places).unwrap();
+    }
+
+    /// Quantity of base currency in whole units
+    pub fn to_human_quantity(&self, quantity: Uint256) -> Decimal256 {
+        return Decimal256::from_atomics(quantity, self.base_decimals as u32).unwrap();
+    }
+
     pub fn is_valid_currency(&self, target_denom: &str) -> bool {
         return self.is_base_currency(target_denom) || self.is_quote_currency(target_denom);
     }
@@ -216,10 +377,59 @@ pub struct InternalUserOrder {
 
 #[cw_serde]
 pub struct UserOrderRecord {
+    pub order_id: u64,
+    pub user: Addr,
     pub market_id: u64,
     pub order_side: OrderSide,
     pub price: Decimal,
+    /// quantity of base currency, whatever the side of the order
     pub quantity: Uint128,
+    /// amount still locked for the order, in the currency it was funded with
+    pub locked: Uint128,
+    /// quantity of base currency the order was placed with, including what was filled
+    pub original_quantity: Uint128,
+    /// quantity of base currency filled, and its value in quote currency
+    pub filled_quantity: Uint128,
+    pub filled_notional: Uint128,
+    pub created_at: Timestamp,
+}
+
+impl UserOrderRecord {
+    /// Status of an order still in the book
+    pub fn open_status(&self) -> OrderStatus {
+        if self.filled_quantity.is_zero() {
+            return OrderStatus::Open;
+        }
+
+        return OrderStatus::PartiallyFilled;
+    }
+}
+
+/// Lifecycle of an order
+#[cw_serde]
+pub enum OrderStatus {
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    /// removed from the book by the contract when its market was delisted
+    Expired,
+}
+
+/// An order no longer in the book, as it was when closed
+#[cw_serde]
+pub struct ClosedOrder {
+    pub order: UserOrderRecord,
+    pub status: OrderStatus,
+    pub closed_at: Timestamp,
+}
+
+/// An order of a user, with price and quantity scaled to whole units of the currencies
+#[cw_serde]
+pub struct UserOrderInfo {
+    pub order: UserOrderRecord,
+    pub human_price: Decimal256,
+    pub human_quantity: Decimal256,
 }
 
 #[cw_serde]
@@ -231,23 +441,11 @@ pub struct UserOrder {
 
 #[cw_serde]
 pub struct LevelOrder {
+    pub order_id: u64,
     pub user: Addr,
     pub amount: Uint128,
 }
 
-impl LevelOrder {
-    pub fn to_quote_currency_level_order(
-        &self,
-        price: Decimal,
-        order_side: OrderSide,
-    ) -> QuoteCurrencyLevelOrder {
-        return QuoteCurrencyLevelOrder {
-            user: self.user.clone(),
-            amount: self.amount,
-        };
-    }
-}
-
 #[cw_serde]
 pub struct QuoteCurrencyLevelOrder {
     pub user: Addr,
@@ -267,13 +465,322 @@ pub type LevelOrders = Vec<LevelOrder>;
 pub struct BookLevel {
     pub price: Decimal,
     pub quantity: Uint256,
+    /// price and quantity scaled to whole units of the currencies
+    pub human_price: Decimal256,
+    pub human_quantity: Decimal256,
 }
 
 impl BookLevel {
-    pub fn new(price: Decimal) -> Self {
+    pub fn new(market_info: &MarketInfo, price: Decimal, quantity: Uint256) -> Self {
         return BookLevel {
             price: price,
-            quantity: Uint256::zero(),
+            quantity: quantity,
+            human_price: market_info.to_human_price(price),
+            human_quantity: market_info.to_human_quantity(quantity),
+        };
+    }
+}
+
+/// Levels of one side of the book within a price range, for depth charts
+#[cw_serde]
+pub struct DepthBucket {
+    /// bound of the range furthest from the top of book
+    pub price: Decimal,
+    /// base currency resting in the range
+    pub quantity: Uint256,
+    /// base currency and its value in quote currency, from the top of book to the end of the range
+    pub cumulative_quantity: Uint256,
+    pub cumulative_notional: Uint256,
+}
+
+/// Price and time of the last fill on a market
+#[cw_serde]
+pub struct LastTrade {
+    pub price: Decimal,
+    pub time: Timestamp,
+}
+
+/// Base and quote currency traded on a market
+#[cw_serde]
+#[derive(Default)]
+pub struct MarketVolume {
+    pub base: Uint128,
+    pub quote: Uint128,
+}
+
+/// Ticker of a market
+#[cw_serde]
+pub struct MarketSummary {
+    pub market_id: u64,
+    /// top of book on each side, with the quantity of base currency resting at that price
+    pub best_bid: Option<BookLevel>,
+    pub best_ask: Option<BookLevel>,
+    /// set when both sides have orders
+    pub spread: Option<Decimal>,
+    /// spread relative to the midprice, in basis points rounded down
+    pub spread_bps: Option<Uint128>,
+    pub midprice: Option<Decimal>,
+    pub last_trade: Option<LastTrade>,
+    /// traded over the last 24 hours, counted by whole hours
+    pub volume_24h: MarketVolume,
+}
+
+/// What the traded volume of a user is counted on to find its fee tier
+#[cw_serde]
+pub enum VolumeScope {
+    /// volume on the market of the order
+    Market,
+    /// volume on all the markets quoted in denom
+    QuoteCurrency { denom: String },
+}
+
+/// Fees applied to users that traded at least min_volume over the window
+#[cw_serde]
+pub struct FeeTier {
+    pub min_volume: Uint128,
+    pub maker_fee_bps: i16,
+    pub taker_fee_bps: u16,
+}
+
+#[cw_serde]
+pub struct FeeTierConfig {
+    pub window_days: u64,
+    pub scope: VolumeScope,
+    /// sorted by increasing min_volume
+    pub tiers: Vec<FeeTier>,
+}
+
+impl Default for FeeTierConfig {
+    fn default() -> Self {
+        return FeeTierConfig {
+            window_days: 30,
+            scope: VolumeScope::Market,
+            tiers: vec![],
+        };
+    }
+}
+
+/// Discount on the taker fee of users locking at least min_locked of the fee token
+#[cw_serde]
+pub struct FeeDiscount {
+    pub min_locked: Uint128,
+    pub discount_bps: u16,
+}
+
+#[cw_serde]
+pub struct FeeDiscountConfig {
+    /// cw20 token which is locked to get discounts
+    pub token: Addr,
+    /// delay before unlocked tokens can be claimed
+    pub unbonding_seconds: u64,
+    /// sorted by increasing min_locked
+    pub discounts: Vec<FeeDiscount>,
+}
+
+/// Fee tokens unlocked by a user, which can be claimed once released
+#[cw_serde]
+pub struct Unbonding {
+    pub amount: Uint128,
+    pub release_at: Timestamp,
+}
+
+/// Fees applied to a user on a market
+#[cw_serde]
+pub struct UserFees {
+    /// index of the tier of the user in the tier table, none if below the first tier
+    pub tier: Option<u32>,
+    pub maker_fee_bps: i16,
+    /// taker fee once the discount for locked fee tokens is applied
+    pub taker_fee_bps: u16,
+    pub taker_discount_bps: u16,
+}
+
+/// Where the funds of an order come from
+/// Proceeds owed to the order sender go back the same way
+#[cw_serde]
+pub enum FundingSource {
+    /// funds were sent along with the order
+    Transfer,
+    /// funds were taken from the internal balance of the sender
+    Balance,
+}
+
+/// Amount owed to a recipient in a given currency
+#[cw_serde]
+pub struct Payout {
+    pub recipient: Addr,
+    pub currency: CurrencyInfo,
+    pub amount: Uint128,
+}
+
+/// Collects what is owed while an order is processed
+/// Transfers are sent out, one per recipient and currency, while credits go to internal balances
+/// and fees to the protocol fee balance
+#[derive(Default)]
+pub struct Settlement {
+    pub transfers: Vec<Payout>,
+    pub credits: Vec<Payout>,
+    pub fees: Vec<(CurrencyInfo, Uint128)>,
+    pub referral_rewards: Vec<Payout>,
+}
+
+impl Settlement {
+    pub fn new() -> Self {
+        return Settlement::default();
+    }
+
+    /// Add an amount to send to recipient, netting it with previous amounts in the same currency
+    pub fn add_transfer(&mut self, recipient: &Addr, currency: &CurrencyInfo, amount: Uint128) {
+        Settlement::net_payout(&mut self.transfers, recipient, currency, amount);
+    }
+
+    /// Add an amount to credit to the internal balance of recipient
+    pub fn add_credit(&mut self, recipient: &Addr, currency: &CurrencyInfo, amount: Uint128) {
+        Settlement::net_payout(&mut self.credits, recipient, currency, amount);
+    }
+
+    /// Add a fee to the protocol fee balance
+    pub fn add_fee(&mut self, currency: &CurrencyInfo, amount: Uint128) {
+        if amount.is_zero() {
+            return;
+        }
+
+        match self
+            .fees
+            .iter_mut()
+            .find(|(fee_currency, _)| fee_currency == currency)
+        {
+            Some((_, fee)) => *fee += amount,
+            None => self.fees.push((currency.clone(), amount)),
+        }
+    }
+
+    /// Add a share of the taker fee to the rewards of a referrer
+    pub fn add_referral_reward(
+        &mut self,
+        referrer: &Addr,
+        currency: &CurrencyInfo,
+        amount: Uint128,
+    ) {
+        Settlement::net_payout(&mut self.referral_rewards, referrer, currency, amount);
+    }
+
+    /// Add an amount owed to the sender of an order, following the funding source of the order
+    pub fn add_for_funding(
+        &mut self,
+        funding: &FundingSource,
+        recipient: &Addr,
+        currency: &CurrencyInfo,
+        amount: Uint128,
+    ) {
+        match funding {
+            FundingSource::Transfer => self.add_transfer(recipient, currency, amount),
+            FundingSource::Balance => self.add_credit(recipient, currency, amount),
+        }
+    }
+
+    fn net_payout(
+        payouts: &mut Vec<Payout>,
+        recipient: &Addr,
+        currency: &CurrencyInfo,
+        amount: Uint128,
+    ) {
+        if amount.is_zero() {
+            return;
+        }
+
+        match payouts
+            .iter_mut()
+            .find(|payout| payout.recipient == *recipient && payout.currency == *currency)
+        {
+            Some(payout) => payout.amount += amount,
+            None => payouts.push(Payout {
+                recipient: recipient.clone(),
+                currency: currency.clone(),
+                amount: amount,
+            }),
+        }
+    }
+
+    /// Credit internal balances and create the transfer messages
+    pub fn settle(self, storage: &mut dyn Storage) -> Result<Vec<CosmosMsg>, ContractError> {
+        for credit in &self.credits {
+            credit_balance(storage, &credit.recipient, &credit.currency, credit.amount)?;
+        }
+
+        for (currency, amount) in &self.fees {
+            PROTOCOL_FEES.update(storage, currency.get_key(), |fees| -> StdResult<_> {
+                return Ok(fees.unwrap_or_default() + amount);
+            })?;
+        }
+
+        for reward in &self.referral_rewards {
+            let key = user_currency_key(&reward.recipient, &reward.currency);
+            REFERRAL_REWARDS.update(storage, key.clone(), |rewards| -> StdResult<_> {
+                return Ok(rewards.unwrap_or_default() + reward.amount);
+            })?;
+            REFERRAL_EARNINGS.update(storage, key, |earnings| -> StdResult<_> {
+                return Ok(earnings.unwrap_or_default() + reward.amount);
+            })?;
+        }
+
+        return Ok(self
+            .transfers
+            .into_iter()
+            .map(|payout| create_funds_message(payout.amount, payout.currency, payout.recipient))
+            .collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{testing::MockStorage, Addr, Uint128};
+
+    use crate::{
+        state::{PROTOCOL_FEES, USER_BALANCES},
+        state_utils::user_currency_key,
+    };
+
+    use super::{CurrencyInfo, Settlement};
+
+    #[test]
+    fn settlement_nets_payouts_per_recipient_and_currency() {
+        let user_1 = Addr::unchecked("user1");
+        let user_2 = Addr::unchecked("user2");
+        let husd = CurrencyInfo::Native {
+            denom: "husd".into(),
         };
+        let heur = CurrencyInfo::Native {
+            denom: "heur".into(),
+        };
+
+        let mut settlement = Settlement::new();
+        settlement.add_transfer(&user_1, &husd, Uint128::new(10));
+        settlement.add_transfer(&user_1, &husd, Uint128::new(5));
+        settlement.add_transfer(&user_1, &heur, Uint128::new(3));
+        settlement.add_transfer(&user_2, &husd, Uint128::new(7));
+        settlement.add_transfer(&user_2, &heur, Uint128::zero());
+        settlement.add_credit(&user_2, &heur, Uint128::new(4));
+        settlement.add_credit(&user_2, &heur, Uint128::new(4));
+        settlement.add_fee(&heur, Uint128::new(2));
+        settlement.add_fee(&heur, Uint128::new(1));
+
+        assert_eq!(settlement.transfers.len(), 3);
+        assert_eq!(settlement.transfers[0].amount, Uint128::new(15));
+        assert_eq!(settlement.credits.len(), 1);
+
+        let mut storage = MockStorage::new();
+        let messages = settlement.settle(&mut storage).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            USER_BALANCES
+                .load(&storage, user_currency_key(&user_2, &heur))
+                .unwrap(),
+            Uint128::new(8)
+        );
+        assert_eq!(
+            PROTOCOL_FEES.load(&storage, heur.get_key()).unwrap(),
+            Uint128::new(3)
+        );
     }
 }
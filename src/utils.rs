@@ -5,14 +5,18 @@ use std::{
 };
 
 use cosmwasm_std::{
-    to_binary, Addr, BankMsg, Coin, CosmosMsg, Decimal, MessageInfo, Uint128, WasmMsg,
+    to_binary, Addr, BankMsg, Coin, CosmosMsg, Decimal, MessageInfo, StdError, StdResult, Uint128,
+    WasmMsg,
 };
 
 use crate::{
-    structs::{CurrencyInfo, CurrencyStatus, MarketInfo},
+    structs::{CurrencyInfo, CurrencyStatus, MarketInfo, OrderSide},
     ContractError,
 };
 
+/// Basis points in a whole
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
 /// Wrap native and cw20 transfer messages in one function
 pub fn create_funds_message(
     amount: Uint128,
@@ -47,6 +51,59 @@ pub fn create_funds_message(
     };
 }
 
+/// Amount locked by an order, in the currency it was funded with
+/// Bids lock the quote value of their quantity rounded up, so that every fill can be paid
+pub fn compute_escrow(quantity: Uint128, price: Decimal, order_side: &OrderSide) -> Uint128 {
+    return match order_side {
+        OrderSide::Buy => quantity.checked_mul_ceil(price).unwrap(),
+        OrderSide::Sell => quantity,
+    };
+}
+
+/// Quote value of a fill, always rounded down whatever the side of the taker
+pub fn compute_fill_notional(quantity: Uint128, price: Decimal) -> Uint128 {
+    return quantity.checked_mul_floor(price).unwrap();
+}
+
+/// Fee in basis points on an amount, rounded down
+pub fn compute_fee(amount: Uint128, fee_bps: u16) -> Uint128 {
+    return amount.multiply_ratio(fee_bps, BPS_DENOMINATOR);
+}
+
+/// Smallest amount which is still worth net once its fee is taken
+/// Errors when that amount doesn't fit
+pub fn compute_gross_for_net(net: Uint128, fee_bps: u16) -> StdResult<Uint128> {
+    let mut gross = net
+        .checked_multiply_ratio(BPS_DENOMINATOR, BPS_DENOMINATOR - fee_bps)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    // the fee is rounded down, so the gross can be off by a few units either way
+    while gross - compute_fee(gross, fee_bps) < net {
+        gross = gross.checked_add(Uint128::one())?;
+    }
+    while !gross.is_zero()
+        && gross - Uint128::one() - compute_fee(gross - Uint128::one(), fee_bps) >= net
+    {
+        gross -= Uint128::one();
+    }
+
+    return Ok(gross);
+}
+
+/// Add amount to the coin of the same denom, or push a new coin
+pub fn add_to_coins(coins: &mut Vec<Coin>, denom: String, amount: Uint128) {
+    if amount.is_zero() {
+        return;
+    }
+
+    match coins.iter_mut().find(|coin| coin.denom == denom) {
+        Some(coin) => coin.amount += amount,
+        None => coins.push(Coin {
+            denom: denom,
+            amount: amount,
+        }),
+    }
+}
+
 /// Compute midprice for a market
 pub fn compute_midprice(bid_price: Decimal, ask_price: Decimal) -> Decimal {
     let divisor = Decimal::from_atomics(Uint128::new(2), 0).unwrap();
@@ -103,7 +160,55 @@ where
 mod tests {
     use std::cmp::Ordering;
 
-    use super::wrapped_comparison;
+    use cosmwasm_std::{Decimal, Uint128};
+
+    use crate::structs::OrderSide;
+
+    use super::{
+        compute_escrow, compute_fee, compute_fill_notional, compute_gross_for_net,
+        wrapped_comparison,
+    };
+
+    /// the gross is the smallest amount which is worth the net after its fee
+    #[test]
+    fn utils_test_gross_for_net() {
+        for fee_bps in [0u16, 30, 100, 1_000] {
+            for net in 0..=1_000u128 {
+                let net = Uint128::new(net);
+                let gross = compute_gross_for_net(net, fee_bps).unwrap();
+                assert!(gross - compute_fee(gross, fee_bps) >= net);
+                if !gross.is_zero() {
+                    let smaller = gross - Uint128::one();
+                    assert!(smaller - compute_fee(smaller, fee_bps) < net);
+                }
+            }
+        }
+
+        // a gross which doesn't fit is an error
+        assert!(compute_gross_for_net(Uint128::MAX, 30).is_err());
+        assert_eq!(compute_gross_for_net(Uint128::MAX, 0), Ok(Uint128::MAX));
+    }
+
+    /// the escrow released by a partial fill of a bid always covers the value of the fill
+    #[test]
+    fn utils_test_escrow_covers_fills() {
+        let price = Decimal::from_atomics(Uint128::new(7), 1).unwrap();
+        let quantity = Uint128::new(143);
+
+        // 143 * 0.7 = 100.1
+        assert_eq!(
+            compute_escrow(quantity, price, &OrderSide::Buy),
+            Uint128::new(101)
+        );
+        assert_eq!(compute_escrow(quantity, price, &OrderSide::Sell), quantity);
+
+        for filled in 1..=143u128 {
+            let filled = Uint128::new(filled);
+            let released = compute_escrow(quantity, price, &OrderSide::Buy)
+                - compute_escrow(quantity - filled, price, &OrderSide::Buy);
+            assert!(released >= compute_fill_notional(filled, price));
+        }
+    }
 
     #[test]
     fn utils_test_wrapped_comparison() {
@@ -0,0 +1,310 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{AdminExecuteMsg, ExecuteMsg, GetUserTradingAccessResponse, QueryMsg},
+        structs::CurrencyInfo,
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        assert_holdings_match_escrow, create_market_native_only_pair, instantiate_selene,
+        query_bank_balance, send_native_order, CashMachine, NATIVE_DENOM_2, TEST_ADMIN,
+        TEST_USER_1, TEST_USER_2,
+    };
+
+    fn execute_admin(router: &mut App, contract_addr: &Addr, msg: AdminExecuteMsg) {
+        router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::Admin(msg),
+                &[],
+            )
+            .unwrap();
+    }
+
+    /// cancels up to limit orders of a blocklisted user, returning whether some are left
+    fn cancel_blocklisted_orders(
+        router: &mut App,
+        contract_addr: &Addr,
+        user: &Addr,
+        limit: u32,
+    ) -> bool {
+        let res = router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::Admin(AdminExecuteMsg::CancelBlocklistedOrders {
+                    address: user.to_string(),
+                    limit: Some(limit),
+                }),
+                &[],
+            )
+            .unwrap();
+        let attribute = res
+            .events
+            .iter()
+            .filter(|event| event.ty == "wasm")
+            .flat_map(|event| event.attributes.iter())
+            .find(|attribute| attribute.key == "has_open_orders")
+            .unwrap();
+        return attribute.value == "true";
+    }
+
+    fn try_limit_order(router: &mut App, contract_addr: &Addr, user: &Addr) -> ContractError {
+        let coin = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(1000),
+        };
+        router.mint_native(user, coin.clone());
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: Decimal::one(),
+        };
+        let err = router
+            .execute_contract(user.clone(), contract_addr.clone(), &msg, &[coin])
+            .unwrap_err();
+        return err.downcast::<ContractError>().unwrap();
+    }
+
+    fn query_access(
+        router: &App,
+        contract_addr: &Addr,
+        user: &Addr,
+    ) -> GetUserTradingAccessResponse {
+        return router
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::GetUserTradingAccess {
+                    user_address: user.clone(),
+                    market_id: 0,
+                },
+            )
+            .unwrap();
+    }
+
+    /// only allowlisted traders can place orders on a permissioned market
+    #[test]
+    fn access_permissioned_market() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        execute_admin(
+            &mut router,
+            &market_addr,
+            AdminExecuteMsg::UpdateMarketPermissioned {
+                market_id: 0,
+                permissioned: true,
+            },
+        );
+        execute_admin(
+            &mut router,
+            &market_addr,
+            AdminExecuteMsg::UpdateMarketAllowlist {
+                market_id: 0,
+                add: vec![TEST_USER_1.into()],
+                remove: vec![],
+            },
+        );
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(Decimal::one()),
+        );
+        assert_eq!(
+            try_limit_order(&mut router, &market_addr, &user_2),
+            ContractError::NotAllowlisted { id: 0 }
+        );
+        assert_eq!(
+            query_access(&router, &market_addr, &user_2),
+            GetUserTradingAccessResponse {
+                blocklisted: false,
+                allowlisted: false,
+                can_trade: false,
+            }
+        );
+
+        // removing from the allowlist stops new orders, opening the market lets everyone trade
+        execute_admin(
+            &mut router,
+            &market_addr,
+            AdminExecuteMsg::UpdateMarketAllowlist {
+                market_id: 0,
+                add: vec![],
+                remove: vec![TEST_USER_1.into()],
+            },
+        );
+        assert_eq!(
+            try_limit_order(&mut router, &market_addr, &user_1),
+            ContractError::NotAllowlisted { id: 0 }
+        );
+
+        execute_admin(
+            &mut router,
+            &market_addr,
+            AdminExecuteMsg::UpdateMarketPermissioned {
+                market_id: 0,
+                permissioned: false,
+            },
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_2,
+            1000,
+            Some(Decimal::from_ratio(1u128, 2u128)),
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+    }
+
+    /// blocklisted traders can't place orders anywhere, but can still recover their funds
+    #[test]
+    fn access_blocklist() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let user_1 = Addr::unchecked(TEST_USER_1);
+
+        let price = Decimal::one();
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(Decimal::percent(50)),
+        );
+
+        // only blocklisted addresses can have their orders cancelled
+        let err = router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                market_addr.clone(),
+                &ExecuteMsg::Admin(AdminExecuteMsg::CancelBlocklistedOrders {
+                    address: TEST_USER_1.into(),
+                    limit: None,
+                }),
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::NotBlocklisted {
+                address: user_1.clone()
+            }
+        );
+
+        execute_admin(
+            &mut router,
+            &market_addr,
+            AdminExecuteMsg::UpdateBlocklist {
+                add: vec![TEST_USER_1.into()],
+                remove: vec![],
+            },
+        );
+        assert_eq!(
+            try_limit_order(&mut router, &market_addr, &user_1),
+            ContractError::Blocklisted {}
+        );
+        assert_eq!(
+            query_access(&router, &market_addr, &user_1),
+            GetUserTradingAccessResponse {
+                blocklisted: true,
+                allowlisted: false,
+                can_trade: false,
+            }
+        );
+
+        // unknown markets are an error
+        router
+            .wrap()
+            .query_wasm_smart::<GetUserTradingAccessResponse>(
+                market_addr.clone(),
+                &QueryMsg::GetUserTradingAccess {
+                    user_address: user_1.clone(),
+                    market_id: 5,
+                },
+            )
+            .unwrap_err();
+
+        // resting orders are cancelled in batches, so they can't be filled anymore
+        assert!(cancel_blocklisted_orders(
+            &mut router,
+            &market_addr,
+            &user_1,
+            1
+        ));
+        assert!(!cancel_blocklisted_orders(
+            &mut router,
+            &market_addr,
+            &user_1,
+            1
+        ));
+        let msg = ExecuteMsg::RemoveLimitOrder {
+            market_id: 0,
+            price: price,
+        };
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::OrderDoesNotExist {}
+        );
+
+        // and the refund can be withdrawn
+        let msg = ExecuteMsg::Withdraw {
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            amount: Uint128::new(2000),
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        // the failed order attempt minted another 1000
+        assert_eq!(
+            query_bank_balance(&router, &user_1, NATIVE_DENOM_2),
+            Uint128::new(3000)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        execute_admin(
+            &mut router,
+            &market_addr,
+            AdminExecuteMsg::UpdateBlocklist {
+                add: vec![],
+                remove: vec![TEST_USER_1.into()],
+            },
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price),
+        );
+    }
+}
@@ -0,0 +1,317 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::Addr;
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{AdminExecuteMsg, ExecuteMsg, GetAdminResponse, GetRoleMembersResponse, QueryMsg},
+        structs::{MarketStatus, PendingAdmin, Role},
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        create_market_native_only_pair, instantiate_selene, TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    fn query_admin(router: &App, market_addr: &Addr) -> GetAdminResponse {
+        return router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetAdmin {})
+            .unwrap();
+    }
+
+    fn set_fee_collector(router: &mut App, market_addr: &Addr) {
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateFeeCollector {
+            fee_collector: Some(TEST_USER_2.into()),
+        });
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+    }
+
+    fn propose(router: &mut App, market_addr: &Addr, new_admin: &str, expires_in: Option<u64>) {
+        let expires_at = expires_in.map(|seconds| router.block_info().time.plus_seconds(seconds));
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::ProposeAdmin {
+            new_admin: new_admin.into(),
+            expires_at: expires_at,
+        });
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+    }
+
+    /// the admin only changes once the proposed address accepts, before the expiry
+    #[test]
+    fn admin_propose_and_accept() {
+        let (mut router, market_addr) = instantiate_selene();
+        let user_1 = Addr::unchecked(TEST_USER_1);
+
+        propose(&mut router, &market_addr, TEST_USER_1, Some(100));
+        let res = query_admin(&router, &market_addr);
+        assert_eq!(res.admin, Some(Addr::unchecked(TEST_ADMIN)));
+        assert_eq!(
+            res.pending_admin,
+            Some(PendingAdmin {
+                address: user_1.clone(),
+                expires_at: Some(router.block_info().time.plus_seconds(100)),
+            })
+        );
+
+        // only the proposed address can accept
+        let err = router
+            .execute_contract(
+                Addr::unchecked(TEST_USER_2),
+                market_addr.clone(),
+                &ExecuteMsg::AcceptAdmin {},
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Unauthorized {}
+        );
+
+        router
+            .execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &ExecuteMsg::AcceptAdmin {},
+                &[],
+            )
+            .unwrap();
+        let res = query_admin(&router, &market_addr);
+        assert_eq!(res.admin, Some(user_1.clone()));
+        assert_eq!(res.pending_admin, None);
+
+        // the previous admin lost its rights
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::CancelAdminProposal {});
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Unauthorized {}
+        );
+
+        // a proposal can not be accepted after it expired
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::ProposeAdmin {
+            new_admin: TEST_USER_2.into(),
+            expires_at: Some(router.block_info().time.plus_seconds(100)),
+        });
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        router.update_block(|block| block.time = block.time.plus_seconds(100));
+        let err = router
+            .execute_contract(
+                Addr::unchecked(TEST_USER_2),
+                market_addr.clone(),
+                &ExecuteMsg::AcceptAdmin {},
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::AdminProposalExpired {}
+        );
+    }
+
+    /// a proposal can be cancelled, and renouncing leaves the contract without admin
+    #[test]
+    fn admin_cancel_and_renounce() {
+        let (mut router, market_addr) = instantiate_selene();
+        let admin = Addr::unchecked(TEST_ADMIN);
+
+        // the expiry must be in the future
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::ProposeAdmin {
+            new_admin: TEST_USER_1.into(),
+            expires_at: Some(router.block_info().time),
+        });
+        let err = router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidExpiry {}
+        );
+
+        propose(&mut router, &market_addr, TEST_USER_1, None);
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::CancelAdminProposal {});
+        router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        assert_eq!(query_admin(&router, &market_addr).pending_admin, None);
+
+        let err = router
+            .execute_contract(
+                Addr::unchecked(TEST_USER_1),
+                market_addr.clone(),
+                &ExecuteMsg::AcceptAdmin {},
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::NoPendingAdmin {}
+        );
+
+        // renouncing needs a fee collector, protocol fees couldn't be withdrawn otherwise
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::RenounceAdmin {});
+        let err = router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::NoFeeCollector {}
+        );
+        set_fee_collector(&mut router, &market_addr);
+
+        // renouncing also drops any pending proposal
+        propose(&mut router, &market_addr, TEST_USER_1, None);
+        router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        let res = query_admin(&router, &market_addr);
+        assert_eq!(res.admin, None);
+        assert_eq!(res.pending_admin, None);
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::CancelAdminProposal {});
+        let err = router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Unauthorized {}
+        );
+    }
+
+    fn query_role_members(router: &App, market_addr: &Addr, role: Role) -> Vec<Addr> {
+        let res: GetRoleMembersResponse = router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetRoleMembers { role: role },
+            )
+            .unwrap();
+        return res.members;
+    }
+
+    /// roles only allow their own messages, and are dropped when the admin renounces
+    #[test]
+    fn admin_roles() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let admin = Addr::unchecked(TEST_ADMIN);
+        let guardian = Addr::unchecked(TEST_USER_1);
+        let fee_manager = Addr::unchecked(TEST_USER_2);
+
+        for (role, address) in [
+            (Role::Guardian, TEST_USER_1),
+            (Role::FeeManager, TEST_USER_2),
+        ] {
+            let msg = ExecuteMsg::Admin(AdminExecuteMsg::GrantRole {
+                role: role,
+                address: address.into(),
+            });
+            router
+                .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+                .unwrap();
+        }
+        assert_eq!(
+            query_role_members(&router, &market_addr, Role::Guardian),
+            vec![guardian.clone()]
+        );
+        assert_eq!(
+            query_role_members(&router, &market_addr, Role::SuperAdmin),
+            Vec::<Addr>::new()
+        );
+
+        // the guardian can pause, but not reactivate the market or touch fees
+        let pause = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketStatus {
+            market_id: 0,
+            status: MarketStatus::Paused,
+        });
+        router
+            .execute_contract(guardian.clone(), market_addr.clone(), &pause, &[])
+            .unwrap();
+
+        let unauthorized = [
+            ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketStatus {
+                market_id: 0,
+                status: MarketStatus::Active,
+            }),
+            ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketFees {
+                market_id: 0,
+                maker_fee_bps: 0,
+                taker_fee_bps: 10,
+            }),
+            ExecuteMsg::Admin(AdminExecuteMsg::GrantRole {
+                role: Role::SuperAdmin,
+                address: TEST_USER_1.into(),
+            }),
+        ];
+        for msg in unauthorized.iter() {
+            let err = router
+                .execute_contract(guardian.clone(), market_addr.clone(), msg, &[])
+                .unwrap_err();
+            assert_eq!(
+                err.downcast::<ContractError>().unwrap(),
+                ContractError::Unauthorized {}
+            );
+        }
+
+        // the fee manager can update fees, but not pause
+        router
+            .execute_contract(
+                fee_manager.clone(),
+                market_addr.clone(),
+                &unauthorized[1],
+                &[],
+            )
+            .unwrap();
+        let err = router
+            .execute_contract(fee_manager.clone(), market_addr.clone(), &pause, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Unauthorized {}
+        );
+
+        // revoked roles are gone, and can't be revoked twice
+        let revoke = ExecuteMsg::Admin(AdminExecuteMsg::RevokeRole {
+            role: Role::Guardian,
+            address: TEST_USER_1.into(),
+        });
+        router
+            .execute_contract(admin.clone(), market_addr.clone(), &revoke, &[])
+            .unwrap();
+        let err = router
+            .execute_contract(admin.clone(), market_addr.clone(), &revoke, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::RoleNotGranted {}
+        );
+        let err = router
+            .execute_contract(guardian.clone(), market_addr.clone(), &pause, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Unauthorized {}
+        );
+
+        // renouncing the admin removes the remaining roles
+        set_fee_collector(&mut router, &market_addr);
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::RenounceAdmin {});
+        router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        assert_eq!(
+            query_role_members(&router, &market_addr, Role::FeeManager),
+            Vec::<Addr>::new()
+        );
+    }
+}
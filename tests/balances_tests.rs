@@ -0,0 +1,287 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{ExecuteMsg, GetMarketBookResponse, GetUserBidsResponse, QueryMsg},
+        structs::CurrencyInfo,
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        create_market_native_only_pair, instantiate_selene, query_internal_balance, CashMachine,
+        NATIVE_DENOM_1, NATIVE_DENOM_2, TEST_USER_1, TEST_USER_2,
+    };
+
+    #[test]
+    fn balances_deposit_and_withdraw() {
+        let (mut router, market_addr) = instantiate_selene();
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+
+        let amount_deposit = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(10000),
+        };
+        router.mint_native(&user_1, amount_deposit.clone());
+
+        let msg = ExecuteMsg::Deposit {};
+        router
+            .execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&amount_deposit),
+            )
+            .unwrap();
+
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            amount_deposit.amount
+        );
+
+        // can't withdraw more than the balance
+        let msg = ExecuteMsg::Withdraw {
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            amount: Uint128::new(10001),
+        };
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InsufficientBalance {}
+        );
+
+        // a cw20 named like the denom doesn't share its balance
+        let msg = ExecuteMsg::Withdraw {
+            currency: CurrencyInfo::Cw20 {
+                address: NATIVE_DENOM_2.into(),
+            },
+            amount: Uint128::new(4000),
+        };
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InsufficientBalance {}
+        );
+
+        // partial withdraw
+        let msg = ExecuteMsg::Withdraw {
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            amount: Uint128::new(4000),
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            Uint128::new(6000)
+        );
+        assert_eq!(
+            router
+                .wrap()
+                .query_balance(user_1.clone(), NATIVE_DENOM_2)
+                .unwrap()
+                .amount,
+            Uint128::new(4000)
+        );
+    }
+
+    /// a maker quoting from its balance gets its proceeds credited
+    /// and can recycle them into a new quote without withdrawing
+    #[test]
+    fn balances_recycle_proceeds_into_new_order() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        // user_1 deposits quote currency and bids from its balance
+        let amount_deposit = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(1000),
+        };
+        router.mint_native(&user_1, amount_deposit.clone());
+
+        let msg = ExecuteMsg::Deposit {};
+        router
+            .execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&amount_deposit),
+            )
+            .unwrap();
+
+        let msg = ExecuteMsg::PlaceOrderFromBalance {
+            market_id: 0,
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            amount: Uint128::new(1000),
+            price: Some(Decimal::one()),
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        assert!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2).is_zero()
+        );
+
+        // user_2 sells into the bid with a market order
+        let amount_order = Coin {
+            denom: NATIVE_DENOM_1.into(),
+            amount: Uint128::new(1000),
+        };
+        router.mint_native(&user_2, amount_order.clone());
+
+        let msg = ExecuteMsg::MarketOrder { market_id: 0 };
+        router
+            .execute_contract(
+                user_2.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&amount_order),
+            )
+            .unwrap();
+
+        // proceeds of the maker are credited, the taker is paid directly
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_1),
+            Uint128::new(1000)
+        );
+        assert_eq!(
+            router
+                .wrap()
+                .query_balance(user_2.clone(), NATIVE_DENOM_2)
+                .unwrap()
+                .amount,
+            Uint128::new(1000)
+        );
+
+        // user_1 recycles its proceeds into an ask
+        let msg = ExecuteMsg::PlaceOrderFromBalance {
+            market_id: 0,
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_1.into(),
+            },
+            amount: Uint128::new(1000),
+            price: Some(Decimal::from_atomics(Uint128::new(11), 1).unwrap()),
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let msg = QueryMsg::GetMarketBook {
+            market_id: 0,
+            nb_levels: 10,
+        };
+        let res: GetMarketBookResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.bids.len(), 0);
+        assert_eq!(res.asks.len(), 1);
+
+        // can't place an order bigger than the balance
+        let msg = ExecuteMsg::PlaceOrderFromBalance {
+            market_id: 0,
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_1.into(),
+            },
+            amount: Uint128::new(1),
+            price: None,
+        };
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InsufficientBalance {}
+        );
+    }
+
+    /// a taker order placed from the balance gets its proceeds credited back
+    #[test]
+    fn balances_taker_from_balance_is_credited() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        // user_1 bids
+        let amount_order = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(500),
+        };
+        router.mint_native(&user_1, amount_order.clone());
+
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: Decimal::one(),
+        };
+        router
+            .execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&amount_order),
+            )
+            .unwrap();
+
+        // user_2 deposits and sells from its balance
+        let amount_deposit = Coin {
+            denom: NATIVE_DENOM_1.into(),
+            amount: Uint128::new(500),
+        };
+        router.mint_native(&user_2, amount_deposit.clone());
+
+        let msg = ExecuteMsg::Deposit {};
+        router
+            .execute_contract(
+                user_2.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&amount_deposit),
+            )
+            .unwrap();
+
+        let msg = ExecuteMsg::PlaceOrderFromBalance {
+            market_id: 0,
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_1.into(),
+            },
+            amount: Uint128::new(500),
+            price: None,
+        };
+        router
+            .execute_contract(user_2.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_2, NATIVE_DENOM_2),
+            Uint128::new(500)
+        );
+        assert!(router
+            .wrap()
+            .query_balance(user_2.clone(), NATIVE_DENOM_2)
+            .unwrap()
+            .amount
+            .is_zero());
+    }
+}
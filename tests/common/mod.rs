@@ -1,25 +1,76 @@
-#![allow(unused)]
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
 
 #[cfg(test)]
 pub mod test_utils {
-    use cosmwasm_std::{Addr, Coin, Empty};
+    use cosmwasm_std::{Addr, Coin, Decimal, Empty, Uint128};
     use cw_multi_test::{App, BankSudo, Contract, ContractWrapper, Executor, SudoMsg};
 
     // You'll need to change the lib name here
     use selene_markets::{
         contract::{execute, instantiate, query},
-        msg::{AdminExecuteMsg, ExecuteMsg, InstantiateMsg},
+        msg::{
+            AdminExecuteMsg, ExecuteMsg, GetEscrowTotalsResponse, GetUserBalancesResponse,
+            InstantiateMsg, QueryMsg,
+        },
         structs::CurrencyInfo,
     };
 
-    pub const TEST_ADMIN: &'static str = "admin";
-    pub const CONTRACT_LABEL: &'static str = "CONTRACT_LABEL";
+    pub const TEST_ADMIN: &str = "admin";
+    pub const CONTRACT_LABEL: &str = "CONTRACT_LABEL";
 
-    pub const TEST_USER_1: &'static str = "user1";
-    pub const TEST_USER_2: &'static str = "user2";
+    pub const TEST_USER_1: &str = "user1";
+    pub const TEST_USER_2: &str = "user2";
 
-    pub const NATIVE_DENOM_1: &'static str = "heur";
-    pub const NATIVE_DENOM_2: &'static str = "husd";
+    pub const NATIVE_DENOM_1: &str = "heur";
+    pub const NATIVE_DENOM_2: &str = "husd";
+
+    pub fn get_cw20_contract() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw20_base::contract::execute,
+            cw20_base::contract::instantiate,
+            cw20_base::contract::query,
+        );
+        Box::new(contract)
+    }
+
+    /// create a cw20 token with the given decimals and no initial balances
+    pub fn instantiate_cw20(router: &mut App, decimals: u8) -> Addr {
+        return instantiate_cw20_with_balances(router, decimals, &[]);
+    }
+
+    pub fn instantiate_cw20_with_balances(
+        router: &mut App,
+        decimals: u8,
+        balances: &[(&Addr, u128)],
+    ) -> Addr {
+        let code_id = router.store_code(get_cw20_contract());
+
+        let msg = cw20_base::msg::InstantiateMsg {
+            name: "Test Token".into(),
+            symbol: "TEST".into(),
+            decimals: decimals,
+            initial_balances: balances
+                .iter()
+                .map(|(address, amount)| cw20::Cw20Coin {
+                    address: address.to_string(),
+                    amount: Uint128::new(*amount),
+                })
+                .collect(),
+            mint: None,
+            marketing: None,
+        };
+
+        return router
+            .instantiate_contract(
+                code_id,
+                Addr::unchecked(TEST_ADMIN),
+                &msg,
+                &[],
+                "cw20",
+                None,
+            )
+            .unwrap();
+    }
 
     pub fn get_contract() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new_with_empty(execute, instantiate, query); //.with_reply(reply);
@@ -30,7 +81,13 @@ pub mod test_utils {
         let mut router = App::default();
         let contract_code_id = router.store_code(get_contract());
 
-        let instantiate_msg = InstantiateMsg {};
+        let instantiate_msg = InstantiateMsg {
+            admin: None,
+            fee_collector: None,
+            default_market_params: None,
+            markets: None,
+            order_limits: None,
+        };
 
         let admin = Addr::unchecked(TEST_ADMIN);
         let instantiate_res = router.instantiate_contract(
@@ -69,6 +126,9 @@ pub mod test_utils {
             quote_currency: CurrencyInfo::Native {
                 denom: NATIVE_DENOM_2.into(),
             },
+            base_decimals: Some(6),
+            quote_decimals: Some(6),
+            allow_inverse: None,
         };
 
         let msg = ExecuteMsg::Admin(admin_msg);
@@ -77,4 +137,108 @@ pub mod test_utils {
             .execute_contract(admin, contract_addr.clone(), &msg, &[])
             .unwrap();
     }
+
+    /// amount of denom in the internal balance of user
+    pub fn query_internal_balance(
+        router: &App,
+        contract_addr: Addr,
+        user: &Addr,
+        denom: &str,
+    ) -> Uint128 {
+        let msg = QueryMsg::GetUserBalances {
+            user_address: user.clone(),
+        };
+        let res: GetUserBalancesResponse =
+            router.wrap().query_wasm_smart(contract_addr, &msg).unwrap();
+
+        return res
+            .balances
+            .into_iter()
+            .find(|coin| coin.denom == denom)
+            .map(|coin| coin.amount)
+            .unwrap_or_default();
+    }
+
+    /// holdings of the contract must be equal to what it owes, for every test denom
+    pub fn assert_holdings_match_escrow(router: &App, contract_addr: Addr) {
+        let msg = QueryMsg::GetEscrowTotals {};
+        let res: GetEscrowTotalsResponse = router
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &msg)
+            .unwrap();
+
+        for denom in [NATIVE_DENOM_1, NATIVE_DENOM_2] {
+            let holdings = router
+                .wrap()
+                .query_balance(contract_addr.clone(), denom)
+                .unwrap()
+                .amount;
+            let owed = res
+                .total
+                .iter()
+                .find(|coin| coin.denom == denom)
+                .map(|coin| coin.amount)
+                .unwrap_or_default();
+
+            assert_eq!(holdings, owed, "holdings mismatch for {}", denom);
+        }
+    }
+
+    /// mint amount of denom to user and send it in an order on market 0
+    /// a limit order if a price is given, else a market order
+    pub fn send_native_order(
+        router: &mut App,
+        contract_addr: &Addr,
+        user: &Addr,
+        denom: &str,
+        amount: u128,
+        price: Option<Decimal>,
+    ) {
+        let coin = Coin {
+            denom: denom.into(),
+            amount: Uint128::new(amount),
+        };
+        router.mint_native(user, coin.clone());
+
+        let msg = match price {
+            Some(price) => ExecuteMsg::LimitOrder {
+                market_id: 0,
+                price: price,
+            },
+            None => ExecuteMsg::MarketOrder { market_id: 0 },
+        };
+        router
+            .execute_contract(user.clone(), contract_addr.clone(), &msg, &[coin])
+            .unwrap();
+    }
+
+    pub fn query_bank_balance(router: &App, user: &Addr, denom: &str) -> Uint128 {
+        return router
+            .wrap()
+            .query_balance(user.clone(), denom)
+            .unwrap()
+            .amount;
+    }
+
+    pub fn set_market_fees(
+        router: &mut App,
+        contract_addr: &Addr,
+        market_id: u64,
+        maker_fee_bps: i16,
+        taker_fee_bps: u16,
+    ) {
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketFees {
+            market_id: market_id,
+            maker_fee_bps: maker_fee_bps,
+            taker_fee_bps: taker_fee_bps,
+        });
+        router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                contract_addr.clone(),
+                &msg,
+                &[],
+            )
+            .unwrap();
+    }
 }
@@ -1,27 +1,85 @@
-#![allow(unused)]
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
 
 mod common;
 
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::Addr;
+    use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
     use cw_multi_test::{App, Executor};
     use selene_markets::{
-        msg::{AdminExecuteMsg, ExecuteMsg, GetMarketsResponse, InstantiateMsg, QueryMsg},
-        structs::CurrencyInfo,
+        msg::{
+            AdminExecuteMsg, ExecuteMsg, GetAdminResponse, GetConfigResponse, GetMarketsResponse,
+            GetUserOrdersResponse, InitialMarket, InstantiateMsg, QueryMsg,
+        },
+        structs::{CurrencyInfo, MarketParams},
+        ContractError,
     };
 
     use crate::common::test_utils::{
-        create_market_native_only_pair, get_contract, instantiate_selene, CONTRACT_LABEL,
-        NATIVE_DENOM_1, NATIVE_DENOM_2, TEST_ADMIN,
+        create_market_native_only_pair, get_contract, instantiate_selene, query_bank_balance,
+        CashMachine, CONTRACT_LABEL, NATIVE_DENOM_1, NATIVE_DENOM_2, TEST_ADMIN, TEST_USER_1,
+        TEST_USER_2,
     };
 
+    fn native(denom: &str) -> CurrencyInfo {
+        return CurrencyInfo::Native {
+            denom: denom.into(),
+        };
+    }
+
+    fn instantiate_configured(
+        router: &mut App,
+        markets: Vec<InitialMarket>,
+    ) -> Result<Addr, ContractError> {
+        let contract_code_id = router.store_code(get_contract());
+
+        let instantiate_msg = InstantiateMsg {
+            admin: Some(TEST_USER_1.into()),
+            fee_collector: Some(TEST_USER_2.into()),
+            default_market_params: Some(MarketParams {
+                tick_size: Some(Decimal::percent(10)),
+                lot_size: Some(Uint128::new(100)),
+                maker_fee_bps: 5,
+                taker_fee_bps: 10,
+            }),
+            markets: Some(markets),
+            order_limits: None,
+        };
+
+        return router
+            .instantiate_contract(
+                contract_code_id,
+                Addr::unchecked(TEST_ADMIN),
+                &instantiate_msg,
+                &[],
+                String::from(CONTRACT_LABEL),
+                Some(TEST_ADMIN.to_owned()),
+            )
+            .map_err(|err| err.downcast::<ContractError>().unwrap());
+    }
+
+    fn initial_market(base_denom: &str, quote_denom: &str) -> InitialMarket {
+        return InitialMarket {
+            base_currency: native(base_denom),
+            quote_currency: native(quote_denom),
+            base_decimals: Some(6),
+            quote_decimals: Some(6),
+            allow_inverse: None,
+        };
+    }
+
     #[test]
     fn test_successful_deployment() {
         let mut router = App::default();
         let contract_code_id = router.store_code(get_contract());
 
-        let instantiate_msg = InstantiateMsg {};
+        let instantiate_msg = InstantiateMsg {
+            admin: None,
+            fee_collector: None,
+            default_market_params: None,
+            markets: None,
+            order_limits: None,
+        };
 
         let admin = Addr::unchecked(TEST_ADMIN);
         let instantiate_res = router.instantiate_contract(
@@ -35,7 +93,7 @@ mod tests {
 
         match instantiate_res {
             Ok(_contract_address) => (),
-            Err(err) => panic!("Failed to instantiate contract: {}", err.to_string()),
+            Err(err) => panic!("Failed to instantiate contract: {}", err),
         }
     }
 
@@ -45,7 +103,10 @@ mod tests {
         create_market_native_only_pair(&mut router, market_addr.clone());
 
         // query markets, check if only one
-        let msg = QueryMsg::GetMarkets {};
+        let msg = QueryMsg::GetMarkets {
+            start_after: None,
+            limit: None,
+        };
         let res: GetMarketsResponse = router
             .wrap()
             .query_wasm_smart(market_addr.clone(), &msg)
@@ -53,4 +114,183 @@ mod tests {
 
         assert_eq!(res.markets.len(), 1);
     }
+
+    /// admin, fee collector and default parameters are set, and initial markets are created with them
+    #[test]
+    fn test_configured_deployment() {
+        let mut router = App::default();
+        let market_addr = instantiate_configured(
+            &mut router,
+            vec![
+                initial_market(NATIVE_DENOM_1, NATIVE_DENOM_2),
+                initial_market("hgbp", NATIVE_DENOM_2),
+            ],
+        )
+        .unwrap();
+
+        let res: GetAdminResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetAdmin {})
+            .unwrap();
+        assert_eq!(res.admin, Some(Addr::unchecked(TEST_USER_1)));
+
+        let res: GetConfigResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetConfig {})
+            .unwrap();
+        assert_eq!(res.fee_collector, Some(Addr::unchecked(TEST_USER_2)));
+        assert_eq!(res.default_market_params.taker_fee_bps, 10);
+
+        let res: GetMarketsResponse = router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetMarkets {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(res.markets.len(), 2);
+        for market in res.markets {
+            assert_eq!(market.maker_fee_bps, 5);
+            assert_eq!(market.taker_fee_bps, 10);
+            assert_eq!(market.tick_size, Some(Decimal::percent(10)));
+            assert_eq!(market.lot_size, Some(Uint128::new(100)));
+        }
+
+        // the sender is not the admin
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateFeeCollector {
+            fee_collector: None,
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Unauthorized {}
+        );
+    }
+
+    /// an invalid initial market fails the whole instantiation
+    #[test]
+    fn test_configured_deployment_invalid_market() {
+        let mut router = App::default();
+        let err = instantiate_configured(
+            &mut router,
+            vec![
+                initial_market(NATIVE_DENOM_1, NATIVE_DENOM_2),
+                initial_market(NATIVE_DENOM_1, NATIVE_DENOM_2),
+            ],
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::MarketAlreadyExists { id: 0 });
+    }
+
+    /// limit prices must be on a tick, and resting quantities are rounded down to whole lots
+    /// a bid whose base quantity doesn't fit at a tiny price is rejected with an error
+    #[test]
+    fn test_tiny_price_overflow() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user = Addr::unchecked(TEST_USER_1);
+        let coin = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(10u128.pow(21)),
+        };
+        router.mint_native(&user, coin.clone());
+
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: Decimal::raw(1),
+        };
+        let err = router
+            .execute_contract(user.clone(), market_addr.clone(), &msg, &[coin])
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Std(_)
+        ));
+    }
+
+    #[test]
+    fn test_tick_and_lot_sizes() {
+        let mut router = App::default();
+        let market_addr = instantiate_configured(
+            &mut router,
+            vec![initial_market(NATIVE_DENOM_1, NATIVE_DENOM_2)],
+        )
+        .unwrap();
+        let user = Addr::unchecked(TEST_USER_1);
+
+        let coin = Coin {
+            denom: NATIVE_DENOM_1.into(),
+            amount: Uint128::new(1050),
+        };
+        router.mint_native(&user, coin.clone());
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: Decimal::permille(1005),
+        };
+        let err = router
+            .execute_contract(
+                user.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&coin),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::PriceNotOnTick {
+                tick_size: Decimal::percent(10),
+            }
+        );
+
+        // a zero price is on every tick, but would block all takers of the other side
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: Decimal::zero(),
+        };
+        let err = router
+            .execute_contract(
+                user.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&coin),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::ZeroPrice {}
+        );
+
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: Decimal::percent(110),
+        };
+        router
+            .execute_contract(user.clone(), market_addr.clone(), &msg, &[coin])
+            .unwrap();
+
+        let res: GetUserOrdersResponse = router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetUserOrders {
+                    user_address: user.clone(),
+                    target_market: Some(0),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(res.orders[0].order.quantity, Uint128::new(1000));
+        // what is above the last lot is sent back
+        assert_eq!(
+            query_bank_balance(&router, &user, NATIVE_DENOM_1),
+            Uint128::new(50)
+        );
+    }
 }
@@ -0,0 +1,392 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{to_binary, Addr, Coin, Decimal, Uint128};
+    use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{
+            AdminExecuteMsg, ExecuteMsg, GetEscrowTotalsResponse, GetUserBidsResponse, QueryMsg,
+            SeleneCw20Msg,
+        },
+        structs::CurrencyInfo,
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        assert_holdings_match_escrow, create_market_native_only_pair,
+        instantiate_cw20_with_balances, instantiate_selene, query_bank_balance,
+        query_internal_balance, send_native_order, CashMachine, NATIVE_DENOM_1, NATIVE_DENOM_2,
+        TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    /// quote funds which can't be converted to a whole base quantity are refunded when the bid is placed
+    #[test]
+    fn escrow_bid_dust_refunded() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+
+        // 100 at price 3 buys 33, which locks 99
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            100,
+            Some(Decimal::from_atomics(Uint128::new(3), 0).unwrap()),
+        );
+
+        assert_eq!(
+            query_bank_balance(&router, &user_1, NATIVE_DENOM_2),
+            Uint128::new(1)
+        );
+
+        let msg = QueryMsg::GetUserBids {
+            user_address: user_1.clone(),
+            target_market: Some(0),
+            start_after: None,
+            limit: None,
+        };
+        let res: GetUserBidsResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.orders[0].quantity, Uint128::new(33));
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // cancelling gives back exactly what was locked
+        let msg = ExecuteMsg::RemoveLimitOrder {
+            market_id: 0,
+            price: Decimal::from_atomics(Uint128::new(3), 0).unwrap(),
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            Uint128::new(99)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+    }
+
+    /// a bid too small to buy a single unit is rejected
+    #[test]
+    fn escrow_bid_too_small() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let coin = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(2),
+        };
+        router.mint_native(&user_1, coin.clone());
+
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: Decimal::from_atomics(Uint128::new(3), 0).unwrap(),
+        };
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[coin])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::OrderTooSmall {}
+        );
+    }
+
+    /// trade through levels with prices that don't divide amounts evenly
+    /// the contract must hold exactly what it owes after each step
+    #[test]
+    fn escrow_holdings_match_after_fills_and_cancels() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        let price_0_3 = Decimal::from_atomics(Uint128::new(3), 1).unwrap();
+        let price_0_7 = Decimal::from_atomics(Uint128::new(7), 1).unwrap();
+        let price_1_3 = Decimal::from_atomics(Uint128::new(13), 1).unwrap();
+        let price_1_5 = Decimal::from_atomics(Uint128::new(15), 1).unwrap();
+
+        // bids of 142 at 0.7 locking 100, and 166 at 0.3 locking 50
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            100,
+            Some(price_0_7),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            50,
+            Some(price_0_3),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            37,
+            Some(price_1_3),
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // sell 150: 142 at 0.7 for 99, then 8 at 0.3 for 2
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            150,
+            None,
+        );
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_2),
+            Uint128::new(101)
+        );
+        // maker gets the base bought, and the rounding dust of the fully filled bid
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_1),
+            Uint128::new(150)
+        );
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            Uint128::new(1)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // market buy with 40: buys 30 at 1.3 for 39, 1 is refunded
+        send_native_order(&mut router, &market_addr, &user_1, NATIVE_DENOM_2, 40, None);
+        assert_eq!(
+            query_bank_balance(&router, &user_1, NATIVE_DENOM_1),
+            Uint128::new(30)
+        );
+        assert_eq!(
+            query_bank_balance(&router, &user_1, NATIVE_DENOM_2),
+            Uint128::new(1)
+        );
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_2, NATIVE_DENOM_2),
+            Uint128::new(39)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // limit buy with 20 at 1.5: buys the last 7 at 1.3 for 9, then rests 7 at 1.5 locking 11
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            20,
+            Some(price_1_5),
+        );
+        assert_eq!(
+            query_bank_balance(&router, &user_1, NATIVE_DENOM_1),
+            Uint128::new(37)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // cancel what is left of the bid at 0.3, 158 locking 48
+        let msg = ExecuteMsg::RemoveLimitOrder {
+            market_id: 0,
+            price: price_0_3,
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            Uint128::new(49)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+    }
+
+    /// orders record what they lock in their deposit currency
+    /// a partially filled bid is cancelled for exactly what it still locks
+    #[test]
+    fn escrow_cancel_partially_filled_bid() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        let price = Decimal::from_atomics(Uint128::new(7), 1).unwrap();
+
+        // 142 at 0.7, locking 100
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            100,
+            Some(price),
+        );
+
+        // sell 10 for 7
+        send_native_order(&mut router, &market_addr, &user_2, NATIVE_DENOM_1, 10, None);
+
+        let msg = QueryMsg::GetUserBids {
+            user_address: user_1.clone(),
+            target_market: Some(0),
+            start_after: None,
+            limit: None,
+        };
+        let res: GetUserBidsResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.orders[0].quantity, Uint128::new(132));
+        assert_eq!(res.orders[0].locked, Uint128::new(93));
+
+        let msg = ExecuteMsg::RemoveLimitOrder {
+            market_id: 0,
+            price: price,
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            Uint128::new(93)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+    }
+
+    /// totals owed must match what the contract holds in the bank and in the cw20 token
+    fn assert_holdings_match_escrow_totals(router: &App, contract_addr: &Addr, token: &Addr) {
+        let res: GetEscrowTotalsResponse = router
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetEscrowTotals {})
+            .unwrap();
+        let owed = |denom: &str| {
+            res.total
+                .iter()
+                .find(|coin| coin.denom == denom)
+                .map(|coin| coin.amount)
+                .unwrap_or_default()
+        };
+
+        let token_holdings: BalanceResponse = router
+            .wrap()
+            .query_wasm_smart(
+                token.clone(),
+                &Cw20QueryMsg::Balance {
+                    address: contract_addr.to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(token_holdings.balance, owed(token.as_str()));
+
+        let bank_holdings = router
+            .wrap()
+            .query_balance(contract_addr.clone(), NATIVE_DENOM_2)
+            .unwrap()
+            .amount;
+        assert_eq!(bank_holdings, owed(NATIVE_DENOM_2));
+    }
+
+    /// a cw20 base against a native quote, through a fill, a cancel and withdrawals
+    #[test]
+    fn escrow_totals_match_cw20_and_bank_holdings() {
+        let (mut router, market_addr) = instantiate_selene();
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+        let token = instantiate_cw20_with_balances(&mut router, 6, &[(&user_1, 1000)]);
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::AddMarket {
+            base_currency: CurrencyInfo::Cw20 {
+                address: token.to_string(),
+            },
+            quote_currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            base_decimals: None,
+            quote_decimals: Some(6),
+            allow_inverse: None,
+        });
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // ask of 1000 tokens at 2
+        let msg = Cw20ExecuteMsg::Send {
+            contract: market_addr.to_string(),
+            amount: Uint128::new(1000),
+            msg: to_binary(&SeleneCw20Msg::LimitOrder {
+                market_id: 0,
+                price: Decimal::from_atomics(Uint128::new(2), 0).unwrap(),
+            })
+            .unwrap(),
+        };
+        router
+            .execute_contract(user_1.clone(), token.clone(), &msg, &[])
+            .unwrap();
+        assert_holdings_match_escrow_totals(&router, &market_addr, &token);
+
+        // market buy of 400 tokens for 800
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_2,
+            800,
+            None,
+        );
+        assert_holdings_match_escrow_totals(&router, &market_addr, &token);
+
+        // the rest of the ask is refunded to the internal balance
+        let msg = ExecuteMsg::RemoveLimitOrder {
+            market_id: 0,
+            price: Decimal::from_atomics(Uint128::new(2), 0).unwrap(),
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        assert_holdings_match_escrow_totals(&router, &market_addr, &token);
+
+        for (currency, amount) in [
+            (
+                CurrencyInfo::Cw20 {
+                    address: token.to_string(),
+                },
+                600,
+            ),
+            (
+                CurrencyInfo::Native {
+                    denom: NATIVE_DENOM_2.into(),
+                },
+                800,
+            ),
+        ] {
+            let msg = ExecuteMsg::Withdraw {
+                currency: currency,
+                amount: Uint128::new(amount),
+            };
+            router
+                .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+                .unwrap();
+            assert_holdings_match_escrow_totals(&router, &market_addr, &token);
+        }
+
+        // nothing is owed anymore, and the contract holds nothing
+        let res: GetEscrowTotalsResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetEscrowTotals {})
+            .unwrap();
+        assert_eq!(res.total, vec![]);
+    }
+}
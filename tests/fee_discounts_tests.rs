@@ -0,0 +1,229 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{to_binary, Addr, Decimal, Uint128};
+    use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{
+            AdminExecuteMsg, ExecuteMsg, GetEscrowTotalsResponse, GetUserFeeTokenLockResponse,
+            QueryMsg, SeleneCw20Msg,
+        },
+        structs::FeeDiscount,
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        create_market_native_only_pair, instantiate_cw20_with_balances, instantiate_selene,
+        query_bank_balance, send_native_order, set_market_fees, NATIVE_DENOM_1, NATIVE_DENOM_2,
+        TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    const UNBONDING_SECONDS: u64 = 7 * 86400;
+
+    fn setup_fee_token(router: &mut App, contract_addr: &Addr, user: &Addr) -> Addr {
+        let token = instantiate_cw20_with_balances(router, 6, &[(user, 5000)]);
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateFeeDiscounts {
+            token: token.to_string(),
+            unbonding_seconds: UNBONDING_SECONDS,
+            discounts: vec![
+                FeeDiscount {
+                    min_locked: Uint128::new(1000),
+                    discount_bps: 2500,
+                },
+                FeeDiscount {
+                    min_locked: Uint128::new(5000),
+                    discount_bps: 5000,
+                },
+            ],
+        });
+        router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                contract_addr.clone(),
+                &msg,
+                &[],
+            )
+            .unwrap();
+
+        return token;
+    }
+
+    fn lock_fee_token(
+        router: &mut App,
+        contract_addr: &Addr,
+        token: &Addr,
+        user: &Addr,
+        amount: u128,
+    ) {
+        let msg = Cw20ExecuteMsg::Send {
+            contract: contract_addr.to_string(),
+            amount: Uint128::new(amount),
+            msg: to_binary(&SeleneCw20Msg::LockFeeToken {}).unwrap(),
+        };
+        router
+            .execute_contract(user.clone(), token.clone(), &msg, &[])
+            .unwrap();
+    }
+
+    fn query_cw20_balance(router: &App, token: &Addr, user: &Addr) -> Uint128 {
+        let res: BalanceResponse = router
+            .wrap()
+            .query_wasm_smart(
+                token.clone(),
+                &Cw20QueryMsg::Balance {
+                    address: user.to_string(),
+                },
+            )
+            .unwrap();
+        return res.balance;
+    }
+
+    /// locked fee tokens discount the taker fee, and are given back after unbonding
+    #[test]
+    fn fee_discounts_lock_and_unbond() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, 0, 20);
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        let token = setup_fee_token(&mut router, &market_addr, &user_2);
+        lock_fee_token(&mut router, &market_addr, &token, &user_2, 1000);
+
+        let msg = QueryMsg::GetUserFeeTokenLock {
+            user_address: user_2.clone(),
+        };
+        let res: GetUserFeeTokenLockResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.locked, Uint128::new(1000));
+        assert_eq!(res.taker_discount_bps, 2500);
+
+        // taker fee of 15 bps instead of 20
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            10000,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            10000,
+            None,
+        );
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_2),
+            Uint128::new(9985)
+        );
+
+        // unlocked tokens stop counting right away, but are only released after the unbonding period
+        let msg = ExecuteMsg::UnlockFeeToken {
+            amount: Uint128::new(1000),
+        };
+        router
+            .execute_contract(user_2.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let res: GetUserFeeTokenLockResponse = router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetUserFeeTokenLock {
+                    user_address: user_2.clone(),
+                },
+            )
+            .unwrap();
+        assert!(res.locked.is_zero());
+        assert_eq!(res.unbonding[0].amount, Uint128::new(1000));
+        assert_eq!(res.taker_discount_bps, 0);
+
+        let res: GetEscrowTotalsResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetEscrowTotals {})
+            .unwrap();
+        assert_eq!(res.fee_token_locks[0].amount, Uint128::new(1000));
+        assert_eq!(
+            query_cw20_balance(&router, &token, &market_addr),
+            Uint128::new(1000)
+        );
+
+        let msg = ExecuteMsg::ClaimUnlockedFeeToken {};
+        let err = router
+            .execute_contract(user_2.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::ZeroAmount {}
+        );
+
+        router.update_block(|block| block.time = block.time.plus_seconds(UNBONDING_SECONDS));
+        router
+            .execute_contract(user_2.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        assert_eq!(
+            query_cw20_balance(&router, &token, &user_2),
+            Uint128::new(5000)
+        );
+    }
+
+    /// only the designated token can be locked
+    #[test]
+    fn fee_discounts_other_token_rejected() {
+        let (mut router, market_addr) = instantiate_selene();
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        setup_fee_token(&mut router, &market_addr, &user_1);
+        let other_token = instantiate_cw20_with_balances(&mut router, 6, &[(&user_1, 5000)]);
+
+        let msg = Cw20ExecuteMsg::Send {
+            contract: market_addr.to_string(),
+            amount: Uint128::new(1000),
+            msg: to_binary(&SeleneCw20Msg::LockFeeToken {}).unwrap(),
+        };
+        let err = router
+            .execute_contract(user_1.clone(), other_token.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidFeeToken {}
+        );
+    }
+
+    /// the largest discount can't leave the taker fee below a maker rebate
+    #[test]
+    fn fee_discounts_keep_rebates_covered() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        setup_fee_token(&mut router, &market_addr, &user_1);
+
+        // 50% off a taker fee of 20 leaves 10, which can't pay a rebate of 11
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketFees {
+            market_id: 0,
+            maker_fee_bps: -11,
+            taker_fee_bps: 20,
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::RebateNotCovered {}
+        );
+
+        set_market_fees(&mut router, &market_addr, 0, -10, 20);
+    }
+}
@@ -0,0 +1,162 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Decimal, Uint128};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{AdminExecuteMsg, ExecuteMsg, GetUserFeeTierResponse, QueryMsg},
+        structs::{FeeTier, VolumeScope},
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        assert_holdings_match_escrow, create_market_native_only_pair, instantiate_selene,
+        query_bank_balance, send_native_order, set_market_fees, NATIVE_DENOM_1, NATIVE_DENOM_2,
+        TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    fn query_user_fee_tier(
+        router: &App,
+        contract_addr: &Addr,
+        user: &Addr,
+    ) -> GetUserFeeTierResponse {
+        let msg = QueryMsg::GetUserFeeTier {
+            user_address: user.clone(),
+            market_id: 0,
+        };
+        return router
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &msg)
+            .unwrap();
+    }
+
+    /// the taker reaches a tier once it traded enough over the window, and loses it when the volume gets old
+    #[test]
+    fn fee_tiers_discount_follows_volume() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, 10, 20);
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateFeeTiers {
+            window_days: 30,
+            scope: VolumeScope::Market,
+            tiers: vec![FeeTier {
+                min_volume: Uint128::new(10000),
+                maker_fee_bps: 5,
+                taker_fee_bps: 10,
+            }],
+        });
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        // first trade at market fees, taker pays 20 on 10000
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            10000,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            10000,
+            None,
+        );
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_2),
+            Uint128::new(9980)
+        );
+
+        let res = query_user_fee_tier(&router, &market_addr, &user_2);
+        assert_eq!(res.tier, Some(0));
+        assert_eq!(res.volume, Uint128::new(10000));
+        assert_eq!(res.window_days, 30);
+        assert_eq!(res.taker_fee_bps, 10);
+
+        // second trade at the tier fees, taker pays 10 on 10000
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            10000,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            10000,
+            None,
+        );
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_2),
+            Uint128::new(9980 + 9990)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // volume leaves the window
+        router.update_block(|block| block.time = block.time.plus_seconds(31 * 86400));
+        let res = query_user_fee_tier(&router, &market_addr, &user_2);
+        assert_eq!(res.tier, None);
+        assert!(res.volume.is_zero());
+        assert_eq!(res.taker_fee_bps, 20);
+
+        // unknown markets are an error of the query
+        let msg = QueryMsg::GetUserFeeTier {
+            user_address: user_2.clone(),
+            market_id: 5,
+        };
+        let res: Result<GetUserFeeTierResponse, _> =
+            router.wrap().query_wasm_smart(market_addr.clone(), &msg);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn fee_tiers_must_be_sorted() {
+        let (mut router, market_addr) = instantiate_selene();
+
+        let tier = FeeTier {
+            min_volume: Uint128::new(10000),
+            maker_fee_bps: 5,
+            taker_fee_bps: 10,
+        };
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateFeeTiers {
+            window_days: 30,
+            scope: VolumeScope::Market,
+            tiers: vec![tier.clone(), tier],
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidFeeTiers {}
+        );
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateFeeTiers {
+            window_days: 0,
+            scope: VolumeScope::Market,
+            tiers: vec![],
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidVolumeWindow { max_days: 365 }
+        );
+    }
+}
@@ -0,0 +1,298 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{AdminExecuteMsg, ExecuteMsg, GetProtocolFeesResponse, QueryMsg},
+        structs::{CurrencyInfo, FeeTier, VolumeScope},
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        assert_holdings_match_escrow, create_market_native_only_pair, instantiate_selene,
+        query_bank_balance, query_internal_balance, send_native_order, set_market_fees,
+        NATIVE_DENOM_1, NATIVE_DENOM_2, TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    /// maker and taker are charged on what they receive, the protocol accrues the fees
+    #[test]
+    fn fees_charged_on_proceeds() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, 10, 20);
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            10000,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            10000,
+            None,
+        );
+
+        // taker pays 0.2% of the quote received, maker 0.1% of the base received
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_2),
+            Uint128::new(9980)
+        );
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_1),
+            Uint128::new(9990)
+        );
+
+        let res: GetProtocolFeesResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetProtocolFees {})
+            .unwrap();
+        assert_eq!(
+            res.fees,
+            vec![
+                Coin {
+                    denom: NATIVE_DENOM_1.into(),
+                    amount: Uint128::new(10),
+                },
+                Coin {
+                    denom: NATIVE_DENOM_2.into(),
+                    amount: Uint128::new(20),
+                },
+            ]
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // only the admin withdraws fees
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::WithdrawFees {
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            recipient: Some(TEST_USER_1.into()),
+        });
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Unauthorized {}
+        );
+
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+        assert_eq!(
+            query_bank_balance(&router, &user_1, NATIVE_DENOM_2),
+            Uint128::new(20)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // nothing left to withdraw
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::ZeroAmount {}
+        );
+    }
+
+    /// once the admin is renounced, fees can still be withdrawn to the fee collector
+    #[test]
+    fn fees_withdrawn_after_renounce() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, 10, 20);
+
+        let admin = Addr::unchecked(TEST_ADMIN);
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+        let fee_collector = Addr::unchecked("fee_collector");
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            10000,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            10000,
+            None,
+        );
+
+        for msg in [
+            AdminExecuteMsg::UpdateFeeCollector {
+                fee_collector: Some(fee_collector.to_string()),
+            },
+            AdminExecuteMsg::RenounceAdmin {},
+        ] {
+            router
+                .execute_contract(
+                    admin.clone(),
+                    market_addr.clone(),
+                    &ExecuteMsg::Admin(msg),
+                    &[],
+                )
+                .unwrap();
+        }
+
+        // fees can't be sent anywhere else
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::WithdrawFees {
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            recipient: Some(TEST_USER_1.into()),
+        });
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::Unauthorized {}
+        );
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::WithdrawFees {
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            recipient: None,
+        });
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        assert_eq!(
+            query_bank_balance(&router, &fee_collector, NATIVE_DENOM_2),
+            Uint128::new(20)
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+    }
+
+    #[test]
+    fn fees_capped() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketFees {
+            market_id: 0,
+            maker_fee_bps: 0,
+            taker_fee_bps: 1001,
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidFee { max_bps: 1000 }
+        );
+    }
+
+    /// a negative maker fee is paid to the maker, out of the taker fee, in the currency the maker sold
+    #[test]
+    fn fees_maker_rebate() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, -5, 20);
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            10000,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            10000,
+            None,
+        );
+
+        // taker pays 20, maker gets all the base it bought and a rebate of 5 quote
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_2),
+            Uint128::new(9980)
+        );
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_1),
+            Uint128::new(10000)
+        );
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            Uint128::new(5)
+        );
+
+        let res: GetProtocolFeesResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetProtocolFees {})
+            .unwrap();
+        assert_eq!(
+            res.fees,
+            vec![Coin {
+                denom: NATIVE_DENOM_2.into(),
+                amount: Uint128::new(15),
+            }]
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+    }
+
+    #[test]
+    fn fees_rebate_must_be_covered() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketFees {
+            market_id: 0,
+            maker_fee_bps: -21,
+            taker_fee_bps: 20,
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::RebateNotCovered {}
+        );
+
+        // a tier with a lower taker fee can't undercut the rebate of a market either
+        set_market_fees(&mut router, &market_addr, 0, -10, 20);
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateFeeTiers {
+            window_days: 30,
+            scope: VolumeScope::Market,
+            tiers: vec![FeeTier {
+                min_volume: Uint128::new(10000),
+                maker_fee_bps: 0,
+                taker_fee_bps: 5,
+            }],
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::RebateNotCovered {}
+        );
+    }
+}
@@ -0,0 +1,280 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Coin, Uint128};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{AdminExecuteMsg, ExecuteMsg, GetMarketsResponse, GetProtocolFeesResponse, QueryMsg},
+        structs::{CurrencyInfo, ListingFee, MarketStatus},
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        assert_holdings_match_escrow, create_market_native_only_pair,
+        instantiate_cw20_with_balances, instantiate_selene, CashMachine, NATIVE_DENOM_1,
+        NATIVE_DENOM_2, TEST_ADMIN, TEST_USER_1,
+    };
+
+    fn create_market_msg(base: &Addr, quote: &Addr) -> ExecuteMsg {
+        return ExecuteMsg::CreateMarket {
+            base_currency: CurrencyInfo::Cw20 {
+                address: base.to_string(),
+            },
+            quote_currency: CurrencyInfo::Cw20 {
+                address: quote.to_string(),
+            },
+            base_decimals: None,
+            quote_decimals: None,
+        };
+    }
+
+    fn set_listing_fee(router: &mut App, contract_addr: &Addr) {
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateListingFee {
+            listing_fee: Some(ListingFee {
+                currency: CurrencyInfo::Native {
+                    denom: NATIVE_DENOM_2.into(),
+                },
+                amount: Uint128::new(1000),
+            }),
+        });
+        router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                contract_addr.clone(),
+                &msg,
+                &[],
+            )
+            .unwrap();
+    }
+
+    fn query_markets(router: &App, contract_addr: &Addr) -> GetMarketsResponse {
+        return router
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::GetMarkets {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+    }
+
+    /// anyone can list a market by paying the exact listing fee, which is added to the protocol fees
+    #[test]
+    fn listing_with_fee() {
+        let (mut router, market_addr) = instantiate_selene();
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let base_token = instantiate_cw20_with_balances(&mut router, 6, &[]);
+        let quote_token = instantiate_cw20_with_balances(&mut router, 8, &[]);
+
+        let fee = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(1000),
+        };
+        router.mint_native(&user_1, fee.clone());
+
+        // only the admin can add markets until a listing fee is set
+        let err = router
+            .execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &create_market_msg(&base_token, &quote_token),
+                std::slice::from_ref(&fee),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::ListingDisabled {}
+        );
+
+        // the fee currency is validated like the currencies of markets
+        for currency in [
+            CurrencyInfo::Native { denom: " ".into() },
+            CurrencyInfo::Cw20 {
+                address: "Not A Token".into(),
+            },
+        ] {
+            let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateListingFee {
+                listing_fee: Some(ListingFee {
+                    currency: currency,
+                    amount: Uint128::new(1000),
+                }),
+            });
+            let err = router
+                .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(
+                err.downcast::<ContractError>().unwrap(),
+                ContractError::InvalidCurrency {}
+            );
+        }
+
+        set_listing_fee(&mut router, &market_addr);
+
+        // native decimals can't be checked, so only market listers list native currencies
+        let msg = ExecuteMsg::CreateMarket {
+            base_currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_1.into(),
+            },
+            quote_currency: CurrencyInfo::Cw20 {
+                address: quote_token.to_string(),
+            },
+            base_decimals: Some(6),
+            quote_decimals: None,
+        };
+        let err = router
+            .execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&fee),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::NativeListingRestricted {}
+        );
+
+        let err = router
+            .execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &create_market_msg(&base_token, &quote_token),
+                &[Coin {
+                    denom: NATIVE_DENOM_2.into(),
+                    amount: Uint128::new(999),
+                }],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidListingFee {
+                amount: Uint128::new(1000),
+                denom: NATIVE_DENOM_2.into(),
+            }
+        );
+
+        router
+            .execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &create_market_msg(&base_token, &quote_token),
+                std::slice::from_ref(&fee),
+            )
+            .unwrap();
+
+        let res = query_markets(&router, &market_addr);
+        assert_eq!(res.markets.len(), 1);
+        // decimals of cw20 tokens are read from the tokens
+        assert_eq!(res.markets[0].base_decimals, 6);
+        assert_eq!(res.markets[0].quote_decimals, 8);
+
+        let res: GetProtocolFeesResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetProtocolFees {})
+            .unwrap();
+        assert_eq!(res.fees, vec![fee]);
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // the admin keeps control of listed markets
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketStatus {
+            market_id: 0,
+            status: MarketStatus::Delisted,
+        });
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+    }
+
+    /// delisting a market frees its pair, so a squatted pair can be listed again
+    #[test]
+    fn listing_relist_delisted_pair() {
+        let (mut router, market_addr) = instantiate_selene();
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let base_token = instantiate_cw20_with_balances(&mut router, 6, &[]);
+        let quote_token = instantiate_cw20_with_balances(&mut router, 6, &[]);
+        set_listing_fee(&mut router, &market_addr);
+
+        let fee = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(1000),
+        };
+        let mut create_market = |router: &mut App, base: &Addr, quote: &Addr| {
+            router.mint_native(&user_1, fee.clone());
+            return router.execute_contract(
+                user_1.clone(),
+                market_addr.clone(),
+                &create_market_msg(base, quote),
+                std::slice::from_ref(&fee),
+            );
+        };
+
+        create_market(&mut router, &base_token, &quote_token).unwrap();
+        let err = create_market(&mut router, &base_token, &quote_token).unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::MarketAlreadyExists { id: 0 }
+        );
+        let err = create_market(&mut router, &quote_token, &base_token).unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InverseMarketExists { id: 0 }
+        );
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketStatus {
+            market_id: 0,
+            status: MarketStatus::Delisted,
+        });
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        create_market(&mut router, &base_token, &quote_token).unwrap();
+        let res = query_markets(&router, &market_addr);
+        assert_eq!(res.markets.len(), 2);
+        assert_eq!(res.markets[1].status, MarketStatus::Active);
+        assert_holdings_match_escrow(&router, market_addr.clone());
+    }
+
+    /// market listers can correct the decimals of a market
+    #[test]
+    fn listing_update_market_decimals() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        // decimals of native currencies must be given
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketDecimals {
+            market_id: 0,
+            base_decimals: Some(8),
+            quote_decimals: None,
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::MissingDecimals {}
+        );
+
+        // only market listers can change them
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketDecimals {
+            market_id: 0,
+            base_decimals: Some(8),
+            quote_decimals: Some(2),
+        });
+        router
+            .execute_contract(Addr::unchecked(TEST_USER_1), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let res = query_markets(&router, &market_addr);
+        assert_eq!(res.markets[0].base_decimals, 8);
+        assert_eq!(res.markets[0].quote_decimals, 2);
+    }
+}
@@ -0,0 +1,233 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{
+            AdminExecuteMsg, ExecuteMsg, GetMarketBookResponse, GetOrderResponse,
+            GetUserOrdersResponse, QueryMsg,
+        },
+        structs::{MarketStatus, OrderStatus},
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        assert_holdings_match_escrow, create_market_native_only_pair, instantiate_selene,
+        query_internal_balance, send_native_order, CashMachine, NATIVE_DENOM_1, NATIVE_DENOM_2,
+        TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    fn set_market_status(router: &mut App, contract_addr: &Addr, status: MarketStatus) {
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketStatus {
+            market_id: 0,
+            status: status,
+        });
+        router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                contract_addr.clone(),
+                &msg,
+                &[],
+            )
+            .unwrap();
+    }
+
+    /// cancel-only markets refuse new orders but let users cancel, paused markets refuse both
+    #[test]
+    fn market_status_cancel_only_and_paused() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let price = Decimal::one();
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price),
+        );
+
+        set_market_status(&mut router, &market_addr, MarketStatus::CancelOnly);
+
+        let coin = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(1000),
+        };
+        router.mint_native(&user_1, coin.clone());
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: price,
+        };
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[coin])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::MarketNotActive { id: 0 }
+        );
+
+        set_market_status(&mut router, &market_addr, MarketStatus::Paused);
+
+        let cancel_msg = ExecuteMsg::RemoveLimitOrder {
+            market_id: 0,
+            price: price,
+        };
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &cancel_msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::MarketPaused { id: 0 }
+        );
+
+        set_market_status(&mut router, &market_addr, MarketStatus::CancelOnly);
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &cancel_msg, &[])
+            .unwrap();
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            Uint128::new(1000)
+        );
+    }
+
+    /// delisting refunds the whole book in batches, and can't be undone
+    #[test]
+    fn market_status_delisted_refunds_book() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_2,
+            500,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            300,
+            Some(Decimal::from_atomics(Uint128::new(2), 0).unwrap()),
+        );
+
+        // only delisted markets are refunded
+        let refund_msg = ExecuteMsg::Admin(AdminExecuteMsg::RefundDelistedOrders {
+            market_id: 0,
+            limit: Some(2),
+        });
+        let err = router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                market_addr.clone(),
+                &refund_msg,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::MarketNotDelisted { id: 0 }
+        );
+
+        set_market_status(&mut router, &market_addr, MarketStatus::Delisted);
+
+        // first batch refunds both bids
+        router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                market_addr.clone(),
+                &refund_msg,
+                &[],
+            )
+            .unwrap();
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2),
+            Uint128::new(1000)
+        );
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_2, NATIVE_DENOM_2),
+            Uint128::new(500)
+        );
+        assert!(
+            query_internal_balance(&router, market_addr.clone(), &user_2, NATIVE_DENOM_1).is_zero()
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // second batch refunds the ask
+        router
+            .execute_contract(
+                Addr::unchecked(TEST_ADMIN),
+                market_addr.clone(),
+                &refund_msg,
+                &[],
+            )
+            .unwrap();
+        assert_eq!(
+            query_internal_balance(&router, market_addr.clone(), &user_2, NATIVE_DENOM_1),
+            Uint128::new(300)
+        );
+        let res: GetOrderResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetOrder { order_id: 2 })
+            .unwrap();
+        assert_eq!(res.status, OrderStatus::Expired);
+
+        let res: GetMarketBookResponse = router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetMarketBook {
+                    market_id: 0,
+                    nb_levels: 10,
+                },
+            )
+            .unwrap();
+        assert!(res.bids.is_empty() && res.asks.is_empty());
+
+        let res: GetUserOrdersResponse = router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetUserOrders {
+                    user_address: user_2.clone(),
+                    target_market: None,
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert!(res.orders.is_empty());
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateMarketStatus {
+            market_id: 0,
+            status: MarketStatus::Active,
+        });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::MarketDelisted {}
+        );
+    }
+}
@@ -1,4 +1,4 @@
-#![allow(unused)]
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
 
 mod common;
 
@@ -11,12 +11,12 @@ mod tests {
             ExecuteMsg, GetMarketBookResponse, GetMarketsResponse, GetUserAsksResponse,
             GetUserBidsResponse, QueryMsg,
         },
-        structs::OrderSide,
+        structs::{CurrencyInfo, OrderSide},
     };
 
     use crate::common::test_utils::{
-        create_market_native_only_pair, instantiate_selene, CashMachine, NATIVE_DENOM_1,
-        NATIVE_DENOM_2, TEST_USER_1,
+        create_market_native_only_pair, instantiate_selene, query_internal_balance, CashMachine,
+        NATIVE_DENOM_1, NATIVE_DENOM_2, TEST_USER_1,
     };
 
     #[test]
@@ -46,7 +46,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -54,6 +54,8 @@ mod tests {
         let msg = QueryMsg::GetUserBids {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserBidsResponse = router
             .wrap()
@@ -64,6 +66,8 @@ mod tests {
         let msg = QueryMsg::GetUserAsks {
             user_address: user_1,
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserAsksResponse = router.wrap().query_wasm_smart(market_addr, &msg).unwrap();
         assert_eq!(res.orders.len(), 0);
@@ -96,7 +100,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -110,6 +114,8 @@ mod tests {
         let msg = QueryMsg::GetUserBids {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserBidsResponse = router
             .wrap()
@@ -132,7 +138,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -140,6 +146,8 @@ mod tests {
         let msg = QueryMsg::GetUserBids {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res_after_update: GetUserBidsResponse = router
             .wrap()
@@ -177,7 +185,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -185,6 +193,8 @@ mod tests {
         let msg = QueryMsg::GetUserBids {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserBidsResponse = router
             .wrap()
@@ -195,6 +205,8 @@ mod tests {
         let msg = QueryMsg::GetUserAsks {
             user_address: user_1,
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserAsksResponse = router.wrap().query_wasm_smart(market_addr, &msg).unwrap();
         assert_eq!(res.orders.len(), 1);
@@ -220,7 +232,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: order_price.clone(),
+            price: order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -229,7 +241,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -237,6 +249,8 @@ mod tests {
         let msg = QueryMsg::GetUserBids {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserBidsResponse = router
             .wrap()
@@ -247,6 +261,8 @@ mod tests {
         let msg = QueryMsg::GetUserAsks {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserAsksResponse = router
             .wrap()
@@ -286,6 +302,8 @@ mod tests {
         let msg = QueryMsg::GetUserBids {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserBidsResponse = router
             .wrap()
@@ -296,6 +314,8 @@ mod tests {
         let msg = QueryMsg::GetUserAsks {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserAsksResponse = router
             .wrap()
@@ -315,13 +335,30 @@ mod tests {
         assert_eq!(res.bids.len(), 0);
         assert_eq!(res.asks.len(), 0);
 
-        // check user balance
-        let balance_post_cancel = router
+        // refund should have been credited to the internal balance of the user
+        let balance_post_cancel =
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2);
+        assert_eq!(balance_post_cancel, amount_order.amount);
+
+        // withdraw it
+        let msg = ExecuteMsg::Withdraw {
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            amount: amount_order.amount,
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let balance_post_withdraw = router
             .wrap()
             .query_balance(user_1.clone(), NATIVE_DENOM_2)
             .unwrap();
-        assert!(!balance_post_cancel.amount.is_zero());
-        assert_eq!(balance_post_cancel, amount_order);
+        assert_eq!(balance_post_withdraw, amount_order);
+        assert!(
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_2).is_zero()
+        );
     }
 
     mod native_taker_orders {
@@ -350,7 +387,7 @@ mod tests {
 
             let msg = ExecuteMsg::LimitOrder {
                 market_id: 0,
-                price: order_price.clone(),
+                price: order_price,
                 //order_side: OrderSide::Buy,
             };
 
@@ -359,7 +396,7 @@ mod tests {
                     user_1.clone(),
                     market_addr.clone(),
                     &msg,
-                    &[amount_order.clone()],
+                    std::slice::from_ref(&amount_order),
                 )
                 .unwrap();
 
@@ -376,7 +413,7 @@ mod tests {
 
             let msg = ExecuteMsg::LimitOrder {
                 market_id: 0,
-                price: order_price.clone(),
+                price: order_price,
                 //order_side: OrderSide::Buy,
             };
 
@@ -385,17 +422,15 @@ mod tests {
                     user_2.clone(),
                     market_addr.clone(),
                     &msg,
-                    &[amount_order.clone()],
+                    std::slice::from_ref(&amount_order),
                 )
                 .unwrap();
 
             // check user balance
-            let balance_post_order = router
-                .wrap()
-                .query_balance(user_1.clone(), NATIVE_DENOM_1)
-                .unwrap();
-            assert!(!balance_post_order.amount.is_zero());
-            assert_eq!(balance_post_order, amount_order);
+            let balance_post_order =
+                query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_1);
+            assert!(!balance_post_order.is_zero());
+            assert_eq!(balance_post_order, amount_order.amount);
         }
 
         #[test]
@@ -419,7 +454,7 @@ mod tests {
 
             let msg = ExecuteMsg::LimitOrder {
                 market_id: 0,
-                price: order_price.clone(),
+                price: order_price,
                 //order_side: OrderSide::Buy,
             };
 
@@ -428,7 +463,7 @@ mod tests {
                     user_1.clone(),
                     market_addr.clone(),
                     &msg,
-                    &[amount_order.clone()],
+                    std::slice::from_ref(&amount_order),
                 )
                 .unwrap();
 
@@ -445,7 +480,7 @@ mod tests {
 
             let msg = ExecuteMsg::LimitOrder {
                 market_id: 0,
-                price: order_price.clone(),
+                price: order_price,
                 //order_side: OrderSide::Buy,
             };
 
@@ -454,16 +489,14 @@ mod tests {
                     user_2.clone(),
                     market_addr.clone(),
                     &msg,
-                    &[amount_order.clone()],
+                    std::slice::from_ref(&amount_order),
                 )
                 .unwrap();
 
             // check user balance
-            let balance_post_order = router
-                .wrap()
-                .query_balance(user_1.clone(), NATIVE_DENOM_1)
-                .unwrap();
-            assert!(!balance_post_order.amount.is_zero());
+            let balance_post_order =
+                query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_1);
+            assert!(!balance_post_order.is_zero());
             //assert_eq!(balance_post_order, amount_order);
         }
     }
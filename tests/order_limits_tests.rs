@@ -0,0 +1,138 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{AdminExecuteMsg, ExecuteMsg},
+        structs::OrderLimits,
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        assert_holdings_match_escrow, create_market_native_only_pair, instantiate_selene,
+        send_native_order, CashMachine, NATIVE_DENOM_2, TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    fn try_bid(
+        router: &mut App,
+        contract_addr: &Addr,
+        user: &Addr,
+        price: Decimal,
+    ) -> ContractError {
+        let coin = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(1000),
+        };
+        router.mint_native(user, coin.clone());
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: price,
+        };
+        let err = router
+            .execute_contract(user.clone(), contract_addr.clone(), &msg, &[coin])
+            .unwrap_err();
+        return err.downcast::<ContractError>().unwrap();
+    }
+
+    /// new orders are rejected above the caps, adding to an existing order is not a new order
+    #[test]
+    fn order_limits_per_user_and_level() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+        let user_3 = Addr::unchecked(TEST_ADMIN);
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateOrderLimits {
+            order_limits: OrderLimits {
+                max_orders_per_user: Some(2),
+                max_orders_per_level: Some(2),
+            },
+        });
+        router
+            .execute_contract(user_3.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let price_1 = Decimal::percent(50);
+        let price_2 = Decimal::percent(60);
+        let price_3 = Decimal::percent(70);
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price_1),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price_2),
+        );
+        assert_eq!(
+            try_bid(&mut router, &market_addr, &user_1, price_3),
+            ContractError::TooManyUserOrders { max: 2 }
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price_2),
+        );
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price_1),
+        );
+        assert_eq!(
+            try_bid(&mut router, &market_addr, &user_3, price_1),
+            ContractError::LevelFull { max: 2 }
+        );
+
+        // cancelling an order frees a slot
+        let msg = ExecuteMsg::RemoveLimitOrder {
+            market_id: 0,
+            price: price_2,
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price_3),
+        );
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // caps must allow an order
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateOrderLimits {
+            order_limits: OrderLimits {
+                max_orders_per_user: Some(0),
+                max_orders_per_level: None,
+            },
+        });
+        let err = router
+            .execute_contract(user_3.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidOrderLimits {}
+        );
+    }
+}
@@ -1,22 +1,25 @@
-#![allow(unused)]
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
 
 mod common;
 
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+    use cosmwasm_std::{Addr, Coin, Decimal, Decimal256, Uint128, Uint256};
     use cw_multi_test::{App, Executor};
     use selene_markets::{
         msg::{
-            ExecuteMsg, GetMarketBookResponse, GetMarketsResponse, GetUserAsksResponse,
-            GetUserBidsResponse, QueryMsg,
+            AdminExecuteMsg, ExecuteMsg, GetDepthResponse, GetMarketBookResponse,
+            GetMarketSummariesResponse, GetMarketSummaryResponse, GetMarketsResponse,
+            GetOrderResponse, GetUserAsksResponse, GetUserBidsResponse, GetUserOrdersResponse,
+            QueryMsg,
         },
-        structs::OrderSide,
+        structs::{CurrencyInfo, DepthBucket, OrderSide, OrderStatus},
+        ContractError,
     };
 
     use crate::common::test_utils::{
-        create_market_native_only_pair, instantiate_selene, CashMachine, NATIVE_DENOM_1,
-        NATIVE_DENOM_2, TEST_USER_1,
+        create_market_native_only_pair, instantiate_cw20, instantiate_selene, send_native_order,
+        CashMachine, NATIVE_DENOM_1, NATIVE_DENOM_2, TEST_ADMIN, TEST_USER_1, TEST_USER_2,
     };
 
     #[test]
@@ -45,7 +48,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -68,7 +71,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -76,6 +79,8 @@ mod tests {
         let msg = QueryMsg::GetUserBids {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserBidsResponse = router
             .wrap()
@@ -86,6 +91,8 @@ mod tests {
         let msg = QueryMsg::GetUserAsks {
             user_address: user_1,
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserAsksResponse = router
             .wrap()
@@ -102,4 +109,594 @@ mod tests {
         assert_eq!(res.bids.len(), 1);
         assert_eq!(res.asks.len(), 1);
     }
+
+    /// decimals are read from cw20 tokens and given for natives
+    /// book and user orders show prices and quantities in whole units
+    #[test]
+    fn queries_human_scaled_prices() {
+        let (mut router, market_addr) = instantiate_selene();
+        let admin = Addr::unchecked(TEST_ADMIN);
+        let user_1 = Addr::unchecked(TEST_USER_1);
+
+        // native currencies need their decimals
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::AddMarket {
+            base_currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_1.into(),
+            },
+            quote_currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            base_decimals: Some(6),
+            quote_decimals: None,
+            allow_inverse: None,
+        });
+        let err = router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::MissingDecimals {}
+        );
+
+        // heur with 6 decimals against husd with 18 decimals
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::AddMarket {
+            base_currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_1.into(),
+            },
+            quote_currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            base_decimals: Some(6),
+            quote_decimals: Some(18),
+            allow_inverse: None,
+        });
+        router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // cw20 decimals are read from the token
+        let cw20_addr = instantiate_cw20(&mut router, 8);
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::AddMarket {
+            base_currency: CurrencyInfo::Cw20 {
+                address: cw20_addr.to_string(),
+            },
+            quote_currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+            base_decimals: None,
+            quote_decimals: Some(18),
+            allow_inverse: None,
+        });
+        router
+            .execute_contract(admin.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let res: GetMarketsResponse = router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetMarkets {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(res.markets[1].base_decimals, 8);
+        assert_eq!(res.markets[1].quote_decimals, 18);
+
+        // bid 1.5 husd at 1.5 husd per heur, so for 1 heur
+        // in atomic units, 1.5 * 10^18 husd at 1.5 * 10^12 per unit of heur
+        let amount_order = Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(1_500_000_000_000_000_000),
+        };
+        router.mint_native(&user_1, amount_order.clone());
+
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: Decimal::from_atomics(Uint128::new(1_500_000_000_000), 0).unwrap(),
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[amount_order])
+            .unwrap();
+
+        let human_price = Decimal256::from_atomics(Uint128::new(15), 1).unwrap();
+        let msg = QueryMsg::GetMarketBook {
+            market_id: 0,
+            nb_levels: 10,
+        };
+        let res: GetMarketBookResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.bids[0].quantity, Uint256::from(1_000_000u128));
+        assert_eq!(res.bids[0].human_price, human_price);
+        assert_eq!(res.bids[0].human_quantity, Decimal256::one());
+
+        let msg = QueryMsg::GetUserOrders {
+            user_address: user_1.clone(),
+            target_market: Some(0),
+            start_after: None,
+            limit: None,
+        };
+        let res: GetUserOrdersResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.orders[0].human_price, human_price);
+        assert_eq!(res.orders[0].human_quantity, Decimal256::one());
+    }
+
+    /// pages follow the ids of orders and markets, and carry the cursor of the next page
+    #[test]
+    fn queries_paginated() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let user_1 = Addr::unchecked(TEST_USER_1);
+
+        // bids at 0.1 to 0.5, asks at 2 to 3
+        for i in 1..=5u64 {
+            send_native_order(
+                &mut router,
+                &market_addr,
+                &user_1,
+                NATIVE_DENOM_2,
+                1000,
+                Some(Decimal::percent(10 * i)),
+            );
+        }
+        for i in 0..3u64 {
+            send_native_order(
+                &mut router,
+                &market_addr,
+                &user_1,
+                NATIVE_DENOM_1,
+                1000,
+                Some(Decimal::percent(200 + 50 * i)),
+            );
+        }
+
+        let mut order_ids = vec![];
+        let mut start_after = None;
+        loop {
+            let msg = QueryMsg::GetUserOrders {
+                user_address: user_1.clone(),
+                target_market: None,
+                start_after: start_after,
+                limit: Some(3),
+            };
+            let res: GetUserOrdersResponse = router
+                .wrap()
+                .query_wasm_smart(market_addr.clone(), &msg)
+                .unwrap();
+            assert!(res.orders.len() <= 3);
+            order_ids.extend(res.orders.iter().map(|order| order.order.order_id));
+            start_after = res.next_start_after;
+            if start_after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(order_ids, (0..8).collect::<Vec<u64>>());
+
+        // sides are filtered before the page is cut
+        let msg = QueryMsg::GetUserAsks {
+            user_address: user_1.clone(),
+            target_market: None,
+            start_after: Some(5),
+            limit: Some(2),
+        };
+        let res: GetUserAsksResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.orders.len(), 2);
+        assert_eq!(res.orders[0].order_id, 6);
+        assert_eq!(res.next_start_after, None);
+
+        let msg = QueryMsg::GetUserBids {
+            user_address: user_1.clone(),
+            target_market: Some(0),
+            start_after: None,
+            limit: Some(5),
+        };
+        let res: GetUserBidsResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.orders.len(), 5);
+        assert_eq!(res.next_start_after, None);
+
+        let msg = QueryMsg::GetMarkets {
+            start_after: Some(0),
+            limit: None,
+        };
+        let res: GetMarketsResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert!(res.markets.is_empty());
+        assert_eq!(res.next_start_after, None);
+    }
+
+    fn query_order(router: &App, market_addr: &Addr, order_id: u64) -> GetOrderResponse {
+        return router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetOrder { order_id: order_id },
+            )
+            .unwrap();
+    }
+
+    /// orders can be followed until they are closed, and for the retention window afterwards
+    #[test]
+    fn queries_order_status() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+        let price = Decimal::percent(50);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(price),
+        );
+        let res = query_order(&router, &market_addr, 0);
+        assert_eq!(res.owner, user_1);
+        assert_eq!(res.order_side, OrderSide::Buy);
+        assert_eq!(res.status, OrderStatus::Open);
+        assert_eq!(res.original_quantity, Uint128::new(2000));
+        assert_eq!(res.average_fill_price, None);
+        assert_eq!(res.created_at, router.block_info().time);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            500,
+            None,
+        );
+        let res = query_order(&router, &market_addr, 0);
+        assert_eq!(res.status, OrderStatus::PartiallyFilled);
+        assert_eq!(res.remaining_quantity, Uint128::new(1500));
+        assert_eq!(res.filled_quantity, Uint128::new(500));
+        assert_eq!(res.average_fill_price, Some(price));
+
+        router.update_block(|block| block.time = block.time.plus_seconds(60));
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            1500,
+            Some(price),
+        );
+        let res = query_order(&router, &market_addr, 0);
+        assert_eq!(res.status, OrderStatus::Filled);
+        assert_eq!(res.remaining_quantity, Uint128::zero());
+        assert_eq!(res.filled_quantity, Uint128::new(2000));
+        assert_eq!(res.closed_at, Some(router.block_info().time));
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            1000,
+            Some(Decimal::percent(25)),
+        );
+        let msg = ExecuteMsg::RemoveLimitOrder {
+            market_id: 0,
+            price: Decimal::percent(25),
+        };
+        router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+        let res = query_order(&router, &market_addr, 1);
+        assert_eq!(res.status, OrderStatus::Cancelled);
+        assert_eq!(res.remaining_quantity, Uint128::new(4000));
+
+        // closed orders are gone once the retention window is over
+        router.update_block(|block| block.time = block.time.plus_seconds(7 * 86_400));
+        for order_id in [0, 1, 2] {
+            router
+                .wrap()
+                .query_wasm_smart::<GetOrderResponse>(
+                    market_addr.clone(),
+                    &QueryMsg::GetOrder { order_id: order_id },
+                )
+                .unwrap_err();
+        }
+    }
+
+    /// place a native limit order on market 0 and return the order id it emits, if any
+    fn place_limit_order(
+        router: &mut App,
+        market_addr: &Addr,
+        user: &Addr,
+        denom: &str,
+        amount: u128,
+        price: Decimal,
+    ) -> Option<u64> {
+        let coin = Coin {
+            denom: denom.into(),
+            amount: Uint128::new(amount),
+        };
+        router.mint_native(user, coin.clone());
+        let msg = ExecuteMsg::LimitOrder {
+            market_id: 0,
+            price: price,
+        };
+        let res = router
+            .execute_contract(user.clone(), market_addr.clone(), &msg, &[coin])
+            .unwrap();
+
+        return res
+            .events
+            .iter()
+            .filter(|event| event.ty == "wasm")
+            .flat_map(|event| event.attributes.iter())
+            .find(|attribute| attribute.key == "order_id")
+            .map(|attribute| attribute.value.parse().unwrap());
+    }
+
+    /// orders resting in the book give their id, which is then used to follow them
+    #[test]
+    fn queries_order_id_attribute() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+        let price = Decimal::percent(50);
+
+        // a new order, then one merged into it
+        let order_id = place_limit_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            500,
+            price,
+        );
+        assert_eq!(order_id, Some(0));
+        let order_id = place_limit_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            500,
+            price,
+        );
+        assert_eq!(order_id, Some(0));
+        let res = query_order(&router, &market_addr, 0);
+        assert_eq!(res.owner, user_1);
+        assert_eq!(res.original_quantity, Uint128::new(2000));
+
+        // a sell of 3000 fills the bid of 2000, the rest of it rests
+        let order_id = place_limit_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            3000,
+            price,
+        )
+        .unwrap();
+        let res = query_order(&router, &market_addr, order_id);
+        assert_eq!(res.owner, user_2);
+        assert_eq!(res.order_side, OrderSide::Sell);
+        assert_eq!(res.status, OrderStatus::PartiallyFilled);
+        assert_eq!(res.filled_quantity, Uint128::new(2000));
+        assert_eq!(res.remaining_quantity, Uint128::new(1000));
+
+        // a fully filled order rests nothing, so it has no id to follow
+        let order_id = place_limit_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            100,
+            price,
+        );
+        assert_eq!(order_id, None);
+    }
+
+    /// the summary gives the top of book on both sides, and follows trades
+    #[test]
+    fn queries_market_summary() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        let msg = QueryMsg::GetMarketSummary { market_id: 0 };
+        let res: GetMarketSummaryResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.summary.best_bid, None);
+        assert_eq!(res.summary.spread, None);
+        assert_eq!(res.summary.last_trade, None);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            800,
+            Some(Decimal::percent(80)),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_1,
+            500,
+            Some(Decimal::percent(120)),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            100,
+            None,
+        );
+
+        let res: GetMarketSummaryResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        let summary = res.summary;
+        let best_bid = summary.best_bid.unwrap();
+        assert_eq!(best_bid.price, Decimal::percent(80));
+        assert_eq!(best_bid.quantity, Uint256::from(900u128));
+        assert_eq!(summary.best_ask.unwrap().quantity, Uint256::from(500u128));
+        assert_eq!(summary.spread, Some(Decimal::percent(40)));
+        assert_eq!(summary.spread_bps, Some(Uint128::new(4000)));
+        assert_eq!(summary.midprice, Some(Decimal::one()));
+
+        let last_trade = summary.last_trade.unwrap();
+        assert_eq!(last_trade.price, Decimal::percent(80));
+        assert_eq!(last_trade.time, router.block_info().time);
+        assert_eq!(summary.volume_24h.base, Uint128::new(100));
+        assert_eq!(summary.volume_24h.quote, Uint128::new(80));
+
+        // the volume leaves the window after a day
+        router.update_block(|block| block.time = block.time.plus_seconds(86_400));
+        let msg = QueryMsg::GetMarketSummaries {
+            start_after: None,
+            limit: None,
+        };
+        let res: GetMarketSummariesResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.summaries.len(), 1);
+        assert!(res.summaries[0].volume_24h.base.is_zero());
+        assert_eq!(res.next_start_after, None);
+    }
+
+    /// levels are grouped down for bids and up for asks, with their cumulative depth
+    #[test]
+    fn queries_depth_buckets() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        let user_1 = Addr::unchecked(TEST_USER_1);
+
+        for (amount, percent) in [(51, 51), (110, 55), (42, 42)] {
+            send_native_order(
+                &mut router,
+                &market_addr,
+                &user_1,
+                NATIVE_DENOM_2,
+                amount,
+                Some(Decimal::percent(percent)),
+            );
+        }
+        for percent in [61, 68, 75] {
+            send_native_order(
+                &mut router,
+                &market_addr,
+                &user_1,
+                NATIVE_DENOM_1,
+                100,
+                Some(Decimal::percent(percent)),
+            );
+        }
+
+        let msg = QueryMsg::GetDepth {
+            market_id: 0,
+            bucket_size: Decimal::percent(10),
+            max_buckets: 10,
+        };
+        let res: GetDepthResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(
+            res.bids,
+            vec![
+                DepthBucket {
+                    price: Decimal::percent(50),
+                    quantity: Uint256::from(300u128),
+                    cumulative_quantity: Uint256::from(300u128),
+                    cumulative_notional: Uint256::from(161u128),
+                },
+                DepthBucket {
+                    price: Decimal::percent(40),
+                    quantity: Uint256::from(100u128),
+                    cumulative_quantity: Uint256::from(400u128),
+                    cumulative_notional: Uint256::from(203u128),
+                },
+            ]
+        );
+        assert_eq!(
+            res.asks,
+            vec![
+                DepthBucket {
+                    price: Decimal::percent(70),
+                    quantity: Uint256::from(200u128),
+                    cumulative_quantity: Uint256::from(200u128),
+                    cumulative_notional: Uint256::from(129u128),
+                },
+                DepthBucket {
+                    price: Decimal::percent(80),
+                    quantity: Uint256::from(100u128),
+                    cumulative_quantity: Uint256::from(300u128),
+                    cumulative_notional: Uint256::from(204u128),
+                },
+            ]
+        );
+
+        let msg = QueryMsg::GetDepth {
+            market_id: 0,
+            bucket_size: Decimal::percent(10),
+            max_buckets: 1,
+        };
+        let res: GetDepthResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert_eq!(res.bids.len(), 1);
+        assert_eq!(res.asks[0].cumulative_quantity, Uint256::from(200u128));
+
+        let msg = QueryMsg::GetDepth {
+            market_id: 0,
+            bucket_size: Decimal::zero(),
+            max_buckets: 1,
+        };
+        let res: Result<GetDepthResponse, _> =
+            router.wrap().query_wasm_smart(market_addr.clone(), &msg);
+        assert!(res.is_err());
+    }
+
+    /// a bucket size too small for the prices of the book fails instead of aborting
+    #[test]
+    fn queries_depth_small_bucket() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &Addr::unchecked(TEST_USER_1),
+            NATIVE_DENOM_1,
+            100,
+            Some(Decimal::from_ratio(1000u128, 1u128)),
+        );
+
+        let msg = QueryMsg::GetDepth {
+            market_id: 0,
+            bucket_size: Decimal::raw(1),
+            max_buckets: 10,
+        };
+        let res: Result<GetDepthResponse, _> =
+            router.wrap().query_wasm_smart(market_addr.clone(), &msg);
+        assert!(res.is_err());
+    }
 }
@@ -0,0 +1,182 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+    use cw_multi_test::{App, Executor};
+    use selene_markets::{
+        msg::{
+            AdminExecuteMsg, ExecuteMsg, GetProtocolFeesResponse, GetReferralEarningsResponse,
+            GetReferrerResponse, QueryMsg,
+        },
+        structs::CurrencyInfo,
+        ContractError,
+    };
+
+    use crate::common::test_utils::{
+        assert_holdings_match_escrow, create_market_native_only_pair, instantiate_selene,
+        query_bank_balance, send_native_order, set_market_fees, NATIVE_DENOM_1, NATIVE_DENOM_2,
+        TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    const TEST_REFERRER: &str = "referrer";
+
+    /// the referrer of a taker gets its share of the taker fee, and claims it
+    #[test]
+    fn referrals_share_of_taker_fees() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, 0, 20);
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateReferralShare { share_bps: 2500 });
+        router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+        let referrer = Addr::unchecked(TEST_REFERRER);
+
+        let msg = ExecuteMsg::RegisterReferrer {
+            referrer: TEST_REFERRER.into(),
+        };
+        router
+            .execute_contract(user_2.clone(), market_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // a referrer is set once
+        let err = router
+            .execute_contract(user_2.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::ReferrerAlreadySet {}
+        );
+
+        let res: GetReferrerResponse = router
+            .wrap()
+            .query_wasm_smart(
+                market_addr.clone(),
+                &QueryMsg::GetReferrer {
+                    user_address: user_2.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(res.referrer, Some(referrer.clone()));
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            10000,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_1,
+            10000,
+            None,
+        );
+
+        // taker fee of 20, of which 5 go to the referrer
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_2),
+            Uint128::new(9980)
+        );
+        let res: GetProtocolFeesResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &QueryMsg::GetProtocolFees {})
+            .unwrap();
+        assert_eq!(res.fees[0].amount, Uint128::new(15));
+
+        let msg = QueryMsg::GetReferralEarnings {
+            referrer: referrer.clone(),
+        };
+        let res: GetReferralEarningsResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        let expected = vec![Coin {
+            denom: NATIVE_DENOM_2.into(),
+            amount: Uint128::new(5),
+        }];
+        assert_eq!(res.pending, expected);
+        assert_eq!(res.earned, expected);
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        // rewards are only paid out in the currency type they were earned in
+        let claim_msg = ExecuteMsg::ClaimReferralRewards {
+            currency: CurrencyInfo::Cw20 {
+                address: NATIVE_DENOM_2.into(),
+            },
+        };
+        let err = router
+            .execute_contract(referrer.clone(), market_addr.clone(), &claim_msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::ZeroAmount {}
+        );
+
+        let claim_msg = ExecuteMsg::ClaimReferralRewards {
+            currency: CurrencyInfo::Native {
+                denom: NATIVE_DENOM_2.into(),
+            },
+        };
+        router
+            .execute_contract(referrer.clone(), market_addr.clone(), &claim_msg, &[])
+            .unwrap();
+        assert_eq!(
+            query_bank_balance(&router, &referrer, NATIVE_DENOM_2),
+            Uint128::new(5)
+        );
+
+        // claimed rewards are no longer pending, but still count as earned
+        let res: GetReferralEarningsResponse = router
+            .wrap()
+            .query_wasm_smart(market_addr.clone(), &msg)
+            .unwrap();
+        assert!(res.pending.is_empty());
+        assert_eq!(res.earned, expected);
+        assert_holdings_match_escrow(&router, market_addr.clone());
+
+        let err = router
+            .execute_contract(referrer.clone(), market_addr.clone(), &claim_msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::ZeroAmount {}
+        );
+    }
+
+    #[test]
+    fn referrals_invalid() {
+        let (mut router, market_addr) = instantiate_selene();
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let msg = ExecuteMsg::RegisterReferrer {
+            referrer: TEST_USER_1.into(),
+        };
+        let err = router
+            .execute_contract(user_1.clone(), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidReferrer {}
+        );
+
+        let msg = ExecuteMsg::Admin(AdminExecuteMsg::UpdateReferralShare { share_bps: 5001 });
+        let err = router
+            .execute_contract(Addr::unchecked(TEST_ADMIN), market_addr.clone(), &msg, &[])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<ContractError>().unwrap(),
+            ContractError::InvalidReferralShare { max_bps: 5000 }
+        );
+    }
+}
@@ -0,0 +1,269 @@
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
+
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{Addr, Decimal, Uint128};
+    use cw_multi_test::App;
+    use selene_markets::{
+        msg::{QueryMsg, SimulateMarketOrderResponse, SimulateReverseResponse},
+        structs::OrderSide,
+    };
+
+    use crate::common::test_utils::{
+        create_market_native_only_pair, instantiate_selene, query_bank_balance, send_native_order,
+        set_market_fees, NATIVE_DENOM_1, NATIVE_DENOM_2, TEST_ADMIN, TEST_USER_1, TEST_USER_2,
+    };
+
+    fn simulate_market_order(
+        router: &App,
+        contract_addr: &Addr,
+        offer_currency: &str,
+        offer_amount: u128,
+    ) -> SimulateMarketOrderResponse {
+        let msg = QueryMsg::SimulateMarketOrder {
+            market_id: 0,
+            offer_currency: offer_currency.into(),
+            offer_amount: Uint128::new(offer_amount),
+            user_address: None,
+        };
+        return router
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &msg)
+            .unwrap();
+    }
+
+    fn simulate_reverse(
+        router: &App,
+        contract_addr: &Addr,
+        ask_currency: &str,
+        ask_amount: u128,
+    ) -> SimulateReverseResponse {
+        let msg = QueryMsg::SimulateReverse {
+            market_id: 0,
+            ask_currency: ask_currency.into(),
+            ask_amount: Uint128::new(ask_amount),
+            user_address: None,
+        };
+        return router
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &msg)
+            .unwrap();
+    }
+
+    /// the quote of a market order is what the order then receives
+    #[test]
+    fn simulate_market_order_matches_execution() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, 0, 100);
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_1,
+            100,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_1,
+            100,
+            Some(Decimal::percent(120)),
+        );
+
+        let res = simulate_market_order(&router, &market_addr, NATIVE_DENOM_2, 160);
+        assert_eq!(
+            res,
+            SimulateMarketOrderResponse {
+                order_side: OrderSide::Buy,
+                offer_used: Uint128::new(160),
+                refund_amount: Uint128::zero(),
+                return_amount: Uint128::new(149),
+                fee_amount: Uint128::new(1),
+                taker_fee_bps: 100,
+                average_price: Some(Decimal::from_ratio(160u128, 150u128)),
+                worst_price: Some(Decimal::percent(120)),
+                levels_consumed: 2,
+                enough_liquidity: true,
+            }
+        );
+
+        // the book is left as it was
+        let res_again = simulate_market_order(&router, &market_addr, NATIVE_DENOM_2, 160);
+        assert_eq!(res_again, res);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_2,
+            160,
+            None,
+        );
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_1),
+            res.return_amount
+        );
+
+        // more than the book holds
+        let res = simulate_market_order(&router, &market_addr, NATIVE_DENOM_2, 1000);
+        assert_eq!(res.offer_used, Uint128::new(60));
+        assert_eq!(res.refund_amount, Uint128::new(940));
+        assert_eq!(res.levels_consumed, 1);
+        assert!(!res.enough_liquidity);
+
+        // nothing to sell into
+        let res = simulate_market_order(&router, &market_addr, NATIVE_DENOM_1, 10);
+        assert_eq!(res.order_side, OrderSide::Sell);
+        assert_eq!(res.average_price, None);
+        assert!(res.return_amount.is_zero());
+        assert!(!res.enough_liquidity);
+
+        let msg = QueryMsg::SimulateMarketOrder {
+            market_id: 0,
+            offer_currency: "other".into(),
+            offer_amount: Uint128::new(10),
+            user_address: None,
+        };
+        let res: Result<SimulateMarketOrderResponse, _> =
+            router.wrap().query_wasm_smart(market_addr.clone(), &msg);
+        assert!(res.is_err());
+    }
+
+    /// the offer is the smallest one which receives the amount asked after fees
+    #[test]
+    fn simulate_reverse_smallest_offer() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, 0, 100);
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_1,
+            100,
+            Some(Decimal::one()),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_1,
+            100,
+            Some(Decimal::percent(120)),
+        );
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_1,
+            NATIVE_DENOM_2,
+            100,
+            Some(Decimal::percent(50)),
+        );
+
+        // 150 are bought, 1 of which is the fee
+        let res = simulate_reverse(&router, &market_addr, NATIVE_DENOM_1, 149);
+        assert_eq!(
+            res,
+            SimulateReverseResponse {
+                order_side: OrderSide::Buy,
+                offer_amount: Uint128::new(160),
+                return_amount: Uint128::new(149),
+                fee_amount: Uint128::new(1),
+                taker_fee_bps: 100,
+                average_price: Some(Decimal::from_ratio(160u128, 150u128)),
+                worst_price: Some(Decimal::percent(120)),
+                price_impact_bps: Some(Uint128::new(666)),
+                enough_liquidity: true,
+            }
+        );
+        let smaller = simulate_market_order(&router, &market_addr, NATIVE_DENOM_2, 159);
+        assert!(smaller.return_amount < Uint128::new(149));
+
+        // selling base currency for quote currency, the fee on 49 rounds down to nothing
+        let res = simulate_reverse(&router, &market_addr, NATIVE_DENOM_2, 49);
+        assert_eq!(res.order_side, OrderSide::Sell);
+        assert_eq!(res.offer_amount, Uint128::new(98));
+        assert_eq!(res.return_amount, Uint128::new(49));
+        assert_eq!(res.price_impact_bps, Some(Uint128::zero()));
+
+        // the book does not hold enough, the offer takes the whole side
+        let res = simulate_reverse(&router, &market_addr, NATIVE_DENOM_1, 1000);
+        assert_eq!(res.offer_amount, Uint128::new(220));
+        assert_eq!(res.return_amount, Uint128::new(198));
+        assert!(!res.enough_liquidity);
+
+        // an amount whose gross doesn't fit is an error, not an abort
+        let msg = QueryMsg::SimulateReverse {
+            market_id: 0,
+            ask_currency: NATIVE_DENOM_1.into(),
+            ask_amount: Uint128::MAX,
+            user_address: None,
+        };
+        router
+            .wrap()
+            .query_wasm_smart::<SimulateReverseResponse>(market_addr.clone(), &msg)
+            .unwrap_err();
+
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &user_2,
+            NATIVE_DENOM_2,
+            160,
+            None,
+        );
+        assert_eq!(
+            query_bank_balance(&router, &user_2, NATIVE_DENOM_1),
+            Uint128::new(149)
+        );
+    }
+
+    /// fills are rounded down one by one, so the offer can need more than the value of the levels
+    #[test]
+    fn simulate_reverse_covers_rounding() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+        set_market_fees(&mut router, &market_addr, 0, 0, 0);
+
+        // each order is worth 102.9, of which 102 are paid
+        let price = Decimal::percent(70);
+        for user in [TEST_USER_1, TEST_USER_2, TEST_ADMIN] {
+            send_native_order(
+                &mut router,
+                &market_addr,
+                &Addr::unchecked(user),
+                NATIVE_DENOM_1,
+                147,
+                Some(price),
+            );
+        }
+        send_native_order(
+            &mut router,
+            &market_addr,
+            &Addr::unchecked(TEST_USER_1),
+            NATIVE_DENOM_1,
+            10,
+            Some(Decimal::one()),
+        );
+
+        // the first level takes 309 to be consumed, though its orders are paid 306
+        let res = simulate_reverse(&router, &market_addr, NATIVE_DENOM_1, 442);
+        assert_eq!(res.offer_amount, Uint128::new(309));
+        assert!(res.return_amount >= Uint128::new(442));
+        assert!(res.enough_liquidity);
+
+        let smaller = simulate_market_order(&router, &market_addr, NATIVE_DENOM_2, 308);
+        assert!(smaller.return_amount < Uint128::new(442));
+    }
+}
@@ -1,4 +1,4 @@
-#![allow(unused)]
+#![allow(unused, clippy::needless_return, clippy::redundant_field_names)]
 
 mod common;
 
@@ -16,12 +16,12 @@ mod tests {
     };
 
     use crate::common::test_utils::{
-        create_market_native_only_pair, instantiate_selene, CashMachine, NATIVE_DENOM_1,
-        NATIVE_DENOM_2, TEST_USER_1, TEST_USER_2,
+        create_market_native_only_pair, instantiate_selene, query_internal_balance, CashMachine,
+        NATIVE_DENOM_1, NATIVE_DENOM_2, TEST_USER_1, TEST_USER_2,
     };
 
-    pub const NATIVE_DENOM_1_EUR: &'static str = "heur";
-    pub const NATIVE_DENOM_2_USD: &'static str = "husd";
+    pub const NATIVE_DENOM_1_EUR: &str = "heur";
+    pub const NATIVE_DENOM_2_USD: &str = "husd";
 
     #[test]
     fn only_native_limit_taker() {
@@ -44,7 +44,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: order_price.clone(),
+            price: order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -53,7 +53,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -79,7 +79,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: order_price.clone(),
+            price: order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -88,7 +88,7 @@ mod tests {
                 user_2.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -103,12 +103,10 @@ mod tests {
             .unwrap();
         println!("book: {:?}", res);
 
-        // user_1 should have received NATIVE_DENOM_1
-        let balance_post_order = router
-            .wrap()
-            .query_balance(user_1.clone(), NATIVE_DENOM_1_EUR)
-            .unwrap();
-        assert!(!balance_post_order.amount.is_zero());
+        // user_1 should have been credited NATIVE_DENOM_1
+        let balance_post_order =
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_1_EUR);
+        assert!(!balance_post_order.is_zero());
         println!("balance: {}", balance_post_order);
 
         // user_2 should have received NATIVE_DENOM_2
@@ -143,7 +141,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: order_price.clone(),
+            price: order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -152,7 +150,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -177,7 +175,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: order_price.clone(),
+            price: order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -186,7 +184,7 @@ mod tests {
                 user_2.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -208,17 +206,21 @@ mod tests {
         let msg = QueryMsg::GetUserOrders {
             user_address: user_2.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserOrdersResponse = router
             .wrap()
             .query_wasm_smart(market_addr.clone(), &msg)
             .unwrap();
         assert_eq!(res.orders.len(), 1);
-        assert_eq!(res.orders[0].order_side, OrderSide::Sell);
+        assert_eq!(res.orders[0].order.order_side, OrderSide::Sell);
 
         let msg = QueryMsg::GetUserOrders {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserOrdersResponse = router
             .wrap()
@@ -250,7 +252,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: order_price.clone(),
+            price: order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -259,7 +261,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -284,7 +286,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: order_price.clone(),
+            price: order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -293,7 +295,7 @@ mod tests {
                 user_2.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -317,17 +319,21 @@ mod tests {
         let msg = QueryMsg::GetUserOrders {
             user_address: user_2.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserOrdersResponse = router
             .wrap()
             .query_wasm_smart(market_addr.clone(), &msg)
             .unwrap();
         assert_eq!(res.orders.len(), 1);
-        assert_eq!(res.orders[0].order_side, OrderSide::Sell);
+        assert_eq!(res.orders[0].order.order_side, OrderSide::Sell);
 
         let msg = QueryMsg::GetUserOrders {
             user_address: user_1.clone(),
             target_market: Some(0),
+            start_after: None,
+            limit: None,
         };
         let res: GetUserOrdersResponse = router
             .wrap()
@@ -371,7 +377,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: bid_order_price.clone(),
+            price: bid_order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -380,7 +386,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -408,7 +414,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: ask_order_price.clone(),
+            price: ask_order_price,
         };
 
         let _res = router
@@ -416,7 +422,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -443,7 +449,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: bid_order_price.clone(),
+            price: bid_order_price,
         };
 
         let _res = router
@@ -451,7 +457,7 @@ mod tests {
                 user_2.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -487,7 +493,7 @@ mod tests {
 
         let msg = ExecuteMsg::LimitOrder {
             market_id: 0,
-            price: bid_order_price.clone(),
+            price: bid_order_price,
             //order_side: OrderSide::Buy,
         };
 
@@ -496,7 +502,7 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
@@ -524,11 +530,14 @@ mod tests {
                 user_1.clone(),
                 market_addr.clone(),
                 &msg,
-                &[amount_order.clone()],
+                std::slice::from_ref(&amount_order),
             )
             .unwrap();
 
-        let msg = QueryMsg::GetMarketBook { market_id: 0, nb_levels: 10 };
+        let msg = QueryMsg::GetMarketBook {
+            market_id: 0,
+            nb_levels: 10,
+        };
         let res: GetMarketBookResponse = router
             .wrap()
             .query_wasm_smart(market_addr.clone(), &msg)
@@ -536,5 +545,83 @@ mod tests {
         println!("book: {:?}", res);
     }
 
+    /// a maker filled at several levels in a single sweep should be credited once
+    /// and the taker should receive a single transfer
+    #[test]
+    fn native_market_order_aggregated_settlement() {
+        let (mut router, market_addr) = instantiate_selene();
+        create_market_native_only_pair(&mut router, market_addr.clone());
+
+        let user_1 = Addr::unchecked(TEST_USER_1);
+        let user_2 = Addr::unchecked(TEST_USER_2);
+
+        // user_1 sets two bids at different levels
+        let amount_order = Coin {
+            denom: NATIVE_DENOM_2_USD.into(),
+            amount: Uint128::new(200),
+        };
+        router.mint_native(&user_1, amount_order.clone());
 
+        for price in [
+            Decimal::one(),
+            Decimal::from_atomics(Uint128::new(5), 1).unwrap(),
+        ] {
+            let msg = ExecuteMsg::LimitOrder {
+                market_id: 0,
+                price: price,
+            };
+
+            router
+                .execute_contract(
+                    user_1.clone(),
+                    market_addr.clone(),
+                    &msg,
+                    &[Coin {
+                        denom: NATIVE_DENOM_2_USD.into(),
+                        amount: Uint128::new(100),
+                    }],
+                )
+                .unwrap();
+        }
+
+        // user_2 sweeps both levels with a market order
+        let amount_order = Coin {
+            denom: NATIVE_DENOM_1_EUR.into(),
+            amount: Uint128::new(300),
+        };
+        router.mint_native(&user_2, amount_order.clone());
+
+        let msg = ExecuteMsg::MarketOrder { market_id: 0 };
+        let res = router
+            .execute_contract(
+                user_2.clone(),
+                market_addr.clone(),
+                &msg,
+                std::slice::from_ref(&amount_order),
+            )
+            .unwrap();
+
+        let transfers_to_user_2 = res
+            .events
+            .iter()
+            .filter(|event| {
+                event.ty == "transfer"
+                    && event
+                        .attributes
+                        .iter()
+                        .any(|attr| attr.key == "recipient" && attr.value == user_2.as_str())
+            })
+            .count();
+        assert_eq!(transfers_to_user_2, 1);
+
+        let balance_user_1 =
+            query_internal_balance(&router, market_addr.clone(), &user_1, NATIVE_DENOM_1_EUR);
+        assert_eq!(balance_user_1, Uint128::new(300));
+
+        let balance_user_2 = router
+            .wrap()
+            .query_balance(user_2.clone(), NATIVE_DENOM_2_USD)
+            .unwrap();
+        assert_eq!(balance_user_2.amount, Uint128::new(200));
+    }
 }